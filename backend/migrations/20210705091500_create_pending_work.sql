@@ -0,0 +1,17 @@
+/*
+ Work that didn't fit into the rate limit window on the previous run.
+ Drained first on the next scheduler tick, so that no timeline / tweet gets starved forever.
+ */
+CREATE TABLE pending_work
+(
+    -- basics
+    id         uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at timestamptz NOT NULL,
+
+    -- what needs doing
+    job_kind   TEXT        NOT NULL, -- user_timeline / core_tweet / helper_tweet
+    object_id  TEXT        NOT NULL, -- twitter_user_id for timelines, tweet_id for tweets
+
+    UNIQUE (job_kind, object_id)
+);
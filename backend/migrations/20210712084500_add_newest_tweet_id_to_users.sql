@@ -0,0 +1,3 @@
+-- newest tweet we've seen on the user's timeline, passed as since_id so that we only pull new tweets
+ALTER TABLE users
+    ADD COLUMN newest_tweet_id TEXT;
@@ -0,0 +1,26 @@
+/*
+ Append-only history of tweet metrics - a row is added every time a tweet's metrics get refreshed.
+ tweets table keeps the latest counts (for sorting), this keeps how they evolved (for velocity / trends).
+ */
+CREATE TABLE tweet_metric_snapshots
+(
+    -- basics
+    id                  uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at          timestamptz NOT NULL, -- = when the snapshot was taken
+
+    -- v2 metrics
+    like_count          BIGINT      NOT NULL,
+    quote_count         BIGINT      NOT NULL,
+    reply_count         BIGINT      NOT NULL,
+    retweet_count       BIGINT      NOT NULL,
+    total_retweet_count BIGINT      NOT NULL,
+    popularity_count    BIGINT      NOT NULL,
+
+    -- relation to tweets
+    tweet_id            uuid        NOT NULL,
+    FOREIGN KEY (tweet_id)
+        REFERENCES tweets (id)
+);
+
+CREATE INDEX tweet_metric_snapshots_tweet_id_created_at_index ON tweet_metric_snapshots (tweet_id, created_at);
@@ -0,0 +1,16 @@
+/*
+ Keyset pagination used to run on a synthetic BIGINT = CAST(metric || LEFT(tweet_id, 10) AS BIGINT).
+ That can overflow / mis-order as counts grow, so we now paginate on the row value (metric, tweet_id) instead.
+ */
+DROP INDEX popularity_count_special_index;
+DROP INDEX like_count_special_index;
+DROP INDEX quote_count_special_index;
+DROP INDEX reply_count_special_index;
+DROP INDEX retweet_count_special_index;
+DROP INDEX total_retweet_count_special_index;
+
+CREATE INDEX popularity_count_keyset_index ON tweets (popularity_count, tweet_id);
+CREATE INDEX like_count_keyset_index ON tweets (like_count, tweet_id);
+CREATE INDEX reply_count_keyset_index ON tweets (reply_count, tweet_id);
+CREATE INDEX total_retweet_count_keyset_index ON tweets (total_retweet_count, tweet_id);
+CREATE INDEX tweet_created_at_keyset_index ON tweets (tweet_created_at, tweet_id);
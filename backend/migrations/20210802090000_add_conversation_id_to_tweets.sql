@@ -0,0 +1,8 @@
+/*
+ Twitter's conversation_id = id of the tweet that started the conversation (the root of the reply tree).
+ Lets us tell which tweets belong to the same thread. Nullable - tweets stored before this get it on their next refresh.
+ */
+ALTER TABLE tweets
+    ADD COLUMN conversation_id TEXT;
+
+CREATE INDEX conversation_id_index ON tweets (conversation_id);
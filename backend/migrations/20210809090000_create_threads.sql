@@ -0,0 +1,28 @@
+/*
+ A thread (aka tweetstorm) = an author replying to themselves within one conversation.
+ Its tweets point here via tweets.thread_id. The feed only shows the head (earliest stored tweet),
+ the rest is served from /threads/{id}.
+ */
+CREATE TABLE threads
+(
+    -- basics
+    id              uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at      timestamptz NOT NULL,
+
+    -- thread info
+    conversation_id TEXT        NOT NULL,
+    head_tweet_id   TEXT,                 -- = twitter's tweet_id of the earliest tweet we have in the thread
+
+    -- relation to users
+    user_id         uuid        NOT NULL,
+    FOREIGN KEY (user_id)
+        REFERENCES users (id),
+
+    UNIQUE (conversation_id, user_id)
+);
+
+ALTER TABLE tweets
+    ADD COLUMN thread_id uuid REFERENCES threads (id);
+
+CREATE INDEX thread_id_index ON tweets (thread_id);
@@ -0,0 +1,33 @@
+/*
+ Content from sources other than twitter (reddit to begin with). Kept source-agnostic on purpose,
+ so that each new source only needs a scraper - not a new table.
+ */
+CREATE TABLE posts
+(
+    -- basics
+    id            uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at    timestamptz NOT NULL,
+
+    -- where it's from
+    source        TEXT        NOT NULL, -- eg reddit
+    source_id     TEXT        NOT NULL, -- id within the source, eg reddit's t3_o8k2xz
+    channel       TEXT        NOT NULL, -- where within the source, eg the subreddit
+
+    -- post info
+    posted_at     timestamptz NOT NULL,
+    title         TEXT        NOT NULL,
+    body          TEXT,
+    author        TEXT,
+    post_url      TEXT        NOT NULL, -- the post itself
+    link_url      TEXT,                 -- what the post links to, if it's a link post
+
+    -- metrics
+    score         BIGINT,
+    comment_count BIGINT,
+
+    UNIQUE (source, source_id)
+);
+
+CREATE INDEX posts_posted_at_index ON posts (posted_at);
+CREATE INDEX posts_score_index ON posts (score);
@@ -0,0 +1,24 @@
+/*
+ People who get the top tweets emailed to them. Double opt-in - nothing is sent until the address
+ is confirmed through the link in the confirmation email.
+ */
+CREATE TABLE subscribers
+(
+    -- basics
+    id                uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at        timestamptz NOT NULL,
+    email             TEXT        NOT NULL UNIQUE,
+
+    -- what goes into their digest - same values as the /tweets query params
+    sort_by           TEXT        NOT NULL,
+    timeframe         TEXT        NOT NULL, -- also how often the digest goes out
+
+    -- opt-in / opt-out
+    confirm_token     TEXT        NOT NULL UNIQUE,
+    unsubscribe_token TEXT        NOT NULL UNIQUE,
+    confirmed_at      timestamptz,          -- NULL until confirmed
+
+    -- when the last digest went out, NULL if never
+    last_sent_at      timestamptz
+);
@@ -0,0 +1,43 @@
+/*
+ Outbound webhooks - chat channels that get a post whenever a tweet crosses their popularity threshold.
+ Managed by hand (psql) for now, as the urls double as credentials.
+ */
+CREATE TABLE webhooks
+(
+    -- basics
+    id             uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at     timestamptz NOT NULL,
+    name           TEXT        NOT NULL, -- just for us, eg "team slack #news"
+
+    -- where to
+    target         TEXT        NOT NULL, -- slack / discord / telegram - decides the payload format
+    url            TEXT        NOT NULL, -- for telegram: https://api.telegram.org/bot<token>/sendMessage
+    chat_id        TEXT,                 -- telegram only
+
+    -- what
+    min_popularity BIGINT      NOT NULL, -- compared against tweets.popularity_count
+    active         BOOLEAN     NOT NULL DEFAULT TRUE
+);
+
+/*
+ One row per (webhook, tweet) ever attempted - both the delivery log and what stops us posting the same tweet twice.
+ */
+CREATE TABLE webhook_deliveries
+(
+    -- basics
+    id              uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at      timestamptz NOT NULL,
+    webhook_id      uuid        NOT NULL,
+    tweet_id        uuid        NOT NULL,
+
+    -- outcome
+    status          TEXT        NOT NULL, -- pending / delivered / failed. Only failed ones are ever tried again
+    attempts        INT         NOT NULL, -- dispatcher runs that tried it, each with its own retries
+    last_attempt_at timestamptz NOT NULL,
+    response_status INT,                  -- http status of the last try, NULL if we never got one
+    error           TEXT,                 -- why the last try failed
+
+    UNIQUE (webhook_id, tweet_id)
+);
@@ -0,0 +1,19 @@
+/*
+ Which tweets each (anonymous) reader has been served, so that /tweets?unseen=true can hide them.
+ Only kept for seen_tweets_ttl_hours - a scheduled job prunes anything older.
+ */
+CREATE TABLE seen_tweets
+(
+    -- who - an opaque token from a cookie or the X-Reader-Token header, not tied to anything else
+    reader_token TEXT        NOT NULL,
+
+    -- what, when
+    tweet_id     uuid        NOT NULL,
+    FOREIGN KEY (tweet_id)
+        REFERENCES tweets (id),
+    seen_at      timestamptz NOT NULL, -- last time it was served to them
+
+    PRIMARY KEY (reader_token, tweet_id)
+);
+
+CREATE INDEX seen_tweets_seen_at_index ON seen_tweets (seen_at);
@@ -0,0 +1,23 @@
+/*
+ Full-text search documents for tweets, kept up to date by store_tweet.
+ A separate table rather than a column on tweets: sqlx can't decode tsvector, so the column would break
+ every SELECT * FROM tweets.
+ */
+CREATE TABLE tweet_search
+(
+    -- relation to tweets, 1:1
+    tweet_id uuid     NOT NULL,
+    PRIMARY KEY (tweet_id),
+    FOREIGN KEY (tweet_id)
+        REFERENCES tweets (id),
+
+    -- to_tsvector('english', tweet_text)
+    document tsvector NOT NULL
+);
+
+CREATE INDEX tweet_search_document_index ON tweet_search USING GIN (document);
+
+-- everything stored so far
+INSERT INTO tweet_search (tweet_id, document)
+SELECT id, to_tsvector('english', tweet_text)
+FROM tweets;
@@ -0,0 +1,32 @@
+/*
+ Cashtags, hashtags, mentions and links, as parsed out of tweets by twitter.
+ Each distinct entity is stored once (entities), and linked to every tweet it appears in (tweet_entities).
+ */
+CREATE TABLE entities
+(
+    -- basics
+    id         uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at timestamptz NOT NULL,
+
+    -- entity info
+    kind       TEXT        NOT NULL, -- cashtag / hashtag / mention / url
+    value      TEXT        NOT NULL, -- normalized: cashtags upper case (SOL), hashtags / mentions lower case, urls expanded
+
+    UNIQUE (kind, value)
+);
+
+CREATE TABLE tweet_entities
+(
+    tweet_id  uuid NOT NULL,
+    FOREIGN KEY (tweet_id)
+        REFERENCES tweets (id),
+    entity_id uuid NOT NULL,
+    FOREIGN KEY (entity_id)
+        REFERENCES entities (id),
+
+    PRIMARY KEY (tweet_id, entity_id)
+);
+
+-- the other direction, for filtering / counting tweets by entity
+CREATE INDEX tweet_entities_entity_id_index ON tweet_entities (entity_id);
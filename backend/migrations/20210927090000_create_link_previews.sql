@@ -0,0 +1,23 @@
+/*
+ OpenGraph metadata for links shared in tweets, so the frontend can show a card instead of a bare t.co link.
+ Keyed by the url as twitter expanded it (= entities.value for kind 'url'), so one fetch serves every tweet sharing the link.
+ */
+CREATE TABLE link_previews
+(
+    -- basics
+    id          uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at  timestamptz NOT NULL,
+
+    -- fetch info
+    url         TEXT        NOT NULL UNIQUE,
+    final_url   TEXT,                 -- where the redirects ended up
+    status      TEXT        NOT NULL, -- ok / failed. Failed ones aren't retried, same as deleted tweets
+    error       TEXT,
+
+    -- preview info
+    title       TEXT,
+    description TEXT,
+    image_url   TEXT,
+    site_name   TEXT
+);
@@ -0,0 +1,30 @@
+/*
+ Everything needed to show media inline, not just a still image:
+ - dimensions (for aspect ratios before the media loads), duration and alt text on media itself
+ - every playable variant of a video / gif in media_variants - twitter returns one per bitrate, plus an hls playlist
+ */
+ALTER TABLE media
+    ADD COLUMN alt_text    TEXT,
+    ADD COLUMN width       INT,
+    ADD COLUMN height      INT,
+    ADD COLUMN duration_ms INT; -- videos only, gifs have none
+
+CREATE TABLE media_variants
+(
+    -- basics
+    id           uuid        NOT NULL,
+    PRIMARY KEY (id),
+    created_at   timestamptz NOT NULL,
+
+    -- variant info
+    content_type TEXT        NOT NULL, -- video/mp4 or application/x-mpegURL
+    bit_rate     INT,                  -- missing for the hls playlist
+    url          TEXT        NOT NULL,
+
+    -- relation to media
+    media_id     uuid        NOT NULL,
+    FOREIGN KEY (media_id)
+        REFERENCES media (id),
+
+    UNIQUE (media_id, url)
+);
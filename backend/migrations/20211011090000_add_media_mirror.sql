@@ -0,0 +1,9 @@
+/*
+ Where media lives once it's mirrored into our own blob store (local fs or s3), so we stop hot-linking twitter.
+ Keys are relative to the store - /media/{key} serves them.
+ */
+ALTER TABLE media
+    ADD COLUMN mirror_key    TEXT,        -- the full size image, as downloaded
+    ADD COLUMN thumbnail_key TEXT,        -- resized jpeg
+    ADD COLUMN mirrored_at   timestamptz,
+    ADD COLUMN mirror_error  TEXT;        -- permanent failures only (404, not an image, too large) - never retried. Transient ones aren't recorded, the next run picks them up
@@ -0,0 +1,8 @@
+/*
+ Failed unfurls used to be final. Timeouts / 5xx / 429 are usually gone by the next run though,
+ so those get a retry_after and are picked up again once it passes (up to LINK_PREVIEW_MAX_ATTEMPTS).
+ Permanent failures (4xx, not html, non-public host) keep retry_after NULL and are never retried.
+ */
+ALTER TABLE link_previews
+    ADD COLUMN attempts    INT NOT NULL DEFAULT 1,
+    ADD COLUMN retry_after timestamptz;
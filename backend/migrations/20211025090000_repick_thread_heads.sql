@@ -0,0 +1,13 @@
+/*
+ Helper tweets used to be eligible as a thread's head - and as helpers never make it into the feed,
+ such threads never showed up at all. New heads are picked among non-helpers only; this fixes the existing ones.
+ */
+UPDATE threads
+SET head_tweet_id = (
+    SELECT tweet_id
+    FROM tweets
+    WHERE thread_id = threads.id
+      AND tweet_class != 'helper'
+    ORDER BY tweet_created_at, tweet_id
+    LIMIT 1
+);
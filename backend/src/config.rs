@@ -6,16 +6,29 @@ pub struct Settings {
     pub app: AppSettings,
     pub database: DbSettings,
     pub twitter: TwitterSettings,
+    pub reddit: RedditSettings,
+    pub rss: RssSettings,
+    pub discord: DiscordSettings,
+    pub email: EmailSettings,
+    pub webhooks: WebhookSettings,
+    pub link_previews: LinkPreviewSettings,
+    pub mirror: MirrorSettings,
 }
 
 #[derive(serde::Deserialize)]
 pub struct AppSettings {
     pub port: u16,
     pub host: String,
+    pub site_url: String,
+    pub api_url: String,
     pub refresh_freq: u64,
     pub refresh_tweets_per_user: u32,
+    pub max_timeline_pages: usize,
+    pub metrics_refresh_hours: i64,
     pub followers_for_account: String,
     pub max_users: usize,
+    pub max_thread_depth: usize,
+    pub seen_tweets_ttl_hours: i64,
 }
 
 #[derive(serde::Deserialize)]
@@ -37,6 +50,83 @@ pub struct TwitterSettings {
     pub bearer_token: String,
 }
 
+#[derive(serde::Deserialize)]
+pub struct RedditSettings {
+    pub user_agent: String,
+    pub subreddits: Vec<String>,
+    pub posts_per_subreddit: u32,
+    pub top_timeframe: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct RssSettings {
+    pub feed_urls: Vec<String>,
+    pub max_entry_age_days: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct DiscordSettings {
+    pub bot_token: String,
+    pub api_base_url: String,
+    pub channels: Vec<DiscordChannel>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct DiscordChannel {
+    pub guild_id: String,
+    pub channel_id: String,
+    pub name: String,
+}
+
+#[derive(serde::Deserialize)]
+pub struct EmailSettings {
+    pub smtp_host: String,
+    pub smtp_port: u16,
+    pub smtp_username: String,
+    pub smtp_password: String,
+    pub smtp_tls: bool,
+    pub from_address: String,
+    pub tweets_per_digest: usize,
+}
+
+#[derive(serde::Deserialize)]
+pub struct WebhookSettings {
+    pub window_hours: i64,
+    pub max_deliveries_per_run: i64,
+}
+
+#[derive(serde::Deserialize)]
+pub struct LinkPreviewSettings {
+    pub window_hours: i64,
+    pub max_per_run: i64,
+    pub timeout_secs: u64,
+    pub max_redirects: usize,
+    pub max_bytes: usize,
+    pub allow_private_hosts: bool,
+}
+
+#[derive(serde::Deserialize)]
+pub struct MirrorSettings {
+    pub store: BlobStoreKind,
+    pub local_dir: String,
+    pub s3_endpoint: String,
+    pub s3_bucket: String,
+    pub s3_region: String,
+    pub s3_access_key: String,
+    pub s3_secret_key: String,
+    pub window_hours: i64,
+    pub max_per_run: i64,
+    pub max_bytes: usize,
+    pub thumbnail_size: u32,
+}
+
+#[derive(serde::Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BlobStoreKind {
+    Local,
+    S3,
+}
+
 impl DbSettings {
     pub fn conn_opts(&self) -> PgConnectOptions {
         let ssl_mode = if self.require_ssl {
@@ -0,0 +1,80 @@
+use std::collections::HashMap;
+
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::digest::core::processors::send_digest;
+use crate::digest::mailers::mailer::Mailer;
+use crate::digest::model::subscriber::fetch_confirmed_subscribers;
+use crate::twitter::routes::serve::{fetch_top_full_tweets, FullTweet};
+
+/// A subscriber's timeframe doubles as how often they get a digest - a "week" subscriber gets the
+/// week's top tweets, once a week.
+///
+/// Subscribers mostly share the same few sort_by / timeframe combos, so each combo's tweets are
+/// fetched once per run and reused.
+#[tracing::instrument(skip(pool, config, mailer))]
+pub async fn send_digests_to_subscribers(
+    pool: &PgPool,
+    config: &Settings,
+    mailer: &dyn Mailer,
+) -> anyhow::Result<()> {
+    let subscribers = fetch_confirmed_subscribers(pool).await?;
+    let now = Utc::now();
+    // the scheduler only wakes up every refresh_freq mins - without some slack a digest sent a few
+    // seconds late one run would be a few seconds too early the next, and get pushed back a whole run
+    let slack = Duration::minutes(config.app.refresh_freq as i64 / 2);
+
+    let mut top_tweets: HashMap<(String, String), Vec<FullTweet>> = HashMap::new();
+    let mut sent = 0;
+    for subscriber in subscribers.iter() {
+        let params = match subscriber.tweet_params() {
+            Ok(params) => params,
+            Err(e) => {
+                tracing::error!(
+                    ">>>E: Bad digest preferences for subscriber {}: {}",
+                    subscriber.id,
+                    e
+                );
+                continue;
+            }
+        };
+        let is_due = match subscriber.last_sent_at {
+            Some(last_sent_at) => last_sent_at + params.timeframe.to_duration() - slack <= now,
+            None => true,
+        };
+        if !is_due {
+            continue;
+        }
+
+        let key = (subscriber.sort_by.clone(), subscriber.timeframe.clone());
+        if !top_tweets.contains_key(&key) {
+            let mut tweets = fetch_top_full_tweets(pool, config, params).await?;
+            tweets.truncate(config.email.tweets_per_digest);
+            top_tweets.insert(key.clone(), tweets);
+        }
+        let tweets = &top_tweets[&key];
+        // nothing worth an email - try again next run
+        if tweets.is_empty() {
+            continue;
+        }
+
+        //fallible, but one bad address shouldn't stop the rest (only logging, retries are inside)
+        match send_digest(pool, config, mailer, subscriber, tweets).await {
+            Ok(_) => sent += 1,
+            Err(e) => tracing::error!(
+                ">>>E: Failed to send digest to subscriber {}: {}",
+                subscriber.id,
+                e
+            ),
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total digests sent: {} out of {} confirmed subscribers",
+        sent,
+        subscribers.len(),
+    );
+    Ok(())
+}
@@ -0,0 +1,3 @@
+pub mod jobs;
+pub mod processors;
+pub mod templates;
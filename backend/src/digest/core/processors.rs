@@ -0,0 +1,52 @@
+use chrono::Utc;
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+
+use crate::config::Settings;
+use crate::digest::core::templates::{render_confirmation_email, render_digest_email};
+use crate::digest::mailers::mailer::{Email, Mailer};
+use crate::digest::model::subscriber::{mark_digest_sent, Subscriber};
+use crate::twitter::routes::serve::FullTweet;
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_NORMAL, RETRY_FACTOR};
+use anyhow::Context;
+
+#[tracing::instrument(skip(config, mailer, subscriber), fields(subscriber_id = %subscriber.id))]
+pub async fn send_confirmation(
+    config: &Settings,
+    mailer: &dyn Mailer,
+    subscriber: &Subscriber,
+) -> anyhow::Result<()> {
+    let email = render_confirmation_email(config, subscriber);
+    send_with_retries(mailer, &email).await
+}
+
+/// Only marked as sent once the smtp server accepted it - otherwise the next run tries again.
+#[tracing::instrument(skip(pool, config, mailer, subscriber, tweets), fields(subscriber_id = %subscriber.id))]
+pub async fn send_digest(
+    pool: &PgPool,
+    config: &Settings,
+    mailer: &dyn Mailer,
+    subscriber: &Subscriber,
+    tweets: &[FullTweet],
+) -> anyhow::Result<()> {
+    let email = render_digest_email(config, subscriber, tweets);
+    send_with_retries(mailer, &email).await?;
+    mark_digest_sent(pool, subscriber.id, Utc::now())
+        .await
+        .context("failed to mark digest as sent")?;
+    Ok(())
+}
+
+async fn send_with_retries(mailer: &dyn Mailer, email: &Email) -> anyhow::Result<()> {
+    // retrying 2 times (5s and 25s)
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    Retry::spawn(retry_strategy, || async { mailer.send(email).await })
+        .await
+        .context(format!(
+            "failed to send email after {} retries",
+            RETRY_COUNT_NORMAL
+        ))
+}
@@ -0,0 +1,118 @@
+use crate::config::Settings;
+use crate::digest::mailers::mailer::Email;
+use crate::digest::model::subscriber::Subscriber;
+use crate::twitter::routes::serve::FullTweet;
+use crate::utils::general::escape_xml;
+
+// Hand-rolled, inline-styled html - email clients ignore <style> blocks and most of css anyway.
+
+// ----------------------------------------------------------------------------- fn
+
+pub fn render_confirmation_email(config: &Settings, subscriber: &Subscriber) -> Email {
+    let confirm_url = format!(
+        "{}/subscribe/confirm?token={}",
+        config.app.api_url, subscriber.confirm_token
+    );
+    let intro = format!(
+        "Someone (hopefully you) asked for the top {} tweets on sol.wtf, sorted by {}, to be emailed to this address.",
+        describe_timeframe(&subscriber.timeframe),
+        subscriber.sort_by,
+    );
+    let outro = "If it wasn't you, just ignore this email - nothing will be sent until the address is confirmed.";
+
+    Email {
+        to: subscriber.email.clone(),
+        subject: String::from("Confirm your sol.wtf digest"),
+        html: wrap_html(&format!(
+            r#"<p>{intro}</p><p><a href="{url}" style="font-weight:bold;">Confirm subscription</a></p><p style="color:#888;">{outro}</p>"#,
+            intro = escape_xml(&intro),
+            url = escape_xml(&confirm_url),
+            outro = escape_xml(outro),
+        )),
+        text: format!("{}\n\nConfirm here: {}\n\n{}\n", intro, confirm_url, outro),
+    }
+}
+
+pub fn render_digest_email(
+    config: &Settings,
+    subscriber: &Subscriber,
+    tweets: &[FullTweet],
+) -> Email {
+    let unsubscribe_url = format!(
+        "{}/unsubscribe?token={}",
+        config.app.api_url, subscriber.unsubscribe_token
+    );
+    let heading = format!(
+        "Top tweets of the {}, by {}",
+        describe_timeframe(&subscriber.timeframe),
+        subscriber.sort_by
+    );
+
+    let mut html_items = String::new();
+    let mut text_items = String::new();
+    for ft in tweets.iter() {
+        let metrics = ft.describe_metrics();
+        html_items.push_str(&format!(
+            r#"<div style="border-bottom:1px solid #eee;padding:12px 0;"><div><b>{name}</b> <span style="color:#888;">@{handle}</span></div><p style="white-space:pre-wrap;">{text}</p>{image}<div style="color:#888;font-size:12px;">{metrics} &middot; <a href="{url}">view on twitter</a></div></div>"#,
+            name = escape_xml(&ft.author.twitter_name),
+            handle = escape_xml(&ft.author.twitter_handle),
+            text = escape_xml(&ft.tweet.tweet_text),
+            image = match ft.first_image() {
+                Some(src) => format!(
+                    r#"<img src="{}" alt="" style="max-width:100%;border-radius:8px;"/>"#,
+                    escape_xml(src)
+                ),
+                None => String::new(),
+            },
+            metrics = escape_xml(&metrics),
+            url = escape_xml(&ft.tweet.tweet_url),
+        ));
+        text_items.push_str(&format!(
+            "{} (@{})\n{}\n{}\n{}\n\n",
+            ft.author.twitter_name,
+            ft.author.twitter_handle,
+            ft.tweet.tweet_text,
+            metrics,
+            ft.tweet.tweet_url
+        ));
+    }
+
+    Email {
+        to: subscriber.email.clone(),
+        subject: format!("sol.wtf - {}", heading.to_lowercase()),
+        html: wrap_html(&format!(
+            r#"<h2>{heading}</h2>{items}<p style="color:#888;font-size:12px;">More at <a href="{site_url}">sol.wtf</a>. <a href="{unsubscribe_url}">Unsubscribe</a></p>"#,
+            heading = escape_xml(&heading),
+            items = html_items,
+            site_url = escape_xml(&config.app.site_url),
+            unsubscribe_url = escape_xml(&unsubscribe_url),
+        )),
+        text: format!(
+            "{}\n\n{}More at {}\nUnsubscribe: {}\n",
+            heading, text_items, config.app.site_url, unsubscribe_url
+        ),
+    }
+}
+
+/// What the confirm / unsubscribe links land on - they're opened straight from the email, so there's no frontend involved.
+pub fn render_page(config: &Settings, message: &str) -> String {
+    wrap_html(&format!(
+        r#"<p>{}</p><p><a href="{}">Back to sol.wtf</a></p>"#,
+        escape_xml(message),
+        escape_xml(&config.app.site_url),
+    ))
+}
+
+fn wrap_html(body: &str) -> String {
+    format!(
+        r#"<!DOCTYPE html><html><head><meta charset="utf-8"><meta name="viewport" content="width=device-width"></head><body style="font-family:sans-serif;max-width:600px;margin:0 auto;padding:16px;">{}</body></html>"#,
+        body
+    )
+}
+
+fn describe_timeframe(timeframe: &str) -> &str {
+    match timeframe {
+        "twodays" => "two days",
+        other => other,
+    }
+}
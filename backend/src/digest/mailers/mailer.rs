@@ -0,0 +1,21 @@
+use async_trait::async_trait;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Every email we send goes out as both html and plain text - clients pick whichever they prefer.
+#[derive(Debug, Clone)]
+pub struct Email {
+    pub to: String,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+/// Everything the digest pipeline needs to send email. The jobs and routes only ever talk to this trait,
+/// so smtp can be swapped for anything else that speaks it (eg an http api like sendgrid's).
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, email: &Email) -> anyhow::Result<()>;
+}
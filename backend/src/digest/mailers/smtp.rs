@@ -0,0 +1,68 @@
+use anyhow::Context;
+use async_trait::async_trait;
+use lettre::message::{Mailbox, MultiPart, SinglePart};
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+
+use crate::config::Settings;
+use crate::digest::mailers::mailer::{Email, Mailer};
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the api clients.
+/// The transport keeps a pool of smtp connections, so a digest run doesn't reconnect for every email.
+pub struct SmtpMailer {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    from: Mailbox,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl SmtpMailer {
+    pub fn new(config: &Settings) -> anyhow::Result<Self> {
+        let email = &config.email;
+        // no tls is only meant for a local smtp sink (eg mailhog) when dev'ing / testing
+        let mut builder = if email.smtp_tls {
+            AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&email.smtp_host)
+                .context("failed to set up smtp tls")?
+        } else {
+            AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&email.smtp_host)
+        };
+        builder = builder.port(email.smtp_port);
+        // local sinks don't do auth, and lettre refuses to send if we offer credentials nobody asked for
+        if !email.smtp_username.is_empty() {
+            builder = builder.credentials(Credentials::new(
+                email.smtp_username.clone(),
+                email.smtp_password.clone(),
+            ));
+        }
+
+        Ok(SmtpMailer {
+            transport: builder.build(),
+            from: email
+                .from_address
+                .parse()
+                .context("failed to parse from_address")?,
+        })
+    }
+}
+
+#[async_trait]
+impl Mailer for SmtpMailer {
+    #[tracing::instrument(skip(self, email), fields(to = %email.to), level = "debug")]
+    async fn send(&self, email: &Email) -> anyhow::Result<()> {
+        let message = Message::builder()
+            .from(self.from.clone())
+            .to(email.to.parse().context("failed to parse recipient")?)
+            .subject(email.subject.clone())
+            .multipart(
+                MultiPart::alternative()
+                    .singlepart(SinglePart::plain(email.text.clone()))
+                    .singlepart(SinglePart::html(email.html.clone())),
+            )?;
+
+        let res = self.transport.send(message).await?;
+        tracing::info!(">>>I: smtp response code: {}", res.code());
+        Ok(())
+    }
+}
@@ -0,0 +1,4 @@
+pub mod core;
+pub mod mailers;
+pub mod model;
+pub mod routes;
@@ -0,0 +1,154 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::twitter::routes::serve::TweetParams;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+pub struct Subscriber {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub email: String,
+    pub sort_by: String,
+    pub timeframe: String,
+    pub confirm_token: String,
+    pub unsubscribe_token: String,
+    pub confirmed_at: Option<DateTime<Utc>>,
+    pub last_sent_at: Option<DateTime<Utc>>,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl Subscriber {
+    /// sort_by / timeframe are stored exactly as they appear in the /tweets query string,
+    /// so they parse back the same way.
+    pub fn tweet_params(&self) -> anyhow::Result<TweetParams> {
+        let params = serde_json::from_value(serde_json::json!({
+            "sort_by": self.sort_by,
+            "timeframe": self.timeframe,
+        }))?;
+        Ok(params)
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Subscribing again with the same address updates the preferences, but also un-confirms it -
+/// otherwise anyone could change someone else's digest without them clicking anything.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn store_subscriber(
+    pool: &PgPool,
+    email: &str,
+    sort_by: &str,
+    timeframe: &str,
+) -> Result<Subscriber, sqlx::error::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        INSERT INTO subscribers
+            (id, created_at, email, sort_by, timeframe, confirm_token, unsubscribe_token)
+        VALUES ($1, $2, $3, $4, $5, $6, $7)
+
+        ON CONFLICT (email)
+        DO UPDATE SET
+            sort_by = $4,
+            timeframe = $5,
+            confirm_token = $6,
+            confirmed_at = NULL
+        RETURNING *
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        email,
+        sort_by,
+        timeframe,
+        new_token(),
+        new_token(),
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(subscriber)
+}
+
+/// None if no one has this token. Confirming twice is fine - keeps the original confirmation time.
+#[tracing::instrument(skip(pool, confirm_token), level = "debug")]
+pub async fn confirm_subscriber(
+    pool: &PgPool,
+    confirm_token: &str,
+) -> Result<Option<Subscriber>, sqlx::error::Error> {
+    let subscriber = sqlx::query_as!(
+        Subscriber,
+        r#"
+        UPDATE subscribers
+        SET confirmed_at = COALESCE(confirmed_at, $2)
+        WHERE confirm_token = $1
+        RETURNING *
+        "#,
+        confirm_token,
+        Utc::now(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(subscriber)
+}
+
+/// Unsubscribing deletes the address entirely - we've no reason to keep it around.
+/// Returns whether anyone had this token.
+#[tracing::instrument(skip(pool, unsubscribe_token), level = "debug")]
+pub async fn delete_subscriber(
+    pool: &PgPool,
+    unsubscribe_token: &str,
+) -> Result<bool, sqlx::error::Error> {
+    let res = sqlx::query!(
+        r#"
+        DELETE FROM subscribers WHERE unsubscribe_token = $1
+        "#,
+        unsubscribe_token,
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_confirmed_subscribers(
+    pool: &PgPool,
+) -> Result<Vec<Subscriber>, sqlx::error::Error> {
+    let subscribers = sqlx::query_as!(
+        Subscriber,
+        r#"
+        SELECT * FROM subscribers
+        WHERE confirmed_at IS NOT NULL
+        ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(subscribers)
+}
+
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn mark_digest_sent(
+    pool: &PgPool,
+    subscriber_id: Uuid,
+    sent_at: DateTime<Utc>,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE subscribers SET last_sent_at = $2 WHERE id = $1
+        "#,
+        subscriber_id,
+        sent_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// v4 uuids are random (122 bits) - plenty for a token that only ever travels by email.
+fn new_token() -> String {
+    Uuid::new_v4().to_simple().to_string()
+}
@@ -0,0 +1,124 @@
+#![allow(clippy::async_yields_async)]
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use actix_web::{get, post, web, HttpResponse};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::digest::core::processors::send_confirmation;
+use crate::digest::core::templates::render_page;
+use crate::digest::mailers::mailer::Mailer;
+use crate::digest::model::subscriber::{confirm_subscriber, delete_subscriber, store_subscriber};
+use crate::twitter::routes::serve::{SortBy, Timeframe};
+use crate::utils::errors::ApiError;
+use anyhow::Context;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct SubscribeParams {
+    pub email: String,
+    pub sort_by: SortBy,
+    pub timeframe: Timeframe,
+}
+
+#[derive(serde::Deserialize, Debug)]
+pub struct TokenParams {
+    pub token: String,
+}
+
+// ----------------------------------------------------------------------------- fns
+
+/// Step 1 of the double opt-in - stores the subscriber as unconfirmed and emails them a confirmation link.
+#[tracing::instrument(skip(pool, config, mailer))]
+#[post("/subscribe")]
+pub async fn subscribe(
+    form: web::Json<SubscribeParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+    mailer: web::Data<Arc<dyn Mailer>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let config = config.as_ref().deref();
+
+    let email = form.email.trim().to_lowercase();
+    if email.parse::<lettre::Address>().is_err() {
+        return Err(ApiError::BadRequest(format!(
+            "{} is not a valid email address",
+            email
+        )));
+    }
+    // the timeframe is also how often the digest goes out - hourly emails are just spam
+    if let Timeframe::Hour | Timeframe::Four = form.timeframe {
+        return Err(ApiError::BadRequest(String::from(
+            "digests go out at most once a day - pick a timeframe of a day or longer",
+        )));
+    }
+
+    let subscriber = store_subscriber(
+        pool,
+        &email,
+        &to_param_value(&form.sort_by)?,
+        &to_param_value(&form.timeframe)?,
+    )
+    .await?;
+    send_confirmation(config, mailer.as_ref().as_ref(), &subscriber)
+        .await
+        .context("failed to send confirmation email")?;
+
+    // nothing is subscribed until they click the link
+    Ok(HttpResponse::Accepted().finish())
+}
+
+/// Step 2 of the double opt-in - the link from the confirmation email.
+#[tracing::instrument(skip(form, pool, config))]
+#[get("/subscribe/confirm")]
+pub async fn confirm_subscription(
+    form: web::Query<TokenParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let config = config.as_ref().deref();
+
+    let message = match confirm_subscriber(pool, &form.token).await? {
+        Some(_) => "You're subscribed! Your first digest will arrive with the next refresh.",
+        // eg they subscribed again since, which issued a new link
+        None => "This confirmation link is no longer valid - try subscribing again.",
+    };
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(render_page(config, message)))
+}
+
+/// The link at the bottom of every digest. GET, so that it works in one click straight from the email.
+#[tracing::instrument(skip(form, pool, config))]
+#[get("/unsubscribe")]
+pub async fn unsubscribe(
+    form: web::Query<TokenParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let config = config.as_ref().deref();
+
+    // same answer either way - clicking the link twice shouldn't look like an error
+    delete_subscriber(pool, &form.token).await?;
+    Ok(HttpResponse::Ok()
+        .content_type("text/html; charset=utf-8")
+        .body(render_page(
+            config,
+            "You've been unsubscribed and your address deleted. Sorry to see you go!",
+        )))
+}
+
+/// eg SortBy::Popularity -> "popularity", same as in the query string.
+fn to_param_value<T: Serialize>(value: &T) -> anyhow::Result<String> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(s) => Ok(s),
+        other => Err(anyhow::anyhow!("expected a string, got {}", other)),
+    }
+}
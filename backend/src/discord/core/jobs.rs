@@ -0,0 +1,34 @@
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::discord::core::processors::process_discord_channel;
+use crate::discord::scrapers::gateway::DiscordGateway;
+
+/// Only channels whitelisted in config are read - a bot sees every channel of a server it's in,
+/// but only announcement channels are news.
+#[tracing::instrument(skip(pool, config, gateway))]
+pub async fn pull_messages_for_discord_channels(
+    pool: &PgPool,
+    config: &Settings,
+    gateway: &dyn DiscordGateway,
+) -> anyhow::Result<()> {
+    let mut total = 0;
+    for channel in config.discord.channels.iter() {
+        //fallible, but one channel failing shouldn't stop the rest (only logging, retries are inside)
+        match process_discord_channel(pool, gateway, channel).await {
+            Ok(stored) => total += stored,
+            Err(e) => tracing::error!(
+                ">>>E: Failed to pull discord channel {}: {}",
+                channel.name,
+                e
+            ),
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total stored discord messages: {} from {} channels",
+        total,
+        config.discord.channels.len(),
+    );
+    Ok(())
+}
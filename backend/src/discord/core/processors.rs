@@ -0,0 +1,47 @@
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+
+use crate::config::DiscordChannel;
+use crate::discord::model::message::store_discord_message;
+use crate::discord::scrapers::gateway::DiscordGateway;
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_NORMAL, RETRY_FACTOR};
+use anyhow::Context;
+
+/// A message that fails to store is logged and skipped, so that it doesn't take down the rest of the channel.
+#[tracing::instrument(skip(pool, gateway))]
+pub async fn process_discord_channel(
+    pool: &PgPool,
+    gateway: &dyn DiscordGateway,
+    channel: &DiscordChannel,
+) -> anyhow::Result<usize> {
+    // get the messages, retrying 2 times (5s and 25s)
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    let messages = Retry::spawn(retry_strategy, || async {
+        gateway.get_recent_messages(&channel.channel_id).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch messages for discord channel {} after {} retries",
+        channel.name, RETRY_COUNT_NORMAL
+    ))?;
+
+    let mut stored = 0;
+    for message in messages.iter() {
+        // eg pins / joins - nothing to show
+        if message.content.trim().is_empty() {
+            continue;
+        }
+        match store_discord_message(pool, channel, message).await {
+            Ok(_) => stored += 1,
+            Err(e) => tracing::error!(
+                ">>>E: Failed to store discord message {}. Full error: {}",
+                message.id,
+                e,
+            ),
+        }
+    }
+    Ok(stored)
+}
@@ -0,0 +1,63 @@
+use chrono::Utc;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::config::DiscordChannel;
+use crate::discord::scrapers::responses::MessageObject;
+use crate::feed::model::content::Source;
+
+// ----------------------------------------------------------------------------- fn
+
+/// Upsert - we re-read the same recent messages every run, so that their reaction counts stay fresh.
+#[tracing::instrument(skip(pool, channel, message), level = "debug")]
+pub async fn store_discord_message(
+    pool: &PgPool,
+    channel: &DiscordChannel,
+    message: &MessageObject,
+) -> Result<(), sqlx::error::Error> {
+    // messages have no title - the first line usually reads like one
+    let title = message
+        .content
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .take(200)
+        .collect::<String>();
+    let post_url = format!(
+        "https://discord.com/channels/{}/{}/{}",
+        channel.guild_id, channel.channel_id, message.id
+    );
+    // the first attachment (usually an image) stands in for the link of a link post
+    let link_url = message.attachments.first().map(|a| a.url.clone());
+
+    sqlx::query!(
+        r#"
+        INSERT INTO posts
+            (id, created_at,
+            source, source_id, channel,
+            posted_at, title, body, author, post_url, link_url,
+            score, comment_count)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, NULL)
+
+        ON CONFLICT (source, source_id)
+        DO UPDATE SET
+            score = $12
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        Source::Discord.to_string(),
+        message.id,
+        channel.name,
+        message.timestamp,
+        title,
+        message.content,
+        message.author.username,
+        post_url,
+        link_url,
+        message.reaction_count(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,11 @@
+use async_trait::async_trait;
+
+use crate::discord::scrapers::responses::MessageObject;
+
+/// Everything the discord pipeline needs from discord itself. The jobs only ever talk to this trait,
+/// so the real http client can be swapped for anything else that speaks it (eg a local mock server).
+#[async_trait]
+pub trait DiscordGateway: Send + Sync {
+    /// Newest first, up to DISCORD_MESSAGES_PER_CHANNEL.
+    async fn get_recent_messages(&self, channel_id: &str) -> anyhow::Result<Vec<MessageObject>>;
+}
@@ -0,0 +1,56 @@
+use anyhow::Context;
+use async_trait::async_trait;
+
+use crate::config::Settings;
+use crate::discord::scrapers::gateway::DiscordGateway;
+use crate::discord::scrapers::responses::MessageObject;
+use crate::utils::constants::DISCORD_MESSAGES_PER_CHANNEL;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the twitter one.
+/// Talks to discord's http api as a bot, which has to be a member of the servers we read from.
+pub struct DiscordClient {
+    client: reqwest::Client,
+    bot_token: String,
+    api_base_url: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl DiscordClient {
+    pub fn new(config: &Settings) -> Self {
+        DiscordClient {
+            client: reqwest::Client::new(),
+            bot_token: config.discord.bot_token.clone(),
+            api_base_url: config.discord.api_base_url.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl DiscordGateway for DiscordClient {
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn get_recent_messages(&self, channel_id: &str) -> anyhow::Result<Vec<MessageObject>> {
+        let url = format!(
+            "{}/channels/{}/messages?limit={}",
+            self.api_base_url, channel_id, DISCORD_MESSAGES_PER_CHANNEL
+        );
+        let res = self
+            .client
+            .get(url)
+            .header("Authorization", format!("Bot {}", self.bot_token))
+            .send()
+            .await?;
+
+        tracing::info!(">>>I: GET call status: {}", &res.status());
+        // discord answers 429 when we go over the limit - surface it as an error so that retries kick in
+        let res = res.error_for_status()?;
+
+        let raw_body = res.text().await?;
+        // going through serde_json directly (instead of res.json()) gives us the exact field / line that failed
+        let messages: Vec<MessageObject> = serde_json::from_str(&raw_body)
+            .context("failed to deserialize response into Vec<MessageObject>")?;
+        Ok(messages)
+    }
+}
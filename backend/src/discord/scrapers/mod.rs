@@ -0,0 +1,3 @@
+pub mod gateway;
+pub mod general;
+pub mod responses;
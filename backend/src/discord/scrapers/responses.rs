@@ -0,0 +1,45 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+// Typed versions of the payloads returned by Discord's http api (v9).
+// Only the fields we actually use are modelled.
+
+// ----------------------------------------------------------------------------- objects
+
+/// GET /channels/:id/messages returns a bare array of these, newest first.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MessageObject {
+    pub id: String,
+    pub channel_id: String,
+    pub content: String,
+    pub timestamp: DateTime<Utc>,
+    pub author: AuthorObject,
+    // not present at all if nobody reacted
+    #[serde(default)]
+    pub reactions: Vec<ReactionObject>,
+    #[serde(default)]
+    pub attachments: Vec<AttachmentObject>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuthorObject {
+    pub id: String,
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReactionObject {
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AttachmentObject {
+    pub url: String,
+}
+
+impl MessageObject {
+    /// All reactions summed up, regardless of emoji - our engagement metric for discord.
+    pub fn reaction_count(&self) -> i64 {
+        self.reactions.iter().map(|r| r.count).sum()
+    }
+}
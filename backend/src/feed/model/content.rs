@@ -0,0 +1,178 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::feed::model::post::Post;
+use crate::twitter::routes::serve::FullTweet;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Source {
+    Twitter,
+    Reddit,
+    Rss,
+    Discord,
+}
+
+/// A single piece of content from any source, in the shape the frontend renders.
+/// Each source maps its own model into this - the feed never needs to know where an item came from.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentItem {
+    pub source: Source,
+    pub external_id: String,     // id within the source, eg the tweet id
+    pub channel: Option<String>, // where within the source, eg the subreddit
+    pub author: ContentAuthor,
+    pub title: Option<String>,
+    pub body: String,
+    pub media: Vec<ContentMedia>,
+    pub url: String,
+    pub posted_at: DateTime<Utc>,
+    pub engagement: Engagement,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentAuthor {
+    pub name: String,
+    pub handle: Option<String>,
+    pub profile_image: Option<String>,
+    pub url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentMedia {
+    pub media_type: Option<String>,
+    pub url: String,
+    pub thumbnail_url: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Engagement {
+    // in the source's own unit, eg popularity_count for tweets, score for reddit. None = source has no metrics (eg rss)
+    pub raw: Option<i64>,
+    pub normalized: f64, // raw / the source's average over the timeframe - comparable across sources
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl fmt::Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Source::Twitter => write!(f, "twitter"),
+            Source::Reddit => write!(f, "reddit"),
+            Source::Rss => write!(f, "rss"),
+            Source::Discord => write!(f, "discord"),
+        }
+    }
+}
+
+impl TryFrom<String> for Source {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match &value[..] {
+            "twitter" => Ok(Self::Twitter),
+            "reddit" => Ok(Self::Reddit),
+            "rss" => Ok(Self::Rss),
+            "discord" => Ok(Self::Discord),
+            _ => Err(format!("{} is an unsupported Source.", value)),
+        }
+    }
+}
+
+impl ContentItem {
+    /// Engagement is left un-normalized - see normalize_engagement.
+    pub fn from_full_tweet(full_tweet: &FullTweet) -> ContentItem {
+        let tweet = &full_tweet.tweet;
+        let author = &full_tweet.author;
+        let media = full_tweet
+            .media
+            .iter()
+            .flatten()
+            .filter_map(|m| {
+                m.display_url.as_ref().map(|url| ContentMedia {
+                    media_type: m.media_type.clone(),
+                    url: url.clone(),
+                    thumbnail_url: m.thumbnail_url.clone(),
+                })
+            })
+            .collect();
+
+        ContentItem {
+            source: Source::Twitter,
+            external_id: tweet.tweet_id.clone(),
+            channel: None,
+            author: ContentAuthor {
+                name: author.twitter_name.clone(),
+                handle: Some(author.twitter_handle.clone()),
+                profile_image: author.profile_image.clone(),
+                url: Some(author.profile_url.clone()),
+            },
+            title: None,
+            body: tweet.tweet_text.clone(),
+            media,
+            url: tweet.tweet_url.clone(),
+            posted_at: tweet.tweet_created_at,
+            engagement: Engagement {
+                raw: Some(tweet.popularity_count.unwrap_or(0)),
+                normalized: 0.0,
+            },
+        }
+    }
+
+    /// Engagement is left un-normalized - see normalize_engagement.
+    pub fn from_post(post: &Post, source: Source) -> ContentItem {
+        // a link post's link is the whole point of it - surface it as media, so it gets rendered
+        let media = post
+            .link_url
+            .iter()
+            .map(|url| ContentMedia {
+                media_type: Some(String::from("link")),
+                url: url.clone(),
+                thumbnail_url: None,
+            })
+            .collect();
+
+        ContentItem {
+            source,
+            external_id: post.source_id.clone(),
+            channel: Some(post.channel.clone()),
+            author: ContentAuthor {
+                name: post.author.clone().unwrap_or_default(),
+                handle: post.author.clone(),
+                profile_image: None,
+                url: None,
+            },
+            title: Some(post.title.clone()),
+            body: post.body.clone().unwrap_or_default(),
+            media,
+            url: post.post_url.clone(),
+            posted_at: post.posted_at,
+            engagement: Engagement {
+                raw: post.score,
+                normalized: 0.0,
+            },
+        }
+    }
+
+    /// A tweet with 3x the average popularity of tweets and a reddit post with 3x the average reddit score
+    /// end up on par, no matter how different the raw numbers are.
+    /// Items without metrics (eg blog posts) count as exactly average.
+    pub fn normalize_engagement(&mut self, source_average: Option<f64>) {
+        let raw = match self.engagement.raw {
+            Some(raw) => raw,
+            None => {
+                self.engagement.normalized = 1.0;
+                return;
+            }
+        };
+        let average = match source_average {
+            Some(average) if average > 0.0 => average,
+            _ => 1.0,
+        };
+        self.engagement.normalized = raw as f64 / average;
+    }
+}
@@ -0,0 +1,91 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+// Posts = content from every source other than twitter. Each source stores its own posts
+// (eg reddit::model::post), reading them back is the same for all of them.
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
+pub struct Post {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    // where it's from
+    pub source: String,
+    pub source_id: String,
+    pub channel: String,
+    // post info
+    pub posted_at: DateTime<Utc>,
+    pub title: String,
+    pub body: Option<String>,
+    pub author: Option<String>,
+    pub post_url: String,
+    pub link_url: Option<String>,
+    // metrics
+    pub score: Option<i64>,
+    pub comment_count: Option<i64>,
+}
+
+/// Average score of a source's posts over some timeframe.
+#[derive(Debug)]
+pub struct SourceAverage {
+    pub source: String,
+    pub average: f64,
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Top `limit` posts from each source, not overall - raw scores aren't comparable across sources
+/// (the caller normalizes them), and sources without scores (rss) would otherwise never make the cut.
+/// Unscored posts come after scored ones, newest first.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_top_posts(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<Post>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Post,
+        r#"
+        SELECT id, created_at, source, source_id, channel, posted_at, title, body, author, post_url, link_url,
+            score, comment_count
+        FROM (
+            SELECT *, ROW_NUMBER() OVER (
+                PARTITION BY source ORDER BY score DESC NULLS LAST, posted_at DESC, id DESC
+            ) AS source_rank
+            FROM posts
+            WHERE posted_at >= $1
+        ) ranked
+        WHERE source_rank <= $2
+        ORDER BY source, source_rank;
+        "#,
+        since,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// One row per source that has posts in the timeframe
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_average_post_scores(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+) -> Result<Vec<SourceAverage>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        SourceAverage,
+        r#"
+        SELECT source, COALESCE(AVG(score), 0)::FLOAT8 AS "average!"
+        FROM posts
+        WHERE posted_at >= $1
+        GROUP BY source;
+        "#,
+        since,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
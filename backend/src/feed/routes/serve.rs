@@ -0,0 +1,136 @@
+#![allow(clippy::async_yields_async)]
+
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::ops::Deref;
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::feed::model::content::{ContentItem, Source};
+use crate::feed::model::post::{fetch_average_post_scores, fetch_top_posts};
+use crate::twitter::model::tweet::{fetch_average_tweet_popularity, fetch_top_tweets};
+use crate::twitter::routes::serve::{prep_full_tweets, Timeframe};
+use crate::utils::constants::{FEED_MAX_PAGE, FEED_PAGE_SIZE};
+use crate::utils::errors::ApiError;
+use anyhow::Context;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct FeedParams {
+    pub timeframe: Timeframe,
+    // 0-based, None = first page. Up to FEED_MAX_PAGE
+    pub page: Option<u32>,
+}
+
+#[derive(Debug, serde::Serialize)]
+pub struct FeedPage {
+    pub items: Vec<ContentItem>,
+    // None after a short page, or FEED_MAX_PAGE. A full page may still be followed by an empty one
+    pub next_page: Option<u32>,
+}
+
+// ----------------------------------------------------------------------------- fns
+
+/// Merges all sources into a single feed, ranked by normalized engagement (see ContentItem::normalize_engagement).
+///
+/// Within a source, normalized order = raw order, so the top N of the merged feed can only come
+/// from the top N of each source. That's why each source only has to hand over its top
+/// (page + 1) * FEED_PAGE_SIZE items - which we then merge, sort, and cut the requested page out of.
+#[tracing::instrument(skip(pool, config))]
+#[get("/feed")]
+pub async fn serve_feed(
+    form: web::Query<FeedParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let config = config.as_ref().deref();
+    let since = form.timeframe.to_datetime();
+    let page = form.page.unwrap_or(0);
+    if page > FEED_MAX_PAGE {
+        return Err(ApiError::BadRequest(format!(
+            "page must be at most {}",
+            FEED_MAX_PAGE
+        )));
+    }
+    let page = page as usize;
+    let limit = ((page + 1) * FEED_PAGE_SIZE) as i64;
+
+    // 1) per-source averages, for normalization
+    let mut averages: HashMap<Source, Option<f64>> = HashMap::new();
+    let tweet_average = fetch_average_tweet_popularity(pool, since)
+        .await
+        .context("failed to fetch average tweet popularity")?;
+    averages.insert(Source::Twitter, tweet_average);
+    for post_average in fetch_average_post_scores(pool, since)
+        .await
+        .context("failed to fetch average post scores")?
+    {
+        if let Ok(source) = Source::try_from(post_average.source) {
+            averages.insert(source, Some(post_average.average));
+        }
+    }
+
+    // 2) top items from each source
+    let tweets = fetch_top_tweets(pool, since, limit)
+        .await
+        .context("failed to fetch top tweets")?;
+    // no reply / quote nesting in the feed - a ContentItem is flat
+    let full_tweets = prep_full_tweets(
+        pool,
+        config,
+        tweets.into_iter().map(|t| (t, None)).collect(),
+        0,
+    )
+    .await
+    .context("failed to prep full tweets")?;
+    let mut items = full_tweets
+        .iter()
+        .map(ContentItem::from_full_tweet)
+        .collect::<Vec<ContentItem>>();
+
+    let posts = fetch_top_posts(pool, since, limit)
+        .await
+        .context("failed to fetch top posts")?;
+    for post in posts.iter() {
+        match Source::try_from(post.source.clone()) {
+            Ok(source) => items.push(ContentItem::from_post(post, source)),
+            Err(e) => tracing::error!(">>>E: Skipping post {}: {}", post.id, e),
+        }
+    }
+
+    // 3) normalize, merge, cut out the page
+    for item in items.iter_mut() {
+        let average = averages.get(&item.source).cloned().flatten();
+        item.normalize_engagement(average);
+    }
+    items.sort_by(|a, b| {
+        b.engagement
+            .normalized
+            .partial_cmp(&a.engagement.normalized)
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| b.posted_at.cmp(&a.posted_at))
+    });
+    let items = items
+        .into_iter()
+        .skip(page * FEED_PAGE_SIZE)
+        .take(FEED_PAGE_SIZE)
+        .collect::<Vec<ContentItem>>();
+
+    // a full page might be followed by an empty one - cheaper than counting every source
+    let next_page = if items.len() == FEED_PAGE_SIZE && page < FEED_MAX_PAGE as usize {
+        Some(page as u32 + 1)
+    } else {
+        None
+    };
+    let feed_page = FeedPage { items, next_page };
+
+    let body = serde_json::to_string(&feed_page).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
@@ -1,4 +1,11 @@
 pub mod config;
+pub mod digest;
+pub mod discord;
+pub mod feed;
+pub mod mirror;
+pub mod reddit;
+pub mod rss;
 pub mod startup;
 pub mod twitter;
 pub mod utils;
+pub mod webhooks;
@@ -6,9 +6,19 @@ use tracing::log::LevelFilter::Debug;
 use tracing_log::LogTracer;
 
 use backend::config::get_config;
+use backend::digest::mailers::mailer::Mailer;
+use backend::digest::mailers::smtp::SmtpMailer;
+use backend::discord::scrapers::general::DiscordClient;
+use backend::mirror::scrapers::general::MediaClient;
+use backend::mirror::stores::store::build_blob_store;
+use backend::reddit::scrapers::general::RedditClient;
+use backend::rss::scrapers::general::RssClient;
 use backend::startup::run_server;
 use backend::twitter::schedulers::tokio_async::schedule_tweet_refresh;
+use backend::twitter::scrapers::general::TwitterClient;
+use backend::twitter::scrapers::unfurl::LinkPreviewClient;
 use backend::utils::tracing::configure_tracing;
+use backend::webhooks::senders::general::WebhookClient;
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
@@ -35,10 +45,51 @@ async fn main() -> std::io::Result<()> {
         .await
         .expect("failed to connect to Postgres");
 
+    // ----------------------------------------------------------------------------- api clients
+    // one client for the whole app, so that rate limits are shared between the scheduler and the routes
+    let twitter_client = TwitterClient::new(&config);
+    let reddit_client = RedditClient::new(&config);
+    let rss_client = RssClient::new();
+    let discord_client = DiscordClient::new(&config);
+    let webhook_client = WebhookClient::new();
+    let link_preview_client = LinkPreviewClient::new(&config);
+    let media_client = MediaClient::new(&config);
+    let blob_store = build_blob_store(&config).expect("failed to set up blob store");
+    let mailer = SmtpMailer::new(&config).expect("failed to set up smtp mailer");
+
     // ----------------------------------------------------------------------------- run
     let arc_pool = Arc::new(pg_pool);
     let arc_config = Arc::new(config);
+    let arc_client = Arc::new(twitter_client);
+    let arc_reddit_client = Arc::new(reddit_client);
+    let arc_rss_client = Arc::new(rss_client);
+    let arc_discord_client = Arc::new(discord_client);
+    let arc_webhook_client = Arc::new(webhook_client);
+    let arc_link_preview_client = Arc::new(link_preview_client);
+    let arc_media_client = Arc::new(media_client);
+    let arc_mailer: Arc<dyn Mailer> = Arc::new(mailer);
 
-    schedule_tweet_refresh(arc_pool.clone(), arc_config.clone()).await;
-    run_server(&addr, arc_pool.clone(), arc_config.clone())?.await
+    schedule_tweet_refresh(
+        arc_pool.clone(),
+        arc_config.clone(),
+        arc_client.clone(),
+        arc_reddit_client.clone(),
+        arc_rss_client.clone(),
+        arc_discord_client.clone(),
+        arc_webhook_client.clone(),
+        arc_link_preview_client.clone(),
+        arc_media_client.clone(),
+        blob_store.clone(),
+        arc_mailer.clone(),
+    )
+    .await;
+    run_server(
+        &addr,
+        arc_pool.clone(),
+        arc_config.clone(),
+        arc_client.clone(),
+        arc_mailer.clone(),
+        blob_store.clone(),
+    )?
+    .await
 }
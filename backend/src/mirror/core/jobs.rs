@@ -0,0 +1,51 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+
+use crate::config::Settings;
+use crate::mirror::core::processors::mirror_media;
+use crate::mirror::scrapers::general::MediaClient;
+use crate::mirror::stores::store::BlobStore;
+use crate::twitter::model::media::fetch_media_to_mirror;
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_IMPORTANT, RETRY_FACTOR};
+use anyhow::Context;
+
+/// Most popular media first - that's what readers are most likely to see.
+/// Anything that doesn't fit into max_per_run stays on twitter's urls until a later run gets to it.
+#[tracing::instrument(skip(pool, config, client, store))]
+pub async fn mirror_recent_media(
+    pool: &PgPool,
+    config: &Settings,
+    client: &MediaClient,
+    store: &dyn BlobStore,
+) -> anyhow::Result<()> {
+    let since = Utc::now() - Duration::hours(config.mirror.window_hours);
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_IMPORTANT);
+    let media = Retry::spawn(retry_strategy, || async {
+        fetch_media_to_mirror(pool, since, config.mirror.max_per_run).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch media to mirror after {} retries",
+        RETRY_COUNT_IMPORTANT
+    ))?;
+
+    let mut failed = 0;
+    for m in media.iter() {
+        //fallible, but one image failing shouldn't stop the rest (only logging, retries are inside)
+        if let Err(e) = mirror_media(pool, config, client, store, m).await {
+            tracing::error!(">>>E: failed to mirror media {}: {}", m.media_key, e);
+            failed += 1;
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total mirrored media: {}, failed: {}",
+        media.len() - failed,
+        failed,
+    );
+    Ok(())
+}
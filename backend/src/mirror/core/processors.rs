@@ -0,0 +1,106 @@
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::{Retry, RetryIf};
+
+use crate::config::Settings;
+use crate::mirror::core::thumbnails::make_thumbnail;
+use crate::mirror::scrapers::general::MediaClient;
+use crate::mirror::stores::store::{extension_for_content_type, Blob, BlobStore};
+use crate::twitter::model::media::{store_failed_media_mirror, store_media_mirror, MediaToMirror};
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_NORMAL, RETRY_FACTOR};
+use crate::utils::general::is_transient_http_error;
+use anyhow::Context;
+
+/// Downloads the image, thumbnails it and puts both into the store.
+/// A bad image (4xx other than 429, not an image, can't be thumbnailed) is remembered and never tried again.
+/// Transient download failures (timeouts, 429, 5xx) and a failing store aren't the image's fault -
+/// that media is simply picked up again next run.
+#[tracing::instrument(skip(pool, config, client, store, media), fields(media_key = %media.media_key))]
+pub async fn mirror_media(
+    pool: &PgPool,
+    config: &Settings,
+    client: &MediaClient,
+    store: &dyn BlobStore,
+    media: &MediaToMirror,
+) -> anyhow::Result<()> {
+    let prepared = prepare_media(config, client, media).await;
+    let (original, thumbnail) = match prepared {
+        Ok(blobs) => blobs,
+        Err(e) => {
+            if !is_transient_http_error(&e) {
+                store_failed_media_mirror(pool, media.id, &e.to_string())
+                    .await
+                    .context("failed to store failed media mirror")?;
+            }
+            return Err(e);
+        }
+    };
+
+    let extension = extension_for_content_type(&original.content_type).unwrap_or("img");
+    let mirror_key = format!("{}.{}", media.media_key, extension);
+    let thumbnail_key = format!("{}_thumb.jpg", media.media_key);
+    put_blob(store, &mirror_key, original).await?;
+    put_blob(store, &thumbnail_key, thumbnail).await?;
+
+    store_media_mirror(pool, media.id, &mirror_key, &thumbnail_key)
+        .await
+        .context("failed to store media mirror")?;
+    Ok(())
+}
+
+/// The original, as downloaded, plus its jpeg thumbnail.
+async fn prepare_media(
+    config: &Settings,
+    client: &MediaClient,
+    media: &MediaToMirror,
+) -> anyhow::Result<(Blob, Blob)> {
+    // download, retrying 2 times (5s and 25s) - a 404 or a non-image won't get any better, so only transient errors
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    let original = RetryIf::spawn(
+        retry_strategy,
+        || async { client.download(&media.display_url).await },
+        is_transient_http_error,
+    )
+    .await
+    .context(format!(
+        "failed to download media after {} retries",
+        RETRY_COUNT_NORMAL
+    ))?;
+    if extension_for_content_type(&original.content_type).is_none() {
+        return Err(anyhow::anyhow!(
+            "unsupported image type {}",
+            original.content_type
+        ));
+    }
+
+    let bytes = original.bytes.clone();
+    let max_size = config.mirror.thumbnail_size;
+    let thumbnail = tokio::task::spawn_blocking(move || make_thumbnail(&bytes, max_size))
+        .await?
+        .context("failed to make thumbnail")?;
+
+    Ok((
+        original,
+        Blob {
+            bytes: thumbnail,
+            content_type: "image/jpeg".to_string(),
+        },
+    ))
+}
+
+async fn put_blob(store: &dyn BlobStore, key: &str, blob: Blob) -> anyhow::Result<()> {
+    // put, retrying 2 times (5s and 25s)
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    Retry::spawn(retry_strategy, || async {
+        store.put(key, blob.clone()).await
+    })
+    .await
+    .context(format!(
+        "failed to put {} into the blob store after {} retries",
+        key, RETRY_COUNT_NORMAL
+    ))
+}
@@ -0,0 +1,36 @@
+use std::io::Cursor;
+
+use image::io::Reader;
+use image::{DynamicImage, GenericImageView, ImageOutputFormat};
+
+use crate::utils::constants::{MAX_IMAGE_DIMENSION, THUMBNAIL_JPEG_QUALITY};
+
+/// Scales the image down to fit into a `max_size` square, keeping the aspect ratio, and re-encodes it as jpeg.
+/// Images that already fit are only re-encoded. Gifs lose their animation - the thumbnail is the first frame.
+/// CPU-bound - call it from a blocking task.
+pub fn make_thumbnail(bytes: &[u8], max_size: u32) -> anyhow::Result<Vec<u8>> {
+    // dimensions are in the header, so we can refuse decompression bombs before decoding anything
+    let (width, height) = Reader::new(Cursor::new(bytes))
+        .with_guessed_format()?
+        .into_dimensions()?;
+    if width > MAX_IMAGE_DIMENSION || height > MAX_IMAGE_DIMENSION {
+        return Err(anyhow::anyhow!(
+            "image too large to thumbnail: {}x{}",
+            width,
+            height
+        ));
+    }
+
+    let img = image::load_from_memory(bytes)?;
+    let img = if img.width() > max_size || img.height() > max_size {
+        img.thumbnail(max_size, max_size)
+    } else {
+        img
+    };
+
+    // jpeg has no alpha channel
+    let mut out = Vec::new();
+    DynamicImage::ImageRgb8(img.to_rgb8())
+        .write_to(&mut out, ImageOutputFormat::Jpeg(THUMBNAIL_JPEG_QUALITY))?;
+    Ok(out)
+}
@@ -0,0 +1,4 @@
+pub mod core;
+pub mod routes;
+pub mod scrapers;
+pub mod stores;
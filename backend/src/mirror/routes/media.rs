@@ -0,0 +1,60 @@
+#![allow(clippy::async_yields_async)]
+
+use std::sync::Arc;
+
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+
+use crate::mirror::stores::store::{is_valid_key, BlobStore};
+use crate::utils::constants::MEDIA_CACHE_MAX_AGE_SECS;
+use crate::utils::errors::ApiError;
+use anyhow::Context;
+
+// ----------------------------------------------------------------------------- fns
+
+/// Serves mirrored media (originals and thumbnails) out of the blob store, whichever one is configured.
+/// Whatever is under a key never changes, so the key itself is the etag and everything is cached for good.
+#[tracing::instrument(skip(req, store))]
+#[get("/media/{key}")]
+pub async fn serve_media(
+    req: HttpRequest,
+    key: web::Path<String>,
+    store: web::Data<Arc<dyn BlobStore>>,
+) -> Result<HttpResponse, ApiError> {
+    let key = key.into_inner();
+    if !is_valid_key(&key) {
+        return Ok(HttpResponse::NotFound().finish());
+    }
+
+    let etag = format!("\"{}\"", key);
+    let cache_control = format!("public, max-age={}, immutable", MEDIA_CACHE_MAX_AGE_SECS);
+    let is_not_modified = req
+        .headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .any(|tag| tag.trim() == etag || tag.trim() == "*")
+        })
+        .unwrap_or(false);
+    if is_not_modified {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::CACHE_CONTROL, cache_control))
+            .finish());
+    }
+
+    let blob = match store
+        .get(&key)
+        .await
+        .context(format!("failed to get {} from the blob store", key))?
+    {
+        Some(blob) => blob,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+    Ok(HttpResponse::Ok()
+        .content_type(blob.content_type)
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::CACHE_CONTROL, cache_control))
+        .body(blob.bytes))
+}
@@ -0,0 +1,63 @@
+use std::time::Duration;
+
+use reqwest::header::CONTENT_TYPE;
+
+use crate::config::Settings;
+use crate::mirror::stores::store::Blob;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the api clients.
+pub struct MediaClient {
+    client: reqwest::Client,
+    max_bytes: usize,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl MediaClient {
+    pub fn new(config: &Settings) -> Self {
+        MediaClient {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .expect("failed to build media http client"),
+            max_bytes: config.mirror.max_bytes,
+        }
+    }
+
+    /// Only images - display_url is always one (for videos / gifs it's the preview).
+    /// Reads the body in chunks, so that something huge gets cut off early instead of ending up in memory.
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn download(&self, url: &str) -> anyhow::Result<Blob> {
+        let mut res = self.client.get(url).send().await?;
+        tracing::info!(">>>I: GET call status: {}", &res.status());
+        res.error_for_status_ref()?;
+
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+        if !content_type.starts_with("image/") {
+            return Err(anyhow::anyhow!("{} is not an image: {}", url, content_type));
+        }
+
+        let mut bytes: Vec<u8> = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            bytes.extend_from_slice(&chunk);
+            if bytes.len() > self.max_bytes {
+                return Err(anyhow::anyhow!(
+                    "{} is over the {} byte limit",
+                    url,
+                    self.max_bytes
+                ));
+            }
+        }
+        Ok(Blob {
+            bytes,
+            content_type,
+        })
+    }
+}
@@ -0,0 +1,58 @@
+use std::io::ErrorKind;
+use std::path::PathBuf;
+
+use anyhow::Context;
+use async_trait::async_trait;
+
+use crate::config::Settings;
+use crate::mirror::stores::store::{content_type_for_key, is_valid_key, Blob, BlobStore};
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// One file per key in a single directory. Fine for dev and a single box - anything bigger wants s3.
+pub struct LocalStore {
+    dir: PathBuf,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl LocalStore {
+    pub fn new(config: &Settings) -> anyhow::Result<Self> {
+        let dir = PathBuf::from(&config.mirror.local_dir);
+        std::fs::create_dir_all(&dir)
+            .context(format!("failed to create media dir {}", dir.display()))?;
+        Ok(LocalStore { dir })
+    }
+
+    fn path_for(&self, key: &str) -> anyhow::Result<PathBuf> {
+        if !is_valid_key(key) {
+            return Err(anyhow::anyhow!("invalid blob key {}", key));
+        }
+        Ok(self.dir.join(key))
+    }
+}
+
+/// The content type isn't stored anywhere - it follows from the key's extension.
+/// File io is blocking, so it's moved off the async workers.
+#[async_trait]
+impl BlobStore for LocalStore {
+    #[tracing::instrument(skip(self, blob), level = "debug")]
+    async fn put(&self, key: &str, blob: Blob) -> anyhow::Result<()> {
+        let path = self.path_for(key)?;
+        tokio::task::spawn_blocking(move || std::fs::write(path, blob.bytes)).await??;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Blob>> {
+        let path = self.path_for(key)?;
+        match tokio::task::spawn_blocking(move || std::fs::read(path)).await? {
+            Ok(bytes) => Ok(Some(Blob {
+                bytes,
+                content_type: content_type_for_key(key).to_string(),
+            })),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+}
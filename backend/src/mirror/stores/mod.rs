@@ -0,0 +1,3 @@
+pub mod local;
+pub mod s3;
+pub mod store;
@@ -0,0 +1,194 @@
+use std::time::Duration;
+
+use anyhow::Context;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac, NewMac};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, StatusCode, Url};
+use sha2::{Digest, Sha256};
+
+use crate::config::Settings;
+use crate::mirror::stores::store::{is_valid_key, Blob, BlobStore};
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Anything that speaks the s3 api - aws itself, or minio locally.
+/// Uses path-style urls ({endpoint}/{bucket}/{key}), as that's what minio expects out of the box.
+pub struct S3Store {
+    client: reqwest::Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl S3Store {
+    pub fn new(config: &Settings) -> anyhow::Result<Self> {
+        let mirror = &config.mirror;
+        Ok(S3Store {
+            client: reqwest::Client::builder()
+                .timeout(Duration::from_secs(30))
+                .build()
+                .context("failed to build s3 http client")?,
+            endpoint: Url::parse(&mirror.s3_endpoint).context("failed to parse s3_endpoint")?,
+            bucket: mirror.s3_bucket.clone(),
+            region: mirror.s3_region.clone(),
+            access_key: mirror.s3_access_key.clone(),
+            secret_key: mirror.s3_secret_key.clone(),
+        })
+    }
+
+    /// Builds a request signed with aws signature v4, for `now` - s3 refuses it 15 minutes later.
+    /// Keys are validated to be url-safe, so the path needs no extra encoding.
+    pub fn signed_request(
+        &self,
+        method: Method,
+        key: &str,
+        content_type: Option<&str>,
+        body: &[u8],
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<reqwest::RequestBuilder> {
+        if !is_valid_key(key) {
+            return Err(anyhow::anyhow!("invalid blob key {}", key));
+        }
+        let path = format!("/{}/{}", self.bucket, key);
+        let url = self.endpoint.join(&path)?;
+        let host = match url.port() {
+            Some(port) => format!("{}:{}", url.host_str().unwrap_or_default(), port),
+            None => url.host_str().unwrap_or_default().to_string(),
+        };
+
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = hex::encode(Sha256::digest(body));
+
+        // headers have to be sorted by name, both here and in signed_headers
+        let mut headers = vec![("host", host)];
+        if let Some(ct) = content_type {
+            headers.insert(0, ("content-type", ct.to_string()));
+        }
+        headers.push(("x-amz-content-sha256", payload_hash.clone()));
+        headers.push(("x-amz-date", amz_date.clone()));
+        let authorization = self.authorization(&method, url.path(), &headers, &payload_hash, now);
+
+        // host is set by reqwest itself, from the url - same value as the one we signed
+        let mut req = self
+            .client
+            .request(method, url)
+            .header("x-amz-content-sha256", payload_hash)
+            .header("x-amz-date", amz_date)
+            .header("authorization", authorization);
+        if let Some(ct) = content_type {
+            req = req.header(CONTENT_TYPE, ct);
+        }
+        Ok(req)
+    }
+
+    /// The authorization header for a request, as described in
+    /// https://docs.aws.amazon.com/general/latest/gr/sigv4_signing.html
+    /// `headers` are all the ones being signed - lowercase, sorted by name, including host and x-amz-date.
+    pub fn authorization(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &[(&str, String)],
+        payload_hash: &str,
+        now: DateTime<Utc>,
+    ) -> String {
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let canonical_headers = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect::<String>();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n\n{}\n{}\n{}",
+            method.as_str(),
+            path,
+            canonical_headers,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let signing_key = [self.region.as_str(), "s3", "aws4_request"].iter().fold(
+            hmac_sha256(
+                format!("AWS4{}", self.secret_key).as_bytes(),
+                date.as_bytes(),
+            ),
+            |key, part| hmac_sha256(&key, part.as_bytes()),
+        );
+        let signature = hex::encode(hmac_sha256(&signing_key, string_to_sign.as_bytes()));
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key, scope, signed_headers, signature
+        )
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3Store {
+    #[tracing::instrument(skip(self, blob), level = "debug")]
+    async fn put(&self, key: &str, blob: Blob) -> anyhow::Result<()> {
+        let res = self
+            .signed_request(
+                Method::PUT,
+                key,
+                Some(&blob.content_type),
+                &blob.bytes,
+                Utc::now(),
+            )?
+            .body(blob.bytes)
+            .send()
+            .await?;
+        tracing::info!(">>>I: PUT s3 object status: {}", &res.status());
+        res.error_for_status()?;
+        Ok(())
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Blob>> {
+        let res = self
+            .signed_request(Method::GET, key, None, &[], Utc::now())?
+            .send()
+            .await?;
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let res = res.error_for_status()?;
+        let content_type = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .unwrap_or("application/octet-stream")
+            .to_string();
+        Ok(Some(Blob {
+            bytes: res.bytes().await?.to_vec(),
+            content_type,
+        }))
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    // hmac takes keys of any length, so this can't fail
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("hmac accepts any key length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
@@ -0,0 +1,70 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+
+use crate::config::{BlobStoreKind, Settings};
+use crate::mirror::stores::local::LocalStore;
+use crate::mirror::stores::s3::S3Store;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug, Clone)]
+pub struct Blob {
+    pub bytes: Vec<u8>,
+    pub content_type: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+/// Everything mirroring needs from storage. The job and the /media route only ever talk to this trait,
+/// which one is behind it is decided by config (mirror.store).
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Overwrites whatever is already under the key.
+    async fn put(&self, key: &str, blob: Blob) -> anyhow::Result<()>;
+
+    /// None if there's nothing under the key.
+    async fn get(&self, key: &str) -> anyhow::Result<Option<Blob>>;
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Meant to be called once on startup - the store is shared (Arc) between the scheduler and the routes.
+pub fn build_blob_store(config: &Settings) -> anyhow::Result<Arc<dyn BlobStore>> {
+    let store: Arc<dyn BlobStore> = match config.mirror.store {
+        BlobStoreKind::Local => Arc::new(LocalStore::new(config)?),
+        BlobStoreKind::S3 => Arc::new(S3Store::new(config)?),
+    };
+    Ok(store)
+}
+
+/// Keys end up in file paths and s3 urls, so we only ever accept the ones we could have generated ourselves.
+/// Twitter's media keys are digits and underscores, so this is plenty.
+pub fn is_valid_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= 128
+        && !key.starts_with('.')
+        && key
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-' || c == '.')
+}
+
+pub fn extension_for_content_type(content_type: &str) -> Option<&'static str> {
+    match content_type.split(';').next().unwrap_or_default().trim() {
+        "image/jpeg" => Some("jpg"),
+        "image/png" => Some("png"),
+        "image/gif" => Some("gif"),
+        "image/webp" => Some("webp"),
+        _ => None,
+    }
+}
+
+pub fn content_type_for_key(key: &str) -> &'static str {
+    match key.rsplit('.').next() {
+        Some("jpg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        _ => "application/octet-stream",
+    }
+}
@@ -0,0 +1,30 @@
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::reddit::core::processors::process_subreddit;
+use crate::reddit::scrapers::general::RedditClient;
+
+/// Reddit's limits (~10 calls / min without oauth) are way above the handful of subreddits we follow,
+/// so unlike twitter there's no budgeting here - just one call per subreddit per run.
+#[tracing::instrument(skip(pool, config, client))]
+pub async fn pull_top_posts_for_subreddits(
+    pool: &PgPool,
+    config: &Settings,
+    client: &RedditClient,
+) -> anyhow::Result<()> {
+    let mut total = 0;
+    for subreddit in config.reddit.subreddits.iter() {
+        //fallible, but one subreddit failing shouldn't stop the rest (only logging, retries are inside)
+        match process_subreddit(config, pool, client, subreddit).await {
+            Ok(stored) => total += stored,
+            Err(e) => tracing::error!(">>>E: Failed to pull top posts for r/{}: {}", subreddit, e),
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total stored reddit posts: {} from {} subreddits",
+        total,
+        config.reddit.subreddits.len(),
+    );
+    Ok(())
+}
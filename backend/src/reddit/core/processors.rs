@@ -0,0 +1,57 @@
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+
+use crate::config::Settings;
+use crate::reddit::model::post::store_reddit_post;
+use crate::reddit::scrapers::general::RedditClient;
+use crate::reddit::scrapers::responses::{ListingResponse, PostObject};
+use crate::reddit::scrapers::specific::get_top_posts;
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_NORMAL, RETRY_FACTOR};
+use anyhow::Context;
+
+/// A post that fails to store is logged and skipped, so that it doesn't take down the rest of the subreddit.
+#[tracing::instrument(skip(config, pool, client))]
+pub async fn process_subreddit(
+    config: &Settings,
+    pool: &PgPool,
+    client: &RedditClient,
+    subreddit: &str,
+) -> anyhow::Result<usize> {
+    // get the top posts, retrying 2 times (5s and 25s)
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    let listing = Retry::spawn(retry_strategy, || async {
+        get_top_posts(client, config, subreddit).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch top posts for r/{} after {} retries",
+        subreddit, RETRY_COUNT_NORMAL
+    ))?;
+
+    let mut stored = 0;
+    for post in posts_worth_storing(&listing) {
+        match store_reddit_post(pool, post).await {
+            Ok(_) => stored += 1,
+            Err(e) => tracing::error!(
+                ">>>E: Failed to store reddit post {}. Full error: {}",
+                post.name,
+                e,
+            ),
+        }
+    }
+    Ok(stored)
+}
+
+/// Posts only (kind t3), minus pinned mod announcements - they sit on top of every listing, but they're not news -
+/// and nsfw ones.
+pub fn posts_worth_storing(listing: &ListingResponse) -> impl Iterator<Item = &PostObject> {
+    listing
+        .data
+        .children
+        .iter()
+        .filter(|thing| thing.kind == "t3" && !thing.data.stickied && !thing.data.over_18)
+        .map(|thing| &thing.data)
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, NaiveDateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::feed::model::content::Source;
+use crate::reddit::scrapers::responses::PostObject;
+
+// ----------------------------------------------------------------------------- fn
+
+/// Upsert - top listings return the same posts run after run, with fresh scores.
+#[tracing::instrument(skip(pool, post), level = "debug")]
+pub async fn store_reddit_post(pool: &PgPool, post: &PostObject) -> Result<(), sqlx::error::Error> {
+    let posted_at = DateTime::<Utc>::from_utc(
+        NaiveDateTime::from_timestamp(post.created_utc as i64, 0),
+        Utc,
+    );
+    let body = if post.selftext.is_empty() {
+        None
+    } else {
+        Some(post.selftext.clone())
+    };
+    // for text posts url just points back at the post itself
+    let link_url = if post.is_self {
+        None
+    } else {
+        Some(post.url.clone())
+    };
+
+    sqlx::query!(
+        r#"
+        INSERT INTO posts
+            (id, created_at,
+            source, source_id, channel,
+            posted_at, title, body, author, post_url, link_url,
+            score, comment_count)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+
+        ON CONFLICT (source, source_id)
+        DO UPDATE SET
+            score = $12,
+            comment_count = $13
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        Source::Reddit.to_string(),
+        post.name,
+        post.subreddit,
+        posted_at,
+        post.title,
+        body,
+        post.author,
+        format!("https://www.reddit.com{}", post.permalink),
+        link_url,
+        post.score,
+        post.num_comments,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
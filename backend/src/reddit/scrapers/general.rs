@@ -0,0 +1,60 @@
+use crate::config::Settings;
+use anyhow::Context;
+use serde::de::DeserializeOwned;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug, serde::Serialize)]
+pub struct Params {
+    pub t: Option<String>, // timeframe for top listings - hour / day / week / month / year / all
+    pub limit: Option<u32>,
+    pub raw_json: Option<u8>, // 1 = don't html-escape &, < and > in the response
+}
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the twitter one.
+/// Reddit's public json api needs no auth, but does want a unique, descriptive user agent.
+pub struct RedditClient {
+    client: reqwest::Client,
+    user_agent: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl RedditClient {
+    pub fn new(config: &Settings) -> Self {
+        RedditClient {
+            client: reqwest::Client::new(),
+            user_agent: config.reddit.user_agent.clone(),
+        }
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn json_api_get<T: DeserializeOwned>(
+        &self,
+        mut url: String,
+        params: Option<&Params>,
+    ) -> anyhow::Result<T> {
+        if let Some(params) = params {
+            url = format!("{}?{}", url, serde_url_params::to_string(params)?);
+        }
+
+        let res = self
+            .client
+            .get(url)
+            .header("User-Agent", &self.user_agent)
+            .send()
+            .await?;
+
+        tracing::info!(">>>I: GET call status: {}", &res.status());
+        // reddit answers 429 when we go over the limit - surface it as an error so that retries kick in
+        let res = res.error_for_status()?;
+
+        let raw_body = res.text().await?;
+        // going through serde_json directly (instead of res.json()) gives us the exact field / line that failed
+        let body: T = serde_json::from_str(&raw_body).context(format!(
+            "failed to deserialize response into {}",
+            std::any::type_name::<T>()
+        ))?;
+        Ok(body)
+    }
+}
@@ -0,0 +1,3 @@
+pub mod general;
+pub mod responses;
+pub mod specific;
@@ -0,0 +1,50 @@
+use serde::Deserialize;
+
+// Typed versions of the payloads returned by Reddit's json listing api (append .json to any listing url).
+// Only the fields we actually use are modelled.
+
+// ----------------------------------------------------------------------------- responses
+
+/// GET /r/:subreddit/top.json
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListingResponse {
+    pub data: ListingData,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ListingData {
+    #[serde(default)]
+    pub children: Vec<PostThing>,
+    pub after: Option<String>,
+}
+
+// ----------------------------------------------------------------------------- objects
+
+/// Reddit wraps every object in a "thing" = {kind, data}. Posts are kind "t3".
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostThing {
+    pub kind: String,
+    pub data: PostObject,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PostObject {
+    pub id: String,
+    pub name: String, // "fullname" = kind + id, eg t3_o8k2xz - unique across reddit
+    pub subreddit: String,
+    pub title: String,
+    #[serde(default)]
+    pub selftext: String,
+    pub author: String,
+    pub permalink: String, // relative, eg /r/solana/comments/o8k2xz/...
+    pub url: String,       // where a link post points to, or the post itself for text posts
+    pub created_utc: f64,  // unix seconds, sent as a float
+    pub score: i64,
+    pub num_comments: i64,
+    #[serde(default)]
+    pub is_self: bool,
+    #[serde(default)]
+    pub stickied: bool,
+    #[serde(default)]
+    pub over_18: bool,
+}
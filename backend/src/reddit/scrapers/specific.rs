@@ -0,0 +1,18 @@
+use crate::config::Settings;
+use crate::reddit::scrapers::general::{Params, RedditClient};
+use crate::reddit::scrapers::responses::ListingResponse;
+
+#[tracing::instrument(skip(client, config))]
+pub async fn get_top_posts(
+    client: &RedditClient,
+    config: &Settings,
+    subreddit: &str,
+) -> anyhow::Result<ListingResponse> {
+    let url = format!("https://www.reddit.com/r/{}/top.json", subreddit);
+    let params = Params {
+        t: Some(config.reddit.top_timeframe.clone()),
+        limit: Some(config.reddit.posts_per_subreddit),
+        raw_json: Some(1),
+    };
+    client.json_api_get(url, Some(&params)).await
+}
@@ -0,0 +1,29 @@
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::rss::core::processors::process_rss_feed;
+use crate::rss::scrapers::general::RssClient;
+
+/// One call per feed per run - feeds aren't rate limited, but we still don't want to hammer people's blogs.
+#[tracing::instrument(skip(pool, config, client))]
+pub async fn pull_entries_for_rss_feeds(
+    pool: &PgPool,
+    config: &Settings,
+    client: &RssClient,
+) -> anyhow::Result<()> {
+    let mut total = 0;
+    for feed_url in config.rss.feed_urls.iter() {
+        //fallible, but one feed failing shouldn't stop the rest (only logging, retries are inside)
+        match process_rss_feed(config, pool, client, feed_url).await {
+            Ok(stored) => total += stored,
+            Err(e) => tracing::error!(">>>E: Failed to pull rss feed {}: {}", feed_url, e),
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total new rss entries: {} from {} feeds",
+        total,
+        config.rss.feed_urls.len(),
+    );
+    Ok(())
+}
@@ -0,0 +1,73 @@
+use chrono::{DateTime, Duration, Utc};
+use feed_rs::model::Feed;
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+
+use crate::config::Settings;
+use crate::rss::model::entry::store_rss_entry;
+use crate::rss::scrapers::general::RssClient;
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_NORMAL, RETRY_FACTOR};
+use anyhow::Context;
+
+/// An entry that fails to store is logged and skipped, so that it doesn't take down the rest of the feed.
+/// Returns the number of entries we hadn't seen before.
+#[tracing::instrument(skip(config, pool, client))]
+pub async fn process_rss_feed(
+    config: &Settings,
+    pool: &PgPool,
+    client: &RssClient,
+    feed_url: &str,
+) -> anyhow::Result<usize> {
+    // get the feed, retrying 2 times (5s and 25s)
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    let feed = Retry::spawn(retry_strategy, || async { client.get_feed(feed_url).await })
+        .await
+        .context(format!(
+            "failed to fetch rss feed {} after {} retries",
+            feed_url, RETRY_COUNT_NORMAL
+        ))?;
+
+    // feeds often carry years of archive - the first poll shouldn't dump all of it into the feed
+    let cutoff = Utc::now() - Duration::days(config.rss.max_entry_age_days);
+    Ok(store_feed_entries(pool, &feed, feed_url, cutoff).await)
+}
+
+/// Stores the entries published (or, failing that, updated) after `cutoff`. Entries with neither date
+/// are skipped too - there's no telling whether they're news or years old.
+/// Returns the number of entries we hadn't seen before.
+#[tracing::instrument(skip(pool, feed), level = "debug")]
+pub async fn store_feed_entries(
+    pool: &PgPool,
+    feed: &Feed,
+    feed_url: &str,
+    cutoff: DateTime<Utc>,
+) -> usize {
+    // the feed's own title (eg "Solana Labs - Medium") reads better than the url
+    let channel = feed
+        .title
+        .as_ref()
+        .map(|t| t.content.clone())
+        .unwrap_or_else(|| feed_url.to_string());
+
+    let mut stored = 0;
+    for entry in feed.entries.iter() {
+        let posted_at = match entry.published.or(entry.updated) {
+            Some(posted_at) if posted_at >= cutoff => posted_at,
+            _ => continue,
+        };
+        match store_rss_entry(pool, feed_url, &channel, entry, posted_at).await {
+            Ok(true) => stored += 1,
+            Ok(false) => {}
+            Err(e) => tracing::error!(
+                ">>>E: Failed to store rss entry {} from {}. Full error: {}",
+                entry.id,
+                feed_url,
+                e,
+            ),
+        }
+    }
+    stored
+}
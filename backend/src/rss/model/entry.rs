@@ -0,0 +1,69 @@
+use chrono::{DateTime, Utc};
+use feed_rs::model::Entry;
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::feed::model::content::Source;
+use crate::utils::general::html_to_text;
+
+// ----------------------------------------------------------------------------- fn
+
+/// Deduped on the entry's guid (rss) / id (atom) - feeds keep returning the same entries run after run.
+/// Only within the feed it came from: guids are whatever the publisher picked (often a bare post number),
+/// so two feeds can easily share one. Returns false if we already had the entry.
+#[tracing::instrument(skip(pool, entry), level = "debug")]
+pub async fn store_rss_entry(
+    pool: &PgPool,
+    feed_url: &str,
+    channel: &str,
+    entry: &Entry,
+    posted_at: DateTime<Utc>,
+) -> Result<bool, sqlx::error::Error> {
+    let title = entry
+        .title
+        .as_ref()
+        .map(|t| t.content.clone())
+        .unwrap_or_default();
+    // summary is the short version - what we want on a feed card. Full content as a fallback.
+    // Either is usually html, which the feed would otherwise show as raw markup
+    let body = entry
+        .summary
+        .as_ref()
+        .map(|s| s.content.clone())
+        .or_else(|| entry.content.as_ref().and_then(|c| c.body.clone()))
+        .map(|html| html_to_text(&html))
+        .filter(|text| !text.is_empty());
+    let author = entry.authors.first().map(|a| a.name.clone());
+    let post_url = entry
+        .links
+        .first()
+        .map(|l| l.href.clone())
+        .unwrap_or_default();
+
+    let res = sqlx::query!(
+        r#"
+        INSERT INTO posts
+            (id, created_at,
+            source, source_id, channel,
+            posted_at, title, body, author, post_url, link_url,
+            score, comment_count)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, NULL, NULL, NULL)
+
+        ON CONFLICT (source, source_id)
+        DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        Source::Rss.to_string(),
+        format!("{}#{}", feed_url, entry.id),
+        channel,
+        posted_at,
+        title,
+        body,
+        author,
+        post_url,
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
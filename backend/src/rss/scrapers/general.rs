@@ -0,0 +1,38 @@
+use anyhow::Context;
+use feed_rs::model::Feed;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the twitter one.
+pub struct RssClient {
+    client: reqwest::Client,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl RssClient {
+    pub fn new() -> Self {
+        RssClient {
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Works for both RSS and Atom - feed_rs figures out which one it got.
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn get_feed(&self, url: &str) -> anyhow::Result<Feed> {
+        let res = self.client.get(url).send().await?;
+        tracing::info!(">>>I: GET call status: {}", &res.status());
+        let res = res.error_for_status()?;
+
+        let raw_body = res.bytes().await?;
+        let feed = feed_rs::parser::parse(&raw_body[..])
+            .context(format!("failed to parse {} as an rss / atom feed", url))?;
+        Ok(feed)
+    }
+}
+
+impl Default for RssClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
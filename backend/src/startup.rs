@@ -7,18 +7,34 @@ use sqlx::PgPool;
 use tracing_actix_web::TracingLogger;
 
 use crate::config::Settings;
-use crate::twitter::routes::pull::{backfill, pull};
-use crate::twitter::routes::serve::{health, serve_tweets};
+use crate::digest::mailers::mailer::Mailer;
+use crate::digest::routes::subscribe::{confirm_subscription, subscribe, unsubscribe};
+use crate::feed::routes::serve::serve_feed;
+use crate::mirror::routes::media::serve_media;
+use crate::mirror::stores::store::BlobStore;
+use crate::twitter::routes::entities::serve_trending_entities;
+use crate::twitter::routes::pull::{backfill, pull, refresh};
+use crate::twitter::routes::search::serve_search;
+use crate::twitter::routes::serve::{health, serve_thread, serve_tweets};
+use crate::twitter::routes::syndication::{serve_feed_atom, serve_feed_json, serve_feed_rss};
+use crate::twitter::scrapers::general::TwitterClient;
+use crate::utils::constants::READER_TOKEN_HEADER;
 
-#[tracing::instrument(skip(pool, config))]
+#[tracing::instrument(skip(pool, config, client, mailer, blob_store))]
 pub fn run_server(
     addr: &str,
     pool: Arc<PgPool>,
     config: Arc<Settings>,
+    client: Arc<TwitterClient>,
+    mailer: Arc<dyn Mailer>,
+    blob_store: Arc<dyn BlobStore>,
 ) -> Result<Server, std::io::Error> {
     //important to add web::Data() - else get https://stackoverflow.com/questions/56117273/actix-web-reports-app-data-is-not-configured-when-processing-a-file-upload
     let pool = web::Data::new(pool);
     let config = web::Data::new(config);
+    let client = web::Data::new(client);
+    let mailer = web::Data::new(mailer);
+    let blob_store = web::Data::new(blob_store);
 
     let server = HttpServer::new(move || {
         let cors = Cors::default()
@@ -26,6 +42,8 @@ pub fn run_server(
             .allowed_methods(vec!["GET", "POST"])
             .allowed_headers(vec![http::header::AUTHORIZATION, http::header::ACCEPT])
             .allowed_header(http::header::CONTENT_TYPE)
+            .allowed_header(READER_TOKEN_HEADER)
+            .expose_headers(vec![READER_TOKEN_HEADER])
             .max_age(3600);
 
         App::new()
@@ -33,11 +51,26 @@ pub fn run_server(
             .wrap(TracingLogger::default()) //add request_id to actix events
             .service(health)
             .service(serve_tweets)
+            .service(serve_thread)
+            .service(serve_search)
+            .service(serve_trending_entities)
+            .service(serve_feed)
+            .service(serve_feed_rss)
+            .service(serve_feed_atom)
+            .service(serve_feed_json)
+            .service(subscribe)
+            .service(confirm_subscription)
+            .service(unsubscribe)
+            .service(serve_media)
             // todo no need in prod
             // .service(pull)
             // .service(backfill)
+            // .service(refresh)
             .app_data(pool.clone())
             .app_data(config.clone())
+            .app_data(client.clone())
+            .app_data(mailer.clone())
+            .app_data(blob_store.clone())
     })
     .bind(addr)?
     .run();
@@ -1,24 +1,37 @@
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use tokio_retry::strategy::ExponentialBackoff;
 use tokio_retry::Retry;
 
 use crate::config::Settings;
-use crate::twitter::core::loops::loop_until_hit_rate_limit;
+use crate::twitter::core::loops::{loop_until_hit_rate_limit, put_pending_first};
 use crate::twitter::core::processors::{
-    process_helper_tweet, process_rt_original_tweet, process_user_timeline,
+    process_helper_tweets, process_link_preview, process_metrics_refresh,
+    process_rt_original_tweets, process_user_timeline,
 };
+use crate::twitter::model::link_preview::fetch_urls_to_unfurl;
+use crate::twitter::model::pending::{fetch_pending_work, replace_pending_work, JobKind};
+use crate::twitter::model::seen::delete_seen_tweets_before;
+use crate::twitter::model::tweet::Tweet;
 use crate::twitter::model::tweet::{
-    fetch_core_tweets_to_backfill, fetch_helper_tweets_to_backfill,
+    fetch_ancestor_tweets_to_backfill, fetch_core_tweets_to_backfill,
+    fetch_helper_tweets_to_backfill, fetch_tweets_for_metrics_refresh,
 };
+use crate::twitter::scrapers::general::{Endpoint, TwitterClient};
 use crate::twitter::scrapers::specific::fetch_all_followed_users;
-use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_IMPORTANT, RETRY_FACTOR};
+use crate::twitter::scrapers::unfurl::LinkPreviewClient;
+use crate::utils::constants::{
+    RETRY_BASE, RETRY_COUNT_IMPORTANT, RETRY_FACTOR, TWEET_LOOKUP_BATCH_SIZE,
+};
 use anyhow::Context;
 use std::cmp::min;
+use std::sync::atomic::AtomicUsize;
 
-#[tracing::instrument(skip(pool, config))]
+#[tracing::instrument(skip(pool, config, client))]
 pub async fn pull_timelines_for_followed_users(
     pool: &PgPool,
     config: &Settings,
+    client: &TwitterClient,
 ) -> anyhow::Result<()> {
     // factor = to turn milliseconds into seconds
     // base = what gets put to the power on each iteration
@@ -27,7 +40,7 @@ pub async fn pull_timelines_for_followed_users(
         .factor(RETRY_FACTOR)
         .take(RETRY_COUNT_IMPORTANT);
     let (users, _) = Retry::spawn(retry_strategy, || async {
-        fetch_all_followed_users(config).await
+        fetch_all_followed_users(client, config).await
     })
     .await
     .context(format!(
@@ -36,10 +49,38 @@ pub async fn pull_timelines_for_followed_users(
     ))?;
 
     let users = &users[..min(config.app.max_users, users.len())];
+    // timelines that didn't fit in last time go first
+    let users = prioritize_pending_work(pool, &JobKind::UserTimeline, users.to_vec(), |u| {
+        u.id.as_str()
+    })
+    .await?;
+
     //the below is fallible, but it won't let me propagate error up, so handled inside of loop (only logging, no retries)
-    loop_until_hit_rate_limit(&users, config, pool, process_user_timeline, 1500).await;
+    // the budget is in calls: every user started gets one for their first page, whatever's left
+    // over is shared out between the timelines that need more than one page
+    let budget = client.remaining(Endpoint::UserTimeline) as usize;
+    let spare_calls = AtomicUsize::new(budget.saturating_sub(users.len()));
+    let leftover = loop_until_hit_rate_limit(
+        &users,
+        config,
+        pool,
+        client,
+        |config, pool, client, user| {
+            process_user_timeline(config, pool, client, user, &spare_calls)
+        },
+        budget,
+    )
+    .await;
+    let leftover_ids = leftover.iter().map(|u| u.id.clone()).collect::<Vec<_>>();
+    replace_pending_work(pool, &JobKind::UserTimeline, &leftover_ids)
+        .await
+        .context("failed to store pending user timelines")?;
 
-    tracing::info!(">>>I: total processed user timelines: {}", users.len());
+    tracing::info!(
+        ">>>I: total processed user timelines: {}, deferred: {}",
+        users.len() - leftover_ids.len(),
+        leftover_ids.len(),
+    );
     Ok(())
 }
 
@@ -48,17 +89,21 @@ pub async fn pull_timelines_for_followed_users(
 ///     1.1) backfill media + helper tweets for them
 /// 2) take helper tweets in the last 24h ordered by popularity
 ///     2.1) backfill media for them
+/// 3) take ancestors (helpers further up a reply / quote chain) whose own parent is missing
+///     3.1) backfill their parent = one more level of the chain, up to max_thread_depth
 ///
 /// Capacity calc:
-/// - Twitter gives me 900 calls / 15min
+/// - Twitter gives me 900 calls / 15min (the client tracks what's actually left)
+/// - Each call looks up a batch of up to 100 tweets, so that's up to 90k tweets / 15min
 /// - With 130 people followed and 13k tweets pulled, I have to backfill around 600 tweets for 7d / 85 for 1d.
 /// - With 1500 people followed and 150k tweets pulled, this becomes 6900 for 7d and 977.5 for 24h.
 /// - BUT: since we never have to backfill a tweet twice, and we'll be calling this func every 15min, the amount will go down over time.
 /// - In other words it should be safe to set days_back to 7.
-#[tracing::instrument(skip(pool, config))]
+#[tracing::instrument(skip(pool, config, client))]
 pub async fn backfill_missing_media_and_helper_tweets(
     pool: &PgPool,
     config: &Settings,
+    client: &TwitterClient,
 ) -> anyhow::Result<()> {
     // 1) process core (normal + rt_orinals) tweets (download media + helpers)
     // sometimes a tweet will be deleted (eg 1401933150012559361) - and we keep trying to backfill it. In theory should handle - but for now I'll just let it drop out of timeframe.
@@ -73,15 +118,29 @@ pub async fn backfill_missing_media_and_helper_tweets(
         "failed to fetch core tweets to backfill after {} retries",
         RETRY_COUNT_IMPORTANT
     ))?;
+    let core =
+        prioritize_pending_work(pool, &JobKind::CoreTweet, core, |t| t.tweet_id.as_str()).await?;
     //the below is fallible, but it won't let me propagate error up, so handled inside of loop (only logging, no retries)
-    loop_until_hit_rate_limit(
-        &core,
+    // each api call looks up a whole batch, so the budget is in batches
+    let core_batches = into_batches(&core);
+    let budget = client.remaining(Endpoint::TweetLookup) as usize;
+    let core_leftover = loop_until_hit_rate_limit(
+        &core_batches,
         config,
         pool,
-        process_rt_original_tweet,
-        900, //in theory can ask twitter for remaining
+        client,
+        process_rt_original_tweets,
+        budget,
     )
     .await;
+    let core_leftover_ids = core_leftover
+        .into_iter()
+        .flatten()
+        .map(|t| t.tweet_id.clone())
+        .collect::<Vec<_>>();
+    replace_pending_work(pool, &JobKind::CoreTweet, &core_leftover_ids)
+        .await
+        .context("failed to store pending core tweets")?;
 
     // 2) process helper tweets (download media only)
     let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
@@ -95,17 +154,203 @@ pub async fn backfill_missing_media_and_helper_tweets(
         "failed to fetch helper tweets to backfill after {} retries",
         RETRY_COUNT_IMPORTANT
     ))?;
+    let helpers = prioritize_pending_work(pool, &JobKind::HelperTweet, helpers, |t| {
+        t.tweet_id.as_str()
+    })
+    .await?;
+    //the below is fallible, but it won't let me propagate error up, so handled inside of loop (only logging, no retries)
+    // asking again, as the core tweets above used up some of the budget
+    let helper_batches = into_batches(&helpers);
+    let budget = client.remaining(Endpoint::TweetLookup) as usize;
+    let helper_leftover = loop_until_hit_rate_limit(
+        &helper_batches,
+        config,
+        pool,
+        client,
+        process_helper_tweets,
+        budget,
+    )
+    .await;
+    let helper_leftover_ids = helper_leftover
+        .into_iter()
+        .flatten()
+        .map(|t| t.tweet_id.clone())
+        .collect::<Vec<_>>();
+    replace_pending_work(pool, &JobKind::HelperTweet, &helper_leftover_ids)
+        .await
+        .context("failed to store pending helper tweets")?;
+
+    // 3) process ancestors (download their parents, same as for core tweets)
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_IMPORTANT);
+    let ancestors = Retry::spawn(retry_strategy, || async {
+        fetch_ancestor_tweets_to_backfill(pool, 7, config.app.max_thread_depth).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch ancestor tweets to backfill after {} retries",
+        RETRY_COUNT_IMPORTANT
+    ))?;
+    let ancestors = prioritize_pending_work(pool, &JobKind::AncestorTweet, ancestors, |t| {
+        t.tweet_id.as_str()
+    })
+    .await?;
     //the below is fallible, but it won't let me propagate error up, so handled inside of loop (only logging, no retries)
-    loop_until_hit_rate_limit(&helpers, config, pool, process_helper_tweet, 900).await;
+    let ancestor_batches = into_batches(&ancestors);
+    let budget = client.remaining(Endpoint::TweetLookup) as usize;
+    let ancestor_leftover = loop_until_hit_rate_limit(
+        &ancestor_batches,
+        config,
+        pool,
+        client,
+        process_rt_original_tweets,
+        budget,
+    )
+    .await;
+    let ancestor_leftover_ids = ancestor_leftover
+        .into_iter()
+        .flatten()
+        .map(|t| t.tweet_id.clone())
+        .collect::<Vec<_>>();
+    replace_pending_work(pool, &JobKind::AncestorTweet, &ancestor_leftover_ids)
+        .await
+        .context("failed to store pending ancestor tweets")?;
 
     tracing::info!(
-        ">>>I: Total executed: {} core and {} helpers",
-        core.len(),
-        helpers.len(),
+        ">>>I: Total executed: {} core, {} helpers and {} ancestors, deferred: {} core, {} helpers and {} ancestors",
+        core.len() - core_leftover_ids.len(),
+        helpers.len() - helper_leftover_ids.len(),
+        ancestors.len() - ancestor_leftover_ids.len(),
+        core_leftover_ids.len(),
+        helper_leftover_ids.len(),
+        ancestor_leftover_ids.len(),
     );
     Ok(())
 }
 
+/// Same idea as the media backfill, only the "api" is whatever site the link points to.
+/// Each link is unfurled once (successfully or not) and shared by every tweet that links to it.
+#[tracing::instrument(skip(pool, config, client))]
+pub async fn backfill_missing_link_previews(
+    pool: &PgPool,
+    config: &Settings,
+    client: &LinkPreviewClient,
+) -> anyhow::Result<()> {
+    let since = Utc::now() - Duration::hours(config.link_previews.window_hours);
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_IMPORTANT);
+    let urls = Retry::spawn(retry_strategy, || async {
+        fetch_urls_to_unfurl(pool, since, config.link_previews.max_per_run).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch urls to unfurl after {} retries",
+        RETRY_COUNT_IMPORTANT
+    ))?;
+
+    let mut failed = 0;
+    for url in urls.iter() {
+        //only logging, same as the other loops - one bad link shouldn't stop the rest
+        if let Err(e) = process_link_preview(pool, client, url).await {
+            tracing::error!(">>>E: failed to process link preview for {}: {}", url, e);
+            failed += 1;
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total processed link previews: {}, failed to store: {}",
+        urls.len() - failed,
+        failed,
+    );
+    Ok(())
+}
+
+/// Timelines are pulled incrementally (since_id), so tweets we've already stored never come back with them.
+/// This is the cheaper pass that keeps their metrics up to date - 100 tweets per api call.
+/// Nothing gets deferred here - if we run out of budget, the next run refreshes them anyway.
+#[tracing::instrument(skip(pool, config, client))]
+pub async fn refresh_metrics_for_recent_tweets(
+    pool: &PgPool,
+    config: &Settings,
+    client: &TwitterClient,
+) -> anyhow::Result<()> {
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_IMPORTANT);
+    let tweets = Retry::spawn(retry_strategy, || async {
+        fetch_tweets_for_metrics_refresh(pool, config.app.metrics_refresh_hours).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch tweets for metrics refresh after {} retries",
+        RETRY_COUNT_IMPORTANT
+    ))?;
+
+    let batches = into_batches(&tweets);
+    let budget = client.remaining(Endpoint::TweetLookup) as usize;
+    //the below is fallible, but it won't let me propagate error up, so handled inside of loop (only logging, no retries)
+    let leftover = loop_until_hit_rate_limit(
+        &batches,
+        config,
+        pool,
+        client,
+        process_metrics_refresh,
+        budget,
+    )
+    .await;
+
+    tracing::info!(
+        ">>>I: Refreshed metrics for {} batches, skipped {}",
+        batches.len() - leftover.len(),
+        leftover.len(),
+    );
+    Ok(())
+}
+
+/// Groups backfill candidates into chunks that fit into a single /2/tweets?ids= lookup.
+pub fn into_batches(tweets: &[Tweet]) -> Vec<Vec<Tweet>> {
+    tweets
+        .chunks(TWEET_LOOKUP_BATCH_SIZE)
+        .map(|chunk| chunk.to_vec())
+        .collect()
+}
+
+/// Impressions past their ttl no longer hide anything, so there's no point keeping them.
+#[tracing::instrument(skip(pool, config))]
+pub async fn prune_expired_seen_tweets(pool: &PgPool, config: &Settings) -> anyhow::Result<()> {
+    let before = Utc::now() - Duration::hours(config.app.seen_tweets_ttl_hours);
+    let pruned = delete_seen_tweets_before(pool, before)
+        .await
+        .context("failed to delete expired seen tweets")?;
+    tracing::info!(">>>I: total pruned seen tweets: {}", pruned);
+    Ok(())
+}
+
+/// Pulls the work that didn't fit into the rate limit last time and puts it at the front of the queue.
+/// Pending objects that are no longer in `objects` (eg already backfilled) are simply dropped.
+#[tracing::instrument(skip(pool, objects, key))]
+pub async fn prioritize_pending_work<T>(
+    pool: &PgPool,
+    job_kind: &JobKind,
+    objects: Vec<T>,
+    key: impl Fn(&T) -> &str,
+) -> anyhow::Result<Vec<T>> {
+    let pending_ids = fetch_pending_work(pool, job_kind)
+        .await
+        .context(format!("failed to fetch pending work for {}", job_kind))?
+        .into_iter()
+        .map(|pw| pw.object_id)
+        .collect::<Vec<String>>();
+    tracing::info!(
+        ">>>I: {} pending {} objects from the previous run",
+        pending_ids.len(),
+        job_kind
+    );
+    Ok(put_pending_first(objects, &pending_ids, key))
+}
+
 // ----------------------------------------------------------------------------- keeping for personal ref - sync fn w retry crate
 // let users = retry_with_index(Fixed::from_millis(10000), |current_try| {
 //     if current_try > 3 {
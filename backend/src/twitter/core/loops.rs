@@ -1,26 +1,35 @@
 use std::cmp::min;
+use std::collections::HashMap;
 use std::future::Future;
 
 use sqlx::PgPool;
 
 use crate::config::Settings;
+use crate::twitter::scrapers::general::TwitterClient;
 use crate::utils::general::type_name_of;
 
-#[tracing::instrument(skip(object_arr, settings, pool, f, rate_limit))]
+/// rate_limit should come from the client (TwitterClient::remaining) - the client will also pause
+/// individual calls if we run out of budget mid-loop, so this is just a cap on how much work we start.
+///
+/// Returns the objects that didn't fit in, plus the ones that failed, so that the caller can persist
+/// them for the next run.
+#[tracing::instrument(skip(object_arr, settings, pool, client, f, rate_limit))]
 pub async fn loop_until_hit_rate_limit<'a, T, Fut>(
     object_arr: &'a [T],
     settings: &'a Settings,
     pool: &'a PgPool,
-    f: impl Fn(&'a Settings, &'a PgPool, &'a T) -> Fut + Copy,
+    client: &'a TwitterClient,
+    f: impl Fn(&'a Settings, &'a PgPool, &'a TwitterClient, &'a T) -> Fut + Copy,
     rate_limit: usize,
-) where
+) -> Vec<&'a T>
+where
     // https://stackoverflow.com/questions/60717746/how-to-accept-an-async-function-as-an-argument
     Fut: Future<Output = anyhow::Result<()>>,
 {
     // this is the easiest way to impl. rate limits.
     // A much harder approach would be to wrap one in Arc(Mutex()) and update from each async task.
     let total = object_arr.len();
-    let capped_total = min(total, rate_limit); // in theory should handle the ones that didn't fit in, but for now cba
+    let capped_total = min(total, rate_limit); // the ones that didn't fit in are returned to the caller
 
     let f_name = type_name_of(f);
     let mut futs = vec![];
@@ -28,17 +37,55 @@ pub async fn loop_until_hit_rate_limit<'a, T, Fut>(
         futs.push(async move {
             tracing::info!(">>>I: Processing {}/{}", i + 1, total);
             // if try to add ? -> get: cannot use the `?` operator in an async block that returns `()`. So instead handing errors here.
-            f(settings, pool, object).await.unwrap_or_else(|e| {
-                tracing::error!(
-                    ">>>E: Failed to process iteration {} of the loop. Function used: {} Full error: {}",
-                    i + 1,
-                    f_name,
-                    e,
-                );
-            });
+            match f(settings, pool, client, object).await {
+                Ok(_) => None,
+                Err(e) => {
+                    tracing::error!(
+                        ">>>E: Failed to process iteration {} of the loop. Function used: {} Full error: {}",
+                        i + 1,
+                        f_name,
+                        e,
+                    );
+                    Some(object)
+                }
+            }
         });
     }
-    futures::future::join_all(futs).await;
+    let mut leftover = futures::future::join_all(futs)
+        .await
+        .into_iter()
+        .flatten()
+        .collect::<Vec<_>>();
+
+    if capped_total < total {
+        tracing::info!(
+            ">>>I: {} objects didn't fit into the rate limit, deferring to next run",
+            total - capped_total
+        );
+    }
+    leftover.extend(object_arr[capped_total..].iter());
+    leftover
+}
+
+/// Moves objects that were left over from the previous run to the front of the queue (oldest first),
+/// keeping the original order for everything else.
+#[tracing::instrument(skip(objects, pending_ids, key), level = "debug")]
+pub fn put_pending_first<T>(
+    objects: Vec<T>,
+    pending_ids: &[String],
+    key: impl Fn(&T) -> &str,
+) -> Vec<T> {
+    let positions: HashMap<&str, usize> = pending_ids
+        .iter()
+        .enumerate()
+        .map(|(i, id)| (id.as_str(), i))
+        .collect();
+    let (mut pending, rest): (Vec<T>, Vec<T>) = objects
+        .into_iter()
+        .partition(|o| positions.contains_key(key(o)));
+    pending.sort_by_key(|o| positions[key(o)]);
+    pending.extend(rest);
+    pending
 }
 
 // pub async fn loop_until_hit_rate_limit_sync<'a, T, Fut>(
@@ -1,133 +1,336 @@
-use serde_json::Value;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use chrono::{Duration, Utc};
 use sqlx::PgPool;
 use tokio_retry::strategy::ExponentialBackoff;
 use tokio_retry::Retry;
 
 use crate::config::Settings;
+use crate::twitter::model::link_preview::{store_failed_link_preview, store_link_preview};
 use crate::twitter::model::media::handle_media_for_tweet;
-use crate::twitter::model::tweet::{store_tweet, Tweet};
-use crate::twitter::model::user::store_user;
-use crate::twitter::scrapers::specific::{get_single_tweet, get_user_timeline};
-use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_NORMAL, RETRY_FACTOR};
+use crate::twitter::model::snapshot::store_tweet_metric_snapshot;
+use crate::twitter::model::tweet::{
+    extract_tweet_metrics, store_tweet, update_tweet_metrics, Tweet,
+};
+use crate::twitter::model::user::{fetch_user_if_exists, store_user, update_newest_tweet_id};
+use crate::twitter::scrapers::general::TwitterClient;
+use crate::twitter::scrapers::responses::{Includes, TimelineResponse, TweetObject, UserObject};
+use crate::twitter::scrapers::specific::{get_tweets_batch, get_user_timeline};
+use crate::twitter::scrapers::unfurl::LinkPreviewClient;
+use crate::utils::constants::{
+    LINK_PREVIEW_MAX_ATTEMPTS, LINK_PREVIEW_RETRY_HOURS, RETRY_BASE, RETRY_COUNT_NORMAL,
+    RETRY_FACTOR,
+};
+use crate::utils::general::is_transient_http_error;
 use anyhow::Context;
 
-#[tracing::instrument(skip(config, pool, user_object))]
+/// Only pulls tweets newer than the newest one we've already seen for the user (since_id),
+/// following next_token until we catch up. Metrics for older tweets are refreshed separately.
+///
+/// The caller budgets one call per user - every page past the first is taken out of `spare_calls`,
+/// which is shared by all the timelines processed in the same run. If that runs out before we've
+/// caught up, the user errors out and gets deferred, with since_id left where it was.
+#[tracing::instrument(skip(config, pool, client, user_object, spare_calls))]
 pub async fn process_user_timeline(
     config: &Settings,
     pool: &PgPool,
-    user_object: &Value,
+    client: &TwitterClient,
+    user_object: &UserObject,
+    spare_calls: &AtomicUsize,
 ) -> anyhow::Result<()> {
-    // get timeline, retrying 2 times (5s and 25s)
-    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
-        .factor(RETRY_FACTOR)
-        .take(RETRY_COUNT_NORMAL);
-    let (user_timeline, _) = Retry::spawn(retry_strategy, || async {
-        get_user_timeline(
-            config,
-            user_object["id"].as_str().ok_or(anyhow::anyhow!("no id"))?,
-        )
+    let since_id = fetch_user_if_exists(pool, &user_object.id)
         .await
-    })
-    .await
-    .context(format!(
-        "failed to fetch user timeline after {} retries",
-        RETRY_COUNT_NORMAL
-    ))?;
+        .context("failed to fetch user when processing timeline")?
+        .and_then(|u| u.newest_tweet_id);
 
-    // 1 store users (must go first)
-    if let Some(users) = user_timeline["includes"]["users"].as_array() {
-        for user in users.iter() {
-            store_user(pool, &user)
-                .await
-                .context("failed to store user when processing timeline")?;
+    let mut newest_tweet_id: Option<String> = None;
+    let mut page_token: Option<String> = None;
+    for page in 1..=config.app.max_timeline_pages {
+        if page > 1 && !take_spare_call(spare_calls) {
+            return Err(anyhow::anyhow!(
+                "out of timeline budget after {} pages for user {}, deferring to next run",
+                page - 1,
+                user_object.id
+            ));
+        }
+
+        // get timeline, retrying 2 times (5s and 25s)
+        let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+            .factor(RETRY_FACTOR)
+            .take(RETRY_COUNT_NORMAL);
+        let (user_timeline, _) = Retry::spawn(retry_strategy, || async {
+            get_user_timeline(
+                client,
+                config,
+                &user_object.id,
+                since_id.clone(),
+                page_token.clone(),
+            )
+            .await
+        })
+        .await
+        .context(format!(
+            "failed to fetch user timeline after {} retries",
+            RETRY_COUNT_NORMAL
+        ))?;
+
+        store_timeline_page(pool, &user_timeline)
+            .await
+            .context("failed to store timeline page")?;
+
+        // pages go newest > oldest, so the first page holds the newest tweet
+        if newest_tweet_id.is_none() {
+            newest_tweet_id = user_timeline.meta.newest_id.clone();
+        }
+
+        // first time we see the user we only want the last few tweets, not their whole history
+        if since_id.is_none() {
+            break;
+        }
+        match user_timeline.meta.next_token {
+            Some(next_token) => page_token = Some(next_token),
+            None => break,
         }
+        if page == config.app.max_timeline_pages {
+            // moving since_id past tweets we never pulled would skip them for good - so keep it,
+            // and next run pages through from the old since_id again
+            tracing::info!(
+                ">>>I: Hit max pages ({}) for user {}, keeping since_id for next run",
+                page,
+                user_object.id
+            );
+            return Ok(());
+        }
+    }
+
+    // only moved forward once we've caught up and everything got stored - otherwise we'll retry
+    // from the old since_id next time
+    if let Some(newest_tweet_id) = newest_tweet_id {
+        update_newest_tweet_id(pool, &user_object.id, &newest_tweet_id)
+            .await
+            .context("failed to update newest tweet id for user")?;
+    }
+    Ok(())
+}
+
+/// Takes one call out of the shared budget, if there's any left.
+fn take_spare_call(spare_calls: &AtomicUsize) -> bool {
+    spare_calls
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |left| {
+            left.checked_sub(1)
+        })
+        .is_ok()
+}
+
+#[tracing::instrument(skip(pool, user_timeline))]
+pub async fn store_timeline_page(
+    pool: &PgPool,
+    user_timeline: &TimelineResponse,
+) -> anyhow::Result<()> {
+    let includes = &user_timeline.includes;
+
+    // 1 store users (must go first)
+    for user in includes.users.iter() {
+        store_user(pool, user)
+            .await
+            .context("failed to store user when processing timeline")?;
     }
 
     // 2 store tweets (references users, so must go second)
-    if let Some(tweets) = user_timeline["data"].as_array() {
-        for tweet in tweets.iter() {
-            store_tweet(pool, &tweet, &user_timeline, "normal")
-                .await
-                .context("failed to store tweet when processing timeline")?;
-        }
+    for tweet in user_timeline.data.iter() {
+        store_tweet(pool, tweet, includes, "normal")
+            .await
+            .context("failed to store tweet when processing timeline")?;
     }
 
     // 3 store helper tweets (least important - goes last)
-    if let Some(helper_tweets) = user_timeline["includes"]["tweets"].as_array() {
-        for ht in helper_tweets.iter() {
-            store_tweet(pool, &ht, &user_timeline, "helper")
-                .await
-                .context("failed to store helper tweet when processing timeline")?;
-        }
+    for ht in includes.tweets.iter() {
+        store_tweet(pool, ht, includes, "helper")
+            .await
+            .context("failed to store helper tweet when processing timeline")?;
     }
     Ok(())
 }
 
-#[tracing::instrument(skip(config, pool, rt_original))]
-pub async fn process_rt_original_tweet(
-    config: &Settings,
+/// Refreshes likes / retweets / etc for up to TWEET_LOOKUP_BATCH_SIZE tweets with a single api call.
+/// This is what used to happen implicitly when we re-pulled the last 5 tweets of every timeline.
+#[allow(clippy::ptr_arg)] // has to be &Vec to fit loop_until_hit_rate_limit's signature
+#[tracing::instrument(skip(_config, pool, client, tweets))]
+pub async fn process_metrics_refresh(
+    _config: &Settings,
     pool: &PgPool,
-    rt_original: &Tweet,
+    client: &TwitterClient,
+    tweets: &Vec<Tweet>,
 ) -> anyhow::Result<()> {
-    // get the original retweet, retrying 2 times (5s and 25s)
+    let tweet_ids = tweets
+        .iter()
+        .map(|t| t.tweet_id.clone())
+        .collect::<Vec<String>>();
     let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
         .factor(RETRY_FACTOR)
         .take(RETRY_COUNT_NORMAL);
-    let (tweet_body, _) = Retry::spawn(retry_strategy, || async {
-        get_single_tweet(config, &rt_original.tweet_id).await
+    let (tweets_body, _) = Retry::spawn(retry_strategy, || async {
+        get_tweets_batch(client, &tweet_ids).await
     })
     .await
     .context(format!(
-        "failed to fetch rt_original tweet after {} retries",
+        "failed to fetch tweets batch for metrics refresh after {} retries",
         RETRY_COUNT_NORMAL
     ))?;
 
+    for tweet in tweets_body.data.iter() {
+        let tweet_metrics = extract_tweet_metrics(tweet);
+        update_tweet_metrics(pool, &tweet.id, &tweet_metrics)
+            .await
+            .context("failed to update tweet metrics")?;
+        store_tweet_metric_snapshot(pool, &tweet.id, &tweet_metrics)
+            .await
+            .context("failed to store tweet metric snapshot")?;
+    }
+    Ok(())
+}
+
+/// Backfills up to TWEET_LOOKUP_BATCH_SIZE rt_originals with a single api call.
+/// A tweet that fails to store is logged and skipped, so that it doesn't take down the rest of the batch.
+#[allow(clippy::ptr_arg)] // has to be &Vec to fit loop_until_hit_rate_limit's signature
+#[tracing::instrument(skip(_config, pool, client, rt_originals))]
+pub async fn process_rt_original_tweets(
+    _config: &Settings,
+    pool: &PgPool,
+    client: &TwitterClient,
+    rt_originals: &Vec<Tweet>,
+) -> anyhow::Result<()> {
+    // get the original retweets, retrying 2 times (5s and 25s)
+    let tweet_ids = rt_originals
+        .iter()
+        .map(|t| t.tweet_id.clone())
+        .collect::<Vec<String>>();
+    let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+        .factor(RETRY_FACTOR)
+        .take(RETRY_COUNT_NORMAL);
+    let (tweets_body, _) = Retry::spawn(retry_strategy, || async {
+        get_tweets_batch(client, &tweet_ids).await
+    })
+    .await
+    .context(format!(
+        "failed to fetch rt_original tweets batch after {} retries",
+        RETRY_COUNT_NORMAL
+    ))?;
+
+    for tweet in tweets_body.data.iter() {
+        // split the batch's includes back to the individual tweet
+        let includes = tweets_body.includes.for_tweet(tweet);
+        process_rt_original_tweet(pool, tweet, &includes)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(
+                    ">>>E: Failed to backfill rt_original tweet {}. Full error: {}",
+                    tweet.id,
+                    e,
+                );
+            });
+    }
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool, tweet, includes))]
+pub async fn process_rt_original_tweet(
+    pool: &PgPool,
+    tweet: &TweetObject,
+    includes: &Includes,
+) -> anyhow::Result<()> {
     // 1 save its media
-    handle_media_for_tweet(pool, &tweet_body["data"], &tweet_body)
+    handle_media_for_tweet(pool, tweet, includes)
         .await
         .context("failed to handle media for rt_original tweet")?;
     // 2 save its helper tweets
     // 2.1 first save users
-    if let Some(users) = &tweet_body["includes"]["users"].as_array() {
-        for user in users.iter() {
-            store_user(pool, &user)
-                .await
-                .context("failed to store user when processing rt_original tweet")?;
-        }
+    for user in includes.users.iter() {
+        store_user(pool, user)
+            .await
+            .context("failed to store user when processing rt_original tweet")?;
     }
     // 2.2 then actual helper tweets
-    if let Some(helper_tweets) = tweet_body["includes"]["tweets"].as_array() {
-        for ht in helper_tweets.iter() {
-            store_tweet(pool, &ht, &tweet_body, "helper")
-                .await
-                .context("failed to store helper tweet when processing rt_original tweet")?;
-        }
+    for ht in includes.tweets.iter() {
+        store_tweet(pool, ht, includes, "helper")
+            .await
+            .context("failed to store helper tweet when processing rt_original tweet")?;
     }
     Ok(())
 }
 
-#[tracing::instrument(skip(config, pool, helper))]
-pub async fn process_helper_tweet(
-    config: &Settings,
+/// Backfills media for up to TWEET_LOOKUP_BATCH_SIZE helper tweets with a single api call.
+#[allow(clippy::ptr_arg)] // has to be &Vec to fit loop_until_hit_rate_limit's signature
+#[tracing::instrument(skip(_config, pool, client, helpers))]
+pub async fn process_helper_tweets(
+    _config: &Settings,
     pool: &PgPool,
-    helper: &Tweet,
+    client: &TwitterClient,
+    helpers: &Vec<Tweet>,
 ) -> anyhow::Result<()> {
-    // get the helper retweet, retrying 2 times (5s and 25s)
+    // get the helper tweets, retrying 2 times (5s and 25s)
+    let tweet_ids = helpers
+        .iter()
+        .map(|t| t.tweet_id.clone())
+        .collect::<Vec<String>>();
     let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
         .factor(RETRY_FACTOR)
         .take(RETRY_COUNT_NORMAL);
-    let (tweet_body, _) = Retry::spawn(retry_strategy, || async {
-        get_single_tweet(config, &helper.tweet_id).await
+    let (tweets_body, _) = Retry::spawn(retry_strategy, || async {
+        get_tweets_batch(client, &tweet_ids).await
     })
     .await
     .context(format!(
-        "failed to fetch helper tweet after {} retries",
+        "failed to fetch helper tweets batch after {} retries",
         RETRY_COUNT_NORMAL
     ))?;
 
-    // save its media
-    handle_media_for_tweet(pool, &tweet_body["data"], &tweet_body)
-        .await
-        .context("failed to handle media for helper tweet")?;
+    // save their media
+    for tweet in tweets_body.data.iter() {
+        let includes = tweets_body.includes.for_tweet(tweet);
+        handle_media_for_tweet(pool, tweet, &includes)
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(
+                    ">>>E: Failed to handle media for helper tweet {}. Full error: {}",
+                    tweet.id,
+                    e,
+                );
+            });
+    }
+    Ok(())
+}
+
+/// No retries on the fetch itself - a site that's down or slow now most likely will be in 5s too,
+/// and we'd rather not hold up the scheduler for it. Transient failures (timeouts, 5xx, 429) get picked up
+/// again after LINK_PREVIEW_RETRY_HOURS, up to LINK_PREVIEW_MAX_ATTEMPTS in total - anything else is final.
+#[tracing::instrument(skip(pool, client))]
+pub async fn process_link_preview(
+    pool: &PgPool,
+    client: &LinkPreviewClient,
+    url: &str,
+) -> anyhow::Result<()> {
+    match client.unfurl(url).await {
+        Ok(unfurled) => store_link_preview(pool, url, &unfurled.final_url, &unfurled.og)
+            .await
+            .context("failed to store link preview")?,
+        Err(e) => {
+            tracing::info!(">>>I: failed to unfurl {}: {}", url, e);
+            let retry_after = if is_transient_http_error(&e) {
+                Some(Utc::now() + Duration::hours(LINK_PREVIEW_RETRY_HOURS))
+            } else {
+                None
+            };
+            store_failed_link_preview(
+                pool,
+                url,
+                &e.to_string(),
+                retry_after,
+                LINK_PREVIEW_MAX_ATTEMPTS,
+            )
+            .await
+            .context("failed to store failed link preview")?
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,198 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::twitter::routes::serve::TweetParams;
+use crate::twitter::scrapers::responses::TweetObject;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntityKind {
+    Cashtag,
+    Hashtag,
+    Mention,
+    Url,
+}
+
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+pub struct TrendingEntity {
+    pub kind: String,
+    pub value: String,
+    pub tweet_count: i64,
+    pub popularity_count: i64, // summed over those tweets
+}
+
+/// "The tweet must have all of these" - goes into the page queries as two parallel arrays.
+#[derive(Debug, Default)]
+pub struct EntityFilter {
+    pub kinds: Vec<String>,
+    pub values: Vec<String>,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl fmt::Display for EntityKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EntityKind::Cashtag => write!(f, "cashtag"),
+            EntityKind::Hashtag => write!(f, "hashtag"),
+            EntityKind::Mention => write!(f, "mention"),
+            EntityKind::Url => write!(f, "url"),
+        }
+    }
+}
+
+impl EntityKind {
+    /// So that $sol, $SOL and SOL all end up as the same entity - both when storing and filtering.
+    pub fn normalize(&self, value: &str) -> String {
+        let value = value.trim();
+        match self {
+            EntityKind::Cashtag => value.trim_start_matches('$').to_uppercase(),
+            EntityKind::Hashtag => value.trim_start_matches('#').to_lowercase(),
+            EntityKind::Mention => value.trim_start_matches('@').to_lowercase(),
+            EntityKind::Url => value.to_string(),
+        }
+    }
+}
+
+impl EntityFilter {
+    pub fn from_params(params: &TweetParams) -> Self {
+        let mut filter = EntityFilter::default();
+        let requested = [
+            (EntityKind::Cashtag, &params.cashtag),
+            (EntityKind::Hashtag, &params.hashtag),
+            (EntityKind::Mention, &params.mention),
+        ];
+        for (kind, value) in requested.iter() {
+            if let Some(value) = value {
+                filter.kinds.push(kind.to_string());
+                filter.values.push(kind.normalize(value));
+            }
+        }
+        filter
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Normalized and deduped. Links back to twitter itself (attached media, quoted tweets) are left out -
+/// they're not what the tweet links to.
+pub fn extract_entities(tweet: &TweetObject) -> Vec<(EntityKind, String)> {
+    let entities = match tweet.entities {
+        Some(ref entities) => entities,
+        None => return vec![],
+    };
+
+    let mut extracted = vec![];
+    for cashtag in entities.cashtags.iter() {
+        extracted.push((
+            EntityKind::Cashtag,
+            EntityKind::Cashtag.normalize(&cashtag.tag),
+        ));
+    }
+    for hashtag in entities.hashtags.iter() {
+        extracted.push((
+            EntityKind::Hashtag,
+            EntityKind::Hashtag.normalize(&hashtag.tag),
+        ));
+    }
+    for mention in entities.mentions.iter() {
+        extracted.push((
+            EntityKind::Mention,
+            EntityKind::Mention.normalize(&mention.username),
+        ));
+    }
+    for url in entities.urls.iter() {
+        let expanded = url.expanded_url.as_ref().unwrap_or(&url.url);
+        if expanded.starts_with("https://twitter.com/") {
+            continue;
+        }
+        extracted.push((EntityKind::Url, EntityKind::Url.normalize(expanded)));
+    }
+
+    extracted.sort();
+    extracted.dedup();
+    extracted
+}
+
+/// One query per entity: creates the entity if it's new, then links it to the tweet.
+/// A tweet's text never changes, so re-storing it just finds everything already linked.
+#[tracing::instrument(skip(pool, tweet), level = "debug")]
+pub async fn store_tweet_entities(
+    pool: &PgPool,
+    tweet_id: &str,
+    tweet: &TweetObject,
+) -> Result<(), sqlx::error::Error> {
+    for (kind, value) in extract_entities(tweet).iter() {
+        sqlx::query!(
+            r#"
+            WITH entity AS (
+                INSERT INTO entities (id, created_at, kind, value)
+                VALUES ($1, $2, $3, $4)
+
+                ON CONFLICT (kind, value)
+                -- no-op update, so that RETURNING gives us the id of an existing entity too
+                DO UPDATE SET
+                    kind = EXCLUDED.kind
+                RETURNING id
+            )
+
+            INSERT INTO tweet_entities (tweet_id, entity_id)
+            SELECT tweets.id, entity.id
+            FROM tweets, entity
+            WHERE tweets.tweet_id = $5
+
+            ON CONFLICT DO NOTHING
+            "#,
+            Uuid::new_v4(),
+            Utc::now(),
+            kind.to_string(),
+            value,
+            tweet_id,
+        )
+        .execute(pool)
+        .await?;
+    }
+    Ok(())
+}
+
+/// Most talked about first - by number of tweets, then by how popular those tweets were.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_trending_entities(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    kind: Option<String>,
+    limit: i64,
+) -> Result<Vec<TrendingEntity>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        TrendingEntity,
+        r#"
+        SELECT
+            e.kind,
+            e.value,
+            COUNT(t.id) AS "tweet_count!",
+            COALESCE(SUM(t.popularity_count), 0)::BIGINT AS "popularity_count!"
+        FROM entities e
+        JOIN tweet_entities te ON te.entity_id = e.id
+        JOIN tweets t ON t.id = te.tweet_id
+        WHERE
+            t.tweet_class != 'helper'
+            AND t.tweet_created_at >= $1
+            AND ($2::TEXT IS NULL OR e.kind = $2)
+        GROUP BY e.id
+        ORDER BY 3 DESC, 4 DESC, e.value
+        LIMIT $3;
+        "#,
+        since,
+        kind,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
@@ -0,0 +1,196 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::twitter::scrapers::unfurl::OpenGraph;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug)]
+pub enum PreviewStatus {
+    Ok,
+    Failed,
+}
+
+/// What goes out with FullTweet - only successful fetches are ever served.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LinkPreview {
+    pub url: String,
+    pub final_url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// A preview together with the tweet it belongs to - caller groups them by tweet_id.
+#[derive(Debug)]
+pub struct TweetLinkPreview {
+    pub tweet_id: Uuid,
+    pub url: String,
+    pub final_url: Option<String>,
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl fmt::Display for PreviewStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreviewStatus::Ok => write!(f, "ok"),
+            PreviewStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+impl From<TweetLinkPreview> for LinkPreview {
+    fn from(p: TweetLinkPreview) -> Self {
+        LinkPreview {
+            url: p.url,
+            final_url: p.final_url,
+            title: p.title,
+            description: p.description,
+            image_url: p.image_url,
+            site_name: p.site_name,
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Links shared in tweets newer than `since` that we haven't tried to unfurl yet,
+/// or whose last attempt failed for a transient reason and is now due for a retry.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_urls_to_unfurl(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<String>, sqlx::error::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT DISTINCT e.value AS "url!"
+        FROM entities e
+        INNER JOIN tweet_entities te ON te.entity_id = e.id
+        INNER JOIN tweets t ON t.id = te.tweet_id
+        WHERE e.kind = 'url'
+            AND t.tweet_created_at >= $1
+            AND NOT EXISTS (
+                SELECT 1 FROM link_previews lp
+                WHERE lp.url = e.value
+                    AND (lp.retry_after IS NULL OR lp.retry_after > $3)
+            )
+        LIMIT $2
+        "#,
+        since,
+        limit,
+        Utc::now(),
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res.into_iter().map(|r| r.url).collect())
+}
+
+#[tracing::instrument(skip(pool, og), level = "debug")]
+pub async fn store_link_preview(
+    pool: &PgPool,
+    url: &str,
+    final_url: &str,
+    og: &OpenGraph,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO link_previews
+        (id, created_at, url, final_url, status, title, description, image_url, site_name)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+
+        ON CONFLICT (url)
+        DO UPDATE SET
+            final_url = $4,
+            status = $5,
+            error = NULL,
+            retry_after = NULL,
+            title = $6,
+            description = $7,
+            image_url = $8,
+            site_name = $9
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        url,
+        final_url,
+        PreviewStatus::Ok.to_string(),
+        og.title,
+        og.description,
+        og.image_url,
+        og.site_name,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Remembers the failure, so that a dead link doesn't get fetched again every run.
+/// `retry_after` is set for transient failures only, and dropped once the link has used up its attempts.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn store_failed_link_preview(
+    pool: &PgPool,
+    url: &str,
+    error: &str,
+    retry_after: Option<DateTime<Utc>>,
+    max_attempts: i32,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO link_previews
+        (id, created_at, url, status, error, attempts, retry_after)
+        VALUES ($1, $2, $3, $4, $5, 1, $6)
+
+        ON CONFLICT (url)
+        DO UPDATE SET
+            status = $4,
+            error = $5,
+            attempts = link_previews.attempts + 1,
+            retry_after = CASE WHEN link_previews.attempts + 1 >= $7 THEN NULL ELSE $6 END
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        url,
+        PreviewStatus::Failed.to_string(),
+        error,
+        retry_after,
+        max_attempts,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Previews for a whole batch of tweets in one go - caller groups them by tweet_id.
+#[tracing::instrument(skip(pool, tweet_ids), level = "debug")]
+pub async fn fetch_link_previews_for_tweets(
+    pool: &PgPool,
+    tweet_ids: &[Uuid],
+) -> Result<Vec<TweetLinkPreview>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        TweetLinkPreview,
+        r#"
+        SELECT te.tweet_id AS "tweet_id!", lp.url AS "url!", lp.final_url, lp.title, lp.description, lp.image_url, lp.site_name
+        FROM tweet_entities te
+        INNER JOIN entities e ON e.id = te.entity_id
+        INNER JOIN link_previews lp ON lp.url = e.value
+        WHERE te.tweet_id = ANY($1)
+            AND e.kind = 'url'
+            AND lp.status = 'ok'
+        "#,
+        tweet_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
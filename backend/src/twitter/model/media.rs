@@ -1,14 +1,15 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::{json, Value};
+use sqlx::types::Json;
 use sqlx::PgPool;
 use uuid::Uuid;
 
 use crate::twitter::model::tweet::{fetch_tweet, Tweet};
+use crate::twitter::scrapers::responses::{Includes, MediaObject, TweetObject, VariantObject};
 
 // ----------------------------------------------------------------------------- structs/enums
 
-#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
 pub struct Media {
     pub id: Uuid,
     pub created_at: DateTime<Utc>,
@@ -16,6 +17,55 @@ pub struct Media {
     pub media_type: Option<String>,
     pub display_url: Option<String>,
     pub tweet_id: Uuid,
+    pub alt_text: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration_ms: Option<i32>,
+    // set once mirrored into our own blob store - see point_at_mirror
+    pub mirror_key: Option<String>,
+    pub thumbnail_key: Option<String>,
+    pub mirrored_at: Option<DateTime<Utc>>,
+    pub mirror_error: Option<String>,
+    // not a column - always NULL from the db, filled in by point_at_mirror once there's a thumbnail
+    pub thumbnail_url: Option<String>,
+    // not a column - aggregated from media_variants, highest bitrate first. Empty for photos
+    pub variants: Json<Vec<MediaVariant>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MediaVariant {
+    pub content_type: String,
+    pub bit_rate: Option<i32>,
+    pub url: String,
+}
+
+/// What the mirroring job needs to know about a not-yet-mirrored media.
+#[derive(Debug)]
+pub struct MediaToMirror {
+    pub id: Uuid,
+    pub media_key: String,
+    pub display_url: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl Media {
+    /// Swaps twitter's url for our mirror's, so that everything downstream (frontend, feeds, digests, webhooks)
+    /// keeps using display_url and stops hot-linking twitter. Until mirrored, twitter's url stays.
+    /// Also exposes our thumbnail as thumbnail_url - twitter has no equivalent, so it stays None until mirrored.
+    pub fn point_at_mirror(&mut self, api_url: &str) {
+        if let Some(key) = &self.mirror_key {
+            self.display_url = Some(format!("{}/media/{}", api_url, key));
+        }
+        if let Some(key) = &self.thumbnail_key {
+            self.thumbnail_url = Some(format!("{}/media/{}", api_url, key));
+        }
+    }
+
+    /// The smaller of the two, for when the image is shown inline (cards, feed entries, emails).
+    pub fn preview_url(&self) -> Option<&String> {
+        self.thumbnail_url.as_ref().or(self.display_url.as_ref())
+    }
 }
 
 // ----------------------------------------------------------------------------- fn
@@ -25,7 +75,15 @@ pub async fn fetch_media(pool: &PgPool, media_key: &str) -> Result<Media, sqlx::
     let res = sqlx::query_as!(
         Media,
         r#"
-        SELECT * FROM media WHERE media_key = $1
+        SELECT m.*, NULL::TEXT AS thumbnail_url, COALESCE(
+            json_agg(json_build_object('content_type', v.content_type, 'bit_rate', v.bit_rate, 'url', v.url)
+                ORDER BY v.bit_rate DESC NULLS LAST) FILTER (WHERE v.id IS NOT NULL),
+            '[]'
+        ) AS "variants!: Json<Vec<MediaVariant>>"
+        FROM media m
+        LEFT JOIN media_variants v ON v.media_id = m.id
+        WHERE m.media_key = $1
+        GROUP BY m.id
         "#,
         media_key,
     )
@@ -43,7 +101,15 @@ pub async fn fetch_all_media_for_tweet(
     let res = sqlx::query_as!(
         Media,
         r#"
-        SELECT * FROM media WHERE tweet_id = $1
+        SELECT m.*, NULL::TEXT AS thumbnail_url, COALESCE(
+            json_agg(json_build_object('content_type', v.content_type, 'bit_rate', v.bit_rate, 'url', v.url)
+                ORDER BY v.bit_rate DESC NULLS LAST) FILTER (WHERE v.id IS NOT NULL),
+            '[]'
+        ) AS "variants!: Json<Vec<MediaVariant>>"
+        FROM media m
+        LEFT JOIN media_variants v ON v.media_id = m.id
+        WHERE m.tweet_id = $1
+        GROUP BY m.id
         "#,
         tweet_id,
     )
@@ -52,6 +118,32 @@ pub async fn fetch_all_media_for_tweet(
     Ok(res)
 }
 
+/// media for a whole batch of tweets in one go - caller groups it by tweet_id
+#[tracing::instrument(skip(pool, tweet_ids), level = "debug")]
+pub async fn fetch_all_media_for_tweets(
+    pool: &PgPool,
+    tweet_ids: &[Uuid],
+) -> Result<Vec<Media>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Media,
+        r#"
+        SELECT m.*, NULL::TEXT AS thumbnail_url, COALESCE(
+            json_agg(json_build_object('content_type', v.content_type, 'bit_rate', v.bit_rate, 'url', v.url)
+                ORDER BY v.bit_rate DESC NULLS LAST) FILTER (WHERE v.id IS NOT NULL),
+            '[]'
+        ) AS "variants!: Json<Vec<MediaVariant>>"
+        FROM media m
+        LEFT JOIN media_variants v ON v.media_id = m.id
+        WHERE m.tweet_id = ANY($1)
+        GROUP BY m.id
+        "#,
+        tweet_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
 /// Twitter v2 api clarification:
 ///     - media_keys = array of keys only attached to individual tweet
 ///     - media_objects = array of objects {key, type, url} for the ENTIRE returned batch of tweets
@@ -60,68 +152,48 @@ pub async fn fetch_all_media_for_tweet(
 /// Model clarification: 2 cases are possible:
 ///     1) for "normal" tweets - media objects will be available if media_keys are
 ///     2) for "rt_original" tweets - only keys will be present. So we store just the keys and will fetch objects later.
-#[tracing::instrument(skip(pool, tweet, body), level = "debug")]
+#[tracing::instrument(skip(pool, tweet, includes), level = "debug")]
 pub async fn handle_media_for_tweet(
     pool: &PgPool,
-    tweet: &Value,
-    body: &Value,
+    tweet: &TweetObject,
+    includes: &Includes,
 ) -> anyhow::Result<()> {
-    // media_keys may not be present for a tweet if has no media content
-    if let Some(media_keys) = tweet["attachments"]["media_keys"].as_array() {
+    // attachments may not be present for a tweet if has no media content
+    if let Some(attachments) = &tweet.attachments {
         // media_objects may not be present for the batch if no tweets have any media
-        if let Some(media_objects) = body["includes"]["media"].as_array() {
-            let final_media_objects = media_keys
+        if !includes.media.is_empty() {
+            let final_media_objects = attachments
+                .media_keys
                 .iter()
-                .map(|mk| {
-                    //will be 0 if not found, 1 if found, hence the option
-                    let relevant_objects = media_objects
-                        .iter()
-                        .filter(|&mo| {
-                            // rust doesn't allow ? inside closures: https://mbuffett.com/posts/rust-less-error-handling/
-                            // I know that twitter's api always provides media_key so I'm just going to use expect here
-                            mo["media_key"].as_str().expect("no media_key [impossible]")
-                                == mk.as_str().expect("no media_key [impossible]")
-                        })
-                        .collect::<Vec<&Value>>();
-                    let relevant_object = relevant_objects.get(0);
-                    prep_final_media_obj(relevant_object, mk)
-                })
-                .collect::<Vec<Value>>();
-
-            let tweet_id = tweet["id"].as_str().ok_or(anyhow::anyhow!("no tweet_id"))?;
-            store_all_media(&pool, tweet_id, final_media_objects).await?;
+                .map(|mk| prep_final_media_obj(mk, &includes.media))
+                .collect::<Vec<(&str, Option<&MediaObject>)>>();
+
+            store_all_media(&pool, &tweet.id, final_media_objects).await?;
         }
     }
     Ok(())
 }
 
-#[tracing::instrument(skip(relevant_object, mk), level = "debug")]
-pub fn prep_final_media_obj(relevant_object: Option<&&Value>, mk: &Value) -> Value {
-    // rust doesn't allow ? inside closures: https://mbuffett.com/posts/rust-less-error-handling/
-    // I know that twitter's api always provides media_key so I'm just going to use expect here
-    match relevant_object {
-        Some(relevant_obj) => json!({
-            "media_key": mk.as_str().expect("media_key not present [impossible]"),
-            "type": relevant_obj["type"].as_str(),
-            "url": relevant_obj["url"].as_str(),
-            "preview_image_url": relevant_obj["preview_image_url"].as_str(),
-        }),
-        None => json!({
-            "media_key": mk.as_str().expect("media_key not present [impossible]"),
-        }),
-    }
+/// pairs a media key with its media object - or with None if the object wasn't returned
+#[tracing::instrument(skip(media_objects), level = "debug")]
+pub fn prep_final_media_obj<'a>(
+    mk: &'a str,
+    media_objects: &'a [MediaObject],
+) -> (&'a str, Option<&'a MediaObject>) {
+    let relevant_object = media_objects.iter().find(|mo| mo.media_key == mk);
+    (mk, relevant_object)
 }
 
 #[tracing::instrument(skip(pool, media_objects), level = "debug")]
 pub async fn store_all_media(
     pool: &PgPool,
     tweet_id: &str,
-    media_objects: Vec<Value>,
+    media_objects: Vec<(&str, Option<&MediaObject>)>,
 ) -> Result<(), sqlx::error::Error> {
     let parent_tweet = fetch_tweet(&pool, tweet_id).await?;
 
-    for mo in media_objects.iter() {
-        store_media(&pool, &parent_tweet, mo).await?;
+    for (media_key, media_object) in media_objects.into_iter() {
+        store_media(&pool, &parent_tweet, media_key, media_object).await?;
     }
     Ok(())
 }
@@ -130,27 +202,73 @@ pub async fn store_all_media(
 pub async fn store_media(
     pool: &PgPool,
     parent_tweet: &Tweet,
-    media_object: &Value,
+    media_key: &str,
+    media_object: Option<&MediaObject>,
 ) -> Result<(), sqlx::error::Error> {
-    let display_url = build_display_url(&media_object);
-    sqlx::query!(
+    let media_type = media_object.map(|mo| mo.media_type.as_str());
+    let display_url = media_object.and_then(build_display_url);
+    let res = sqlx::query!(
         r#"
         INSERT INTO media
-            (id, created_at, media_key, media_type, display_url, tweet_id)
+            (id, created_at, media_key, media_type, display_url, tweet_id, alt_text, width, height, duration_ms)
         VALUES 
-            ($1, $2, $3, $4, $5, $6)
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
             
         ON CONFLICT (media_key)
         DO UPDATE SET
             media_type = $4,
-            display_url = $5;
+            display_url = $5,
+            alt_text = $7,
+            width = $8,
+            height = $9,
+            duration_ms = $10
+        RETURNING id;
         "#,
         Uuid::new_v4(),
         Utc::now(),
-        media_object["media_key"].as_str(),
-        media_object["type"].as_str(),
+        media_key,
+        media_type,
         display_url,
         parent_tweet.id,
+        media_object.and_then(|mo| mo.alt_text.as_deref()),
+        media_object.and_then(|mo| mo.width),
+        media_object.and_then(|mo| mo.height),
+        media_object.and_then(|mo| mo.duration_ms),
+    )
+    .fetch_one(pool)
+    .await?;
+
+    if let Some(media_object) = media_object {
+        for variant in media_object.variants.iter() {
+            store_media_variant(pool, res.id, variant).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Variant urls never change for a given media, so there's nothing to update on conflict.
+#[tracing::instrument(skip(pool, variant), level = "debug")]
+pub async fn store_media_variant(
+    pool: &PgPool,
+    media_id: Uuid,
+    variant: &VariantObject,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO media_variants
+            (id, created_at, content_type, bit_rate, url, media_id)
+        VALUES
+            ($1, $2, $3, $4, $5, $6)
+
+        ON CONFLICT (media_id, url)
+        DO NOTHING
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        variant.content_type,
+        variant.bit_rate,
+        variant.url,
+        media_id,
     )
     .execute(pool)
     .await?;
@@ -158,8 +276,78 @@ pub async fn store_media(
 }
 
 #[tracing::instrument(skip(media_object), level = "debug")]
-pub fn build_display_url(media_object: &Value) -> Option<&str> {
-    let photo_url = media_object["url"].as_str();
-    let preview_url = media_object["preview_image_url"].as_str();
+pub fn build_display_url(media_object: &MediaObject) -> Option<&str> {
+    let photo_url = media_object.url.as_deref();
+    let preview_url = media_object.preview_image_url.as_deref();
     photo_url.or(preview_url)
 }
+
+/// Media of tweets newer than `since` that hasn't been mirrored (or failed to) yet, most popular first.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_media_to_mirror(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    limit: i64,
+) -> Result<Vec<MediaToMirror>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        MediaToMirror,
+        r#"
+        SELECT m.id, m.media_key, m.display_url AS "display_url!"
+        FROM media m
+        INNER JOIN tweets t ON t.id = m.tweet_id
+        WHERE m.display_url IS NOT NULL
+            AND m.mirror_key IS NULL
+            AND m.mirror_error IS NULL
+            AND t.tweet_created_at >= $1
+        ORDER BY t.popularity_count DESC
+        LIMIT $2
+        "#,
+        since,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn store_media_mirror(
+    pool: &PgPool,
+    media_id: Uuid,
+    mirror_key: &str,
+    thumbnail_key: &str,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE media
+        SET mirror_key = $2, thumbnail_key = $3, mirrored_at = $4
+        WHERE id = $1
+        "#,
+        media_id,
+        mirror_key,
+        thumbnail_key,
+        Utc::now(),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Remembers the failure, so that a dead image doesn't get downloaded again every run.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn store_failed_media_mirror(
+    pool: &PgPool,
+    media_id: Uuid,
+    error: &str,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE media SET mirror_error = $2 WHERE id = $1
+        "#,
+        media_id,
+        error,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -1,3 +1,10 @@
+pub mod entity;
+pub mod link_preview;
 pub mod media;
+pub mod pending;
+pub mod search;
+pub mod seen;
+pub mod snapshot;
+pub mod thread;
 pub mod tweet;
 pub mod user;
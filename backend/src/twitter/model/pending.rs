@@ -0,0 +1,108 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+pub struct PendingWork {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub job_kind: String,
+    pub object_id: String,
+}
+
+/// What a pending_work row is waiting on - stored in job_kind as the Display string.
+/// The table's migration only lists the first three, ancestor_tweet came later.
+/// - user_timeline: object_id is a twitter user id, whose timeline still needs pulling
+/// - core_tweet: object_id is a tweet_id, whose media + helper tweets still need backfilling
+/// - helper_tweet: object_id is a tweet_id, whose media still needs backfilling
+/// - ancestor_tweet: object_id is a tweet_id, whose parent (one more level up the chain) still needs pulling
+#[derive(Debug)]
+pub enum JobKind {
+    UserTimeline,
+    CoreTweet,
+    HelperTweet,
+    AncestorTweet,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl fmt::Display for JobKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobKind::UserTimeline => write!(f, "user_timeline"),
+            JobKind::CoreTweet => write!(f, "core_tweet"),
+            JobKind::HelperTweet => write!(f, "helper_tweet"),
+            JobKind::AncestorTweet => write!(f, "ancestor_tweet"),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// oldest first - those have been waiting the longest
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_pending_work(
+    pool: &PgPool,
+    job_kind: &JobKind,
+) -> Result<Vec<PendingWork>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        PendingWork,
+        r#"
+        SELECT * FROM pending_work WHERE job_kind = $1 ORDER BY created_at
+        "#,
+        job_kind.to_string(),
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Swaps out whatever was pending for this job kind with the new leftovers. Objects that were already
+/// pending keep their created_at, so they don't lose their place in the queue by being deferred again.
+/// Done in a transaction so that a crash halfway through doesn't lose the queue.
+#[tracing::instrument(skip(pool, object_ids), level = "debug")]
+pub async fn replace_pending_work(
+    pool: &PgPool,
+    job_kind: &JobKind,
+    object_ids: &[String],
+) -> Result<(), sqlx::error::Error> {
+    let mut tx = pool.begin().await?;
+
+    sqlx::query!(
+        r#"
+        DELETE FROM pending_work WHERE job_kind = $1 AND NOT (object_id = ANY($2))
+        "#,
+        job_kind.to_string(),
+        object_ids,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    for object_id in object_ids.iter() {
+        sqlx::query!(
+            r#"
+            INSERT INTO pending_work
+                (id, created_at, job_kind, object_id)
+            VALUES
+                ($1, $2, $3, $4)
+
+            ON CONFLICT (job_kind, object_id)
+            DO NOTHING;
+            "#,
+            Uuid::new_v4(),
+            Utc::now(),
+            job_kind.to_string(),
+            object_id,
+        )
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
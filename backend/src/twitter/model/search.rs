@@ -0,0 +1,99 @@
+use chrono::{DateTime, Utc};
+use sqlx::{FromRow, PgPool, Row};
+
+use crate::twitter::model::tweet::Tweet;
+use crate::twitter::routes::serve::{Cursor, CursorMetric};
+use crate::utils::constants::{SEARCH_POPULARITY_WEIGHT, TWEET_PAGE_SIZE};
+
+// ----------------------------------------------------------------------------- fn
+
+/// (Re)builds the tweet's search document from its stored text. Upsert, as store_tweet runs again for
+/// every tweet we re-pull.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn store_tweet_search_document(
+    pool: &PgPool,
+    tweet_id: &str,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tweet_search (tweet_id, document)
+        SELECT id, to_tsvector('english', tweet_text)
+        FROM tweets
+        WHERE tweet_id = $1
+
+        ON CONFLICT (tweet_id)
+        DO UPDATE SET
+            document = EXCLUDED.document
+        "#,
+        tweet_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Search score = text relevance, boosted by popularity:
+///     score = ts_rank_cd(document, query) * (1 + SEARCH_POPULARITY_WEIGHT * ln(1 + popularity_count))
+///
+/// - the query goes through websearch_to_tsquery, so "quoted phrases", -exclusions and OR all work
+/// - unlike the feed, every matching tweet counts - not just thread heads, as the match may well be further down
+/// - keyset pagination on (score, tweet_id), same as trending. The score only moves when metrics get
+///   refreshed, so the cursor stays valid between page requests
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_next_page_of_search_results(
+    pool: &PgPool,
+    query: &str,
+    since: DateTime<Utc>,
+    cursor: Option<&Cursor>,
+) -> anyhow::Result<Vec<(Tweet, f64)>> {
+    let (last_score, last_tweet_id) = match cursor {
+        Some(Cursor {
+            last_metric: CursorMetric::Score(last_score),
+            last_tweet_id,
+        }) => (*last_score, last_tweet_id.clone()),
+        Some(_) => anyhow::bail!("non-score cursor passed to search"),
+        None => (f64::INFINITY, String::new()),
+    };
+
+    let rows = sqlx::query(
+        r#"
+        WITH matches AS (
+            SELECT
+                tweets.*,
+                (
+                    ts_rank_cd(ts.document, q.query)
+                    * (1 + $2 * LN(1 + GREATEST(COALESCE(tweets.popularity_count, 0), 0)))
+                )::FLOAT8 AS search_score
+            FROM tweets
+            JOIN tweet_search ts ON ts.tweet_id = tweets.id
+            CROSS JOIN websearch_to_tsquery('english', $1) AS q(query)
+            WHERE
+                ts.document @@ q.query
+                AND tweets.tweet_class != 'helper'
+                AND tweets.tweet_created_at >= $3
+        )
+
+        SELECT *
+        FROM matches
+        WHERE (search_score, tweet_id) < ($4, $5)
+        ORDER BY search_score DESC, tweet_id DESC
+        LIMIT $6;
+        "#,
+    )
+    .bind(query)
+    .bind(SEARCH_POPULARITY_WEIGHT)
+    .bind(since)
+    .bind(last_score)
+    .bind(last_tweet_id)
+    .bind(TWEET_PAGE_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tweets = vec![];
+    for row in rows.iter() {
+        let tweet = Tweet::from_row(row)?;
+        let search_score: f64 = row.try_get("search_score")?;
+        tweets.push((tweet, search_score));
+    }
+    Ok(tweets)
+}
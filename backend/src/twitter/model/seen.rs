@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Hides tweets served to this reader since `since` - passed down into the page queries.
+#[derive(Debug)]
+pub struct UnseenFilter {
+    pub reader_token: String,
+    pub since: DateTime<Utc>,
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// One query for the whole page. Seeing a tweet again restarts its ttl.
+#[tracing::instrument(skip(pool, reader_token, tweet_ids), level = "debug")]
+pub async fn record_seen_tweets(
+    pool: &PgPool,
+    reader_token: &str,
+    tweet_ids: &[Uuid],
+    seen_at: DateTime<Utc>,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO seen_tweets (reader_token, tweet_id, seen_at)
+        SELECT $1, UNNEST($2::uuid[]), $3
+
+        ON CONFLICT (reader_token, tweet_id)
+        DO UPDATE SET
+            seen_at = $3
+        "#,
+        reader_token,
+        tweet_ids,
+        seen_at,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Returns how many rows were pruned.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn delete_seen_tweets_before(
+    pool: &PgPool,
+    before: DateTime<Utc>,
+) -> Result<u64, sqlx::error::Error> {
+    let res = sqlx::query!(
+        r#"
+        DELETE FROM seen_tweets WHERE seen_at < $1
+        "#,
+        before,
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected())
+}
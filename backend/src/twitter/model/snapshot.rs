@@ -0,0 +1,144 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::twitter::model::tweet::TweetMetrics;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+pub struct TweetMetricSnapshot {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub like_count: i64,
+    pub quote_count: i64,
+    pub reply_count: i64,
+    pub retweet_count: i64,
+    pub total_retweet_count: i64,
+    pub popularity_count: i64,
+    pub tweet_id: Uuid,
+}
+
+/// Engagement gained during a single time bucket (eg an hour), summed over all of a user's tweets.
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+pub struct MetricGrowth {
+    pub bucket: DateTime<Utc>,
+    pub like_gain: i64,
+    pub quote_gain: i64,
+    pub reply_gain: i64,
+    pub retweet_gain: i64,
+    pub total_retweet_gain: i64,
+    pub popularity_gain: i64,
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// takes twitter's tweet_id (not our uuid), so that it can be called straight after storing / updating a tweet
+#[tracing::instrument(skip(pool, tweet_metrics), level = "debug")]
+pub async fn store_tweet_metric_snapshot(
+    pool: &PgPool,
+    tweet_id: &str,
+    tweet_metrics: &TweetMetrics,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO tweet_metric_snapshots
+            (id, created_at,
+            like_count, quote_count, reply_count, retweet_count, total_retweet_count, popularity_count,
+            tweet_id)
+        SELECT
+            $1, $2, $3, $4, $5, $6, $7, $8, id
+        FROM tweets
+        WHERE tweet_id = $9;
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        tweet_metrics.like_count,
+        tweet_metrics.quote_count,
+        tweet_metrics.reply_count,
+        tweet_metrics.retweet_count,
+        tweet_metrics.total_retweet_count,
+        tweet_metrics.popularity_count,
+        tweet_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// oldest first
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_tweet_metric_history(
+    pool: &PgPool,
+    tweet_id: &str,
+) -> Result<Vec<TweetMetricSnapshot>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        TweetMetricSnapshot,
+        r#"
+        SELECT s.*
+        FROM tweet_metric_snapshots s
+        JOIN tweets t ON t.id = s.tweet_id
+        WHERE t.tweet_id = $1
+        ORDER BY s.created_at
+        "#,
+        tweet_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Growth of a user over time.
+///
+/// For every tweet we take the difference between consecutive snapshots (the first snapshot counts in full),
+/// then sum those differences per bucket across all of the user's tweets.
+/// - bucket is anything postgres' date_trunc understands - eg "hour", "day"
+/// - since only limits the output, deltas are still computed against older snapshots
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_user_metric_growth(
+    pool: &PgPool,
+    user_id: &str,
+    bucket: &str,
+    since: DateTime<Utc>,
+) -> Result<Vec<MetricGrowth>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        MetricGrowth,
+        r#"
+        WITH deltas AS (
+            SELECT
+                date_trunc($2, s.created_at) AS bucket,
+                s.like_count - LAG(s.like_count, 1, 0::BIGINT) OVER w AS like_gain,
+                s.quote_count - LAG(s.quote_count, 1, 0::BIGINT) OVER w AS quote_gain,
+                s.reply_count - LAG(s.reply_count, 1, 0::BIGINT) OVER w AS reply_gain,
+                s.retweet_count - LAG(s.retweet_count, 1, 0::BIGINT) OVER w AS retweet_gain,
+                s.total_retweet_count - LAG(s.total_retweet_count, 1, 0::BIGINT) OVER w AS total_retweet_gain,
+                s.popularity_count - LAG(s.popularity_count, 1, 0::BIGINT) OVER w AS popularity_gain
+            FROM tweet_metric_snapshots s
+            JOIN tweets t ON t.id = s.tweet_id
+            JOIN users u ON u.id = t.user_id
+            WHERE u.twitter_user_id = $1
+            WINDOW w AS (PARTITION BY s.tweet_id ORDER BY s.created_at)
+        )
+
+        SELECT
+            bucket AS "bucket!",
+            SUM(like_gain)::BIGINT AS "like_gain!",
+            SUM(quote_gain)::BIGINT AS "quote_gain!",
+            SUM(reply_gain)::BIGINT AS "reply_gain!",
+            SUM(retweet_gain)::BIGINT AS "retweet_gain!",
+            SUM(total_retweet_gain)::BIGINT AS "total_retweet_gain!",
+            SUM(popularity_gain)::BIGINT AS "popularity_gain!"
+        FROM deltas
+        WHERE bucket >= date_trunc($2, $3::timestamptz)
+        GROUP BY bucket
+        ORDER BY bucket
+        "#,
+        user_id,
+        bucket,
+        since,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
@@ -0,0 +1,130 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// A thread with its metrics summed over all of its tweets.
+/// The feed ranks a thread by its head tweet alone though - see fetch_next_page_of_tweets.
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
+pub struct Thread {
+    pub id: Uuid,
+    pub conversation_id: String,
+    pub head_tweet_id: Option<String>,
+    pub user_id: Uuid,
+    pub tweet_count: i64,
+    // combined metrics
+    pub like_count: i64,
+    pub quote_count: i64,
+    pub reply_count: i64,
+    pub retweet_count: i64,
+    pub total_retweet_count: i64,
+    pub popularity_count: i64,
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Called when we see an author reply to themselves. Safe to call for an existing thread.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn store_thread(
+    pool: &PgPool,
+    conversation_id: &str,
+    user_id: Uuid,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        INSERT INTO threads
+            (id, created_at, conversation_id, user_id)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT (conversation_id, user_id) DO NOTHING;
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        conversation_id,
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Pulls all of the author's tweets in the conversation into their thread (if there is one) and re-picks its head.
+/// Timelines come newest first, so the thread often only gets created after some of its tweets are already
+/// stored - which is why this looks at the whole conversation, not just the tweet that was just stored.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn link_thread_tweets(
+    pool: &PgPool,
+    conversation_id: &str,
+    user_id: Uuid,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE tweets
+        SET thread_id = threads.id
+        FROM threads
+        WHERE
+            threads.conversation_id = $1
+            AND threads.user_id = $2
+            AND tweets.conversation_id = $1
+            AND tweets.user_id = $2;
+        "#,
+        conversation_id,
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+        UPDATE threads
+        SET head_tweet_id = (
+            SELECT tweet_id
+            FROM tweets
+            WHERE thread_id = threads.id
+                -- helpers never make it into the feed, so a helper head would hide the whole thread
+                AND tweet_class != 'helper'
+            ORDER BY tweet_created_at, tweet_id
+            LIMIT 1
+        )
+        WHERE
+            conversation_id = $1
+            AND user_id = $2;
+        "#,
+        conversation_id,
+        user_id,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// silently leaves out ids that don't exist
+#[tracing::instrument(skip(pool, ids), level = "debug")]
+pub async fn fetch_threads(pool: &PgPool, ids: &[Uuid]) -> Result<Vec<Thread>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Thread,
+        r#"
+        SELECT
+            th.id,
+            th.conversation_id,
+            th.head_tweet_id,
+            th.user_id,
+            COUNT(t.id) AS "tweet_count!",
+            COALESCE(SUM(t.like_count), 0)::BIGINT AS "like_count!",
+            COALESCE(SUM(t.quote_count), 0)::BIGINT AS "quote_count!",
+            COALESCE(SUM(t.reply_count), 0)::BIGINT AS "reply_count!",
+            COALESCE(SUM(t.retweet_count), 0)::BIGINT AS "retweet_count!",
+            COALESCE(SUM(t.total_retweet_count), 0)::BIGINT AS "total_retweet_count!",
+            COALESCE(SUM(t.popularity_count), 0)::BIGINT AS "popularity_count!"
+        FROM threads th
+        JOIN tweets t ON t.thread_id = th.id
+        WHERE th.id = ANY($1)
+        GROUP BY th.id;
+        "#,
+        ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
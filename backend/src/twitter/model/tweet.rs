@@ -2,17 +2,23 @@ use actix_web::web;
 use async_recursion::async_recursion;
 use chrono::{DateTime, Duration, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use sqlx::types::Uuid;
-use sqlx::PgPool;
+use sqlx::{FromRow, PgPool, Row};
 
+use crate::twitter::model::entity::{store_tweet_entities, EntityFilter};
 use crate::twitter::model::media::handle_media_for_tweet;
+use crate::twitter::model::search::store_tweet_search_document;
+use crate::twitter::model::seen::UnseenFilter;
+use crate::twitter::model::snapshot::store_tweet_metric_snapshot;
+use crate::twitter::model::thread::{link_thread_tweets, store_thread};
 use crate::twitter::model::user::fetch_user;
-use crate::twitter::routes::serve::{SortBy, TweetParams};
+use crate::twitter::routes::serve::{Cursor, CursorMetric, SortBy, TweetParams};
+use crate::twitter::scrapers::responses::{Includes, ReferenceType, TweetObject};
+use crate::utils::constants::{TRENDING_GRAVITY, TWEET_PAGE_SIZE};
 
 // ----------------------------------------------------------------------------- structs/enums
 
-#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
 pub struct Tweet {
     pub id: Uuid,
     pub created_at: DateTime<Utc>,
@@ -32,6 +38,9 @@ pub struct Tweet {
     pub total_retweet_count: Option<i64>,
     pub popularity_count: Option<i64>,
     pub user_id: Uuid,
+    // threads
+    pub conversation_id: Option<String>,
+    pub thread_id: Option<Uuid>,
 }
 
 pub struct TweetMetrics {
@@ -56,30 +65,19 @@ pub struct TweetMetrics {
 // ----------------------------------------------------------------------------- fn
 
 #[tracing::instrument(skip(tweet), level = "debug")]
-pub fn extract_tweet_metrics(tweet: &Value) -> anyhow::Result<TweetMetrics> {
-    let like_count = tweet["public_metrics"]["like_count"]
-        .as_i64()
-        .ok_or(anyhow::anyhow!("no like_count"))?;
-    let quote_count = tweet["public_metrics"]["quote_count"]
-        .as_i64()
-        .ok_or(anyhow::anyhow!("no quote_count"))?;
-    let reply_count = tweet["public_metrics"]["reply_count"]
-        .as_i64()
-        .ok_or(anyhow::anyhow!("no reply_count"))?;
-    let retweet_count = tweet["public_metrics"]["retweet_count"]
-        .as_i64()
-        .ok_or(anyhow::anyhow!("no retweet_count"))?;
-    let total_retweet_count = quote_count + retweet_count;
-    let popularity_count = total_retweet_count + like_count + reply_count;
-
-    Ok(TweetMetrics {
-        like_count,
-        quote_count,
-        reply_count,
-        retweet_count,
+pub fn extract_tweet_metrics(tweet: &TweetObject) -> TweetMetrics {
+    let metrics = &tweet.public_metrics;
+    let total_retweet_count = metrics.quote_count + metrics.retweet_count;
+    let popularity_count = total_retweet_count + metrics.like_count + metrics.reply_count;
+
+    TweetMetrics {
+        like_count: metrics.like_count,
+        quote_count: metrics.quote_count,
+        reply_count: metrics.reply_count,
+        retweet_count: metrics.retweet_count,
         total_retweet_count,
         popularity_count,
-    })
+    }
 }
 
 #[tracing::instrument(skip(pool), level = "debug")]
@@ -96,70 +94,88 @@ pub async fn fetch_tweet(pool: &PgPool, tweet_id: &str) -> Result<Tweet, sqlx::e
     Ok(res)
 }
 
-#[tracing::instrument(skip(pool, tweet, body), level = "debug")]
+/// oldest first = reading order
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_thread_tweets(
+    pool: &PgPool,
+    thread_id: Uuid,
+) -> Result<Vec<Tweet>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Tweet,
+        r#"
+        SELECT * FROM tweets
+        WHERE thread_id = $1
+        ORDER BY tweet_created_at, tweet_id;
+        "#,
+        thread_id,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// silently leaves out tweets we don't have (yet)
+#[tracing::instrument(skip(pool, tweet_ids), level = "debug")]
+pub async fn fetch_tweets(
+    pool: &PgPool,
+    tweet_ids: &[String],
+) -> Result<Vec<Tweet>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Tweet,
+        r#"
+        SELECT * FROM tweets WHERE tweet_id = ANY($1)
+        "#,
+        tweet_ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+#[tracing::instrument(skip(pool, tweet, includes), level = "debug")]
 #[async_recursion]
 pub async fn store_tweet(
     pool: &PgPool,
-    tweet: &Value,
-    body: &Value, // not ideal that we have to pass the body here, but I need the media array from "includes"
+    tweet: &TweetObject,
+    includes: &Includes, // not ideal that we have to pass includes here, but I need the media array from it
     tweet_class: &str,
 ) -> anyhow::Result<()> {
-    let tweet_id = tweet["id"].as_str().ok_or(anyhow::anyhow!("no tweet_id"))?;
-    let author_id = tweet["author_id"]
-        .as_str()
-        .ok_or(anyhow::anyhow!("no author_id"))?;
+    let tweet_id = &tweet.id;
+    let author_id = &tweet.author_id;
     let author = fetch_user(&pool, author_id).await?; //needed to pass into sqlx query
     let tweet_url = format!("https://twitter.com/{}/status/{}", &author_id, &tweet_id);
-    let tweet_created_at = DateTime::parse_from_rfc3339(
-        tweet["created_at"]
-            .as_str()
-            .ok_or(anyhow::anyhow!("no created_at"))?,
-    )?;
+    let tweet_created_at = tweet.created_at;
 
     // handle reference tweets
     let mut replied_to_tweet_id: Option<String> = None;
     let mut quoted_tweet_id: Option<String> = None;
-    if let Some(ref_tweets) = tweet.get("referenced_tweets") {
-        for rt in ref_tweets
-            .as_array()
-            .ok_or(anyhow::anyhow!("no ref_tweets"))?
-        {
-            match rt["type"].as_str().ok_or(anyhow::anyhow!("no type"))? {
-                "retweeted" => {
-                    // NOTE: returns from function, as we only care to store the original post
-                    let retweet_id = rt["id"].as_str().ok_or(anyhow::anyhow!("no id"))?;
-                    // tracing::info!(
-                    //     ">>>I: Retweet detected, storing original tweet instead (id: {})",
-                    //     retweet_id
-                    // );
-                    let retweet = body["includes"]["tweets"]
-                        .as_array()
-                        .ok_or(anyhow::anyhow!("no included tweets"))?
-                        .iter()
-                        .filter(|&t| t["id"].as_str().expect("no id [impossible]") == retweet_id)
-                        .collect::<Vec<&Value>>(); //todo is there a better way (single value) than .collect here?
-                    return store_tweet(&pool, &retweet[0], &body, "rt_original").await;
-                }
-                "replied_to" => {
-                    replied_to_tweet_id =
-                        Some(rt["id"].as_str().ok_or(anyhow::anyhow!("no id"))?.into())
-                }
-                "quoted" => {
-                    quoted_tweet_id =
-                        Some(rt["id"].as_str().ok_or(anyhow::anyhow!("no id"))?.into())
-                }
-                _ => tracing::info!(">>>I: unrecognized referenced_tweet type"),
+    for rt in tweet.referenced_tweets.iter() {
+        match rt.ref_type {
+            ReferenceType::Retweeted => {
+                // NOTE: returns from function, as we only care to store the original post
+                // tracing::info!(
+                //     ">>>I: Retweet detected, storing original tweet instead (id: {})",
+                //     rt.id
+                // );
+                let retweet = includes
+                    .tweets
+                    .iter()
+                    .find(|t| t.id == rt.id)
+                    .ok_or(anyhow::anyhow!("retweeted tweet not in includes"))?;
+                return store_tweet(&pool, retweet, includes, "rt_original").await;
             }
+            ReferenceType::RepliedTo => replied_to_tweet_id = Some(rt.id.clone()),
+            ReferenceType::Quoted => quoted_tweet_id = Some(rt.id.clone()),
+            ReferenceType::Unknown => tracing::info!(">>>I: unrecognized referenced_tweet type"),
         }
     }
 
     // handle metrics
-    let tweet_metrics = extract_tweet_metrics(&tweet)?;
+    let tweet_metrics = extract_tweet_metrics(&tweet);
 
     // fix tweet text
-    let tweet_text = tweet["text"]
-        .as_str()
-        .ok_or(anyhow::anyhow!("no text"))?
+    let tweet_text = tweet
+        .text
         .replace("&quot;", "\"")
         .replace("&apos;", "'")
         .replace("&lt;", "<")
@@ -174,9 +190,9 @@ pub async fn store_tweet(
             tweet_id, tweet_created_at, tweet_text, tweet_url,
             replied_to_tweet_id, quoted_tweet_id, tweet_class, 
             like_count, quote_count, reply_count, retweet_count, total_retweet_count, popularity_count,
-            user_id)
+            user_id, conversation_id)
         VALUES 
-            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16)
+            ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17)
             
         ON CONFLICT (tweet_id)
         DO UPDATE SET
@@ -185,7 +201,8 @@ pub async fn store_tweet(
             reply_count = $12,
             retweet_count = $13,
             total_retweet_count = $14,
-            popularity_count = $15
+            popularity_count = $15,
+            conversation_id = $17
         "#,
         Uuid::new_v4(),
         Utc::now(),
@@ -205,15 +222,89 @@ pub async fn store_tweet(
         tweet_metrics.total_retweet_count,
         tweet_metrics.popularity_count,
         author.id,
+        tweet.conversation_id,
     )
     .execute(pool)
     .await?;
 
+    // keep history of metrics (IMPORTANT: must go after tweet itself, as references stored tweet id)
+    store_tweet_metric_snapshot(&pool, &tweet_id, &tweet_metrics).await?;
+
+    // keep search in sync (IMPORTANT: must go after tweet itself, as references stored tweet id)
+    store_tweet_search_document(&pool, &tweet_id).await?;
+
+    // cashtags, hashtags etc (IMPORTANT: must go after tweet itself, as references stored tweet id)
+    store_tweet_entities(&pool, &tweet_id, &tweet).await?;
+
+    // group self-replies into threads (IMPORTANT: must go after tweet itself, as links stored tweets)
+    let is_self_reply = replied_to_tweet_id.is_some()
+        && tweet.in_reply_to_user_id.as_deref() == Some(author_id.as_str());
+    if is_self_reply {
+        store_thread(&pool, &tweet.conversation_id, author.id).await?;
+    }
+    link_thread_tweets(&pool, &tweet.conversation_id, author.id).await?;
+
     // handle media (IMPORTANT: must go after tweet itself, as references stored tweet id)
-    handle_media_for_tweet(&pool, &tweet, &body).await?;
+    handle_media_for_tweet(&pool, &tweet, &includes).await?;
     Ok(())
 }
 
+#[tracing::instrument(skip(pool, tweet_metrics), level = "debug")]
+pub async fn update_tweet_metrics(
+    pool: &PgPool,
+    tweet_id: &str,
+    tweet_metrics: &TweetMetrics,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE tweets
+        SET
+            like_count = $2,
+            quote_count = $3,
+            reply_count = $4,
+            retweet_count = $5,
+            total_retweet_count = $6,
+            popularity_count = $7
+        WHERE tweet_id = $1;
+        "#,
+        tweet_id,
+        tweet_metrics.like_count,
+        tweet_metrics.quote_count,
+        tweet_metrics.reply_count,
+        tweet_metrics.retweet_count,
+        tweet_metrics.total_retweet_count,
+        tweet_metrics.popularity_count,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+// ----------------------------------------------------------------------------- metrics refresh
+
+/// core tweets (normal + rt_original) recent enough that their metrics are still moving
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_tweets_for_metrics_refresh(
+    pool: &PgPool,
+    hours_back: i64,
+) -> Result<Vec<Tweet>, sqlx::error::Error> {
+    let timeframe = Utc::now() - Duration::hours(hours_back);
+    let res = sqlx::query_as!(
+        Tweet,
+        r#"
+        SELECT * FROM tweets
+        WHERE
+            tweet_class != 'helper'
+            AND tweet_created_at > $1
+        ORDER BY tweet_created_at DESC
+        "#,
+        timeframe,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
 // ----------------------------------------------------------------------------- backfill
 
 /// core = rt_oritinal + normal
@@ -223,14 +314,14 @@ pub async fn fetch_core_tweets_to_backfill(
     days_back: i64,
 ) -> Result<Vec<Tweet>, sqlx::error::Error> {
     let timeframe = Utc::now() - Duration::days(days_back);
-    let sql = format!(
+    let tweets = sqlx::query_as(
         r#"
         WITH prefiltered_tweets AS (
             SELECT * 
             FROM tweets 
             WHERE 
                 tweet_class IN ('normal', 'rt_oritinal') 
-                AND tweet_created_at > '{}'
+                AND tweet_created_at > $1
             ORDER BY popularity_count
         )
         
@@ -258,9 +349,10 @@ pub async fn fetch_core_tweets_to_backfill(
         JOIN media ON prefiltered_tweets.id = media.tweet_id
         WHERE media.display_url IS NULL;
         "#,
-        timeframe.to_rfc3339(),
-    );
-    let tweets = sqlx::query_as(&sql).fetch_all(pool).await?;
+    )
+    .bind(timeframe)
+    .fetch_all(pool)
+    .await?;
     Ok(tweets)
 }
 
@@ -270,14 +362,14 @@ pub async fn fetch_helper_tweets_to_backfill(
     days_back: i64,
 ) -> Result<Vec<Tweet>, sqlx::error::Error> {
     let timeframe = Utc::now() - Duration::days(days_back);
-    let sql = format!(
+    let tweets = sqlx::query_as(
         r#"
         WITH prefiltered_tweets AS (
             SELECT * 
             FROM tweets 
             WHERE 
                 tweet_class = 'helper' 
-                AND tweet_created_at > '{}'
+                AND tweet_created_at > $1
             ORDER BY popularity_count
         )
         
@@ -286,85 +378,294 @@ pub async fn fetch_helper_tweets_to_backfill(
         JOIN media ON prefiltered_tweets.id = media.tweet_id
         WHERE media.display_url IS NULL;
         "#,
-        timeframe.to_rfc3339(),
-    );
-    let tweets = sqlx::query_as(&sql).fetch_all(pool).await?;
+    )
+    .bind(timeframe)
+    .fetch_all(pool)
+    .await?;
+    Ok(tweets)
+}
+
+/// Ancestors = the reply / quote chain above a core tweet (a reply to a reply to a reply...).
+/// The core pass above only fetches a core tweet's direct parents, so this walks up the chain we already
+/// have and returns the stored ancestors whose own parent is still missing. Re-fetching those pulls in the
+/// next level - so every backfill run extends each chain by one level, until max_depth is reached.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_ancestor_tweets_to_backfill(
+    pool: &PgPool,
+    days_back: i64,
+    max_depth: usize,
+) -> Result<Vec<Tweet>, sqlx::error::Error> {
+    let timeframe = Utc::now() - Duration::days(days_back);
+    let tweets = sqlx::query_as(
+        r#"
+        WITH RECURSIVE ancestors AS (
+            -- depth 1 = direct parents of core tweets
+            SELECT parent.*, 1 AS depth
+            FROM tweets core
+            JOIN tweets parent
+                ON parent.tweet_id IN (core.replied_to_tweet_id, core.quoted_tweet_id)
+            WHERE
+                core.tweet_class != 'helper'
+                AND core.tweet_created_at > $1
+
+            UNION
+
+            SELECT parent.*, ancestors.depth + 1
+            FROM ancestors
+            JOIN tweets parent
+                ON parent.tweet_id IN (ancestors.replied_to_tweet_id, ancestors.quoted_tweet_id)
+            WHERE ancestors.depth < $2
+        )
+
+        SELECT DISTINCT ON (tweet_id) *
+        FROM ancestors
+        WHERE
+            depth < $2
+            AND (
+                (replied_to_tweet_id IS NOT NULL AND replied_to_tweet_id NOT IN (SELECT tweet_id FROM tweets))
+                OR (quoted_tweet_id IS NOT NULL AND quoted_tweet_id NOT IN (SELECT tweet_id FROM tweets))
+            );
+        "#,
+    )
+    .bind(timeframe)
+    .bind(max_depth as i32)
+    .fetch_all(pool)
+    .await?;
     Ok(tweets)
 }
 
 // ----------------------------------------------------------------------------- serve
 
-/// Time case is trivial - simply order by creation date.
-/// Dates are long / diverse enough that we're unlikely to have two be exactly the same.
-///
-/// Other, not time based cases:
-///
-/// New metric
-/// - invents a new metric which:
-///     - takes user chosen metric (eg popularity count)
-///     - takes the first 10 numbers from tweet id
-///     - concats the two together into a single string, then casts it to int
-/// - why do it?
-///     - need a unique metric we can use as cursor when fetching tweets in pages (keyset pagination)
-/// - (!) THE COLUMN IS INDEXED FOR FASTER QUERIES
+/// Keyset pagination on the row value (metric, tweet_id):
+/// - metric = whatever the user sorts by (eg popularity count, or tweet_created_at for time)
+/// - tweet_id breaks ties, so that tweets with the same metric don't get skipped or repeated
+/// - cursor = (metric, tweet_id) of the last tweet on the previous page. None = first page,
+///   in which case we start from a sentinel that sits above every possible row
+/// - (!) (metric, tweet_id) HAS A COMPOSITE INDEX PER METRIC, so Postgres can walk it backwards
 ///
 /// Filter:
 /// - ignore helper tweets
 /// - limit to timeframe specified by user (eg last 24h)
-/// - bottom of query cut off: use the newly invented metric above
-/// - top of query cut off: page size (eg 20)
-///
-/// Order:
-/// - use the newly invented metric above
+/// - if unseen only: drop tweets the reader was already served. Happens before the LIMIT, so pages stay
+///   full - and since the cursor is a position in the sort order (not an offset), tweets dropping out of
+///   the result between pages can't shift it
+/// - if filtering by entities (eg cashtag=SOL): only tweets that have every one of them
+/// - a thread shows up once, as its head - ranked by the head's own metric, NOT the thread's combined
+///   metrics (those are only served, in FullTweet.thread). Ranking by a sum over each thread would mean
+///   aggregating every thread in the timeframe on every page, instead of walking the keyset index
+/// - bottom of query cut off: the cursor
+/// - top of query cut off: page size (TWEET_PAGE_SIZE)
 #[tracing::instrument(skip(pool, form), level = "debug")]
 pub async fn fetch_next_page_of_tweets(
     pool: &PgPool,
     form: &web::Query<TweetParams>,
+    cursor: Option<&Cursor>,
+    unseen: Option<&UnseenFilter>,
+) -> anyhow::Result<Vec<Tweet>> {
+    let column = match form.sort_by.column() {
+        Some(column) => column,
+        None => anyhow::bail!("{:?} isn't a column - it has its own query", form.sort_by),
+    };
+    let sql = format!(
+        r#"
+        SELECT *
+        FROM tweets
+        WHERE
+            tweet_class != 'helper'
+            AND tweet_created_at >= $1
+            AND ({0}, tweet_id) < ($2, $3)
+            -- a thread only shows up once, as its head
+            AND (
+                thread_id IS NULL
+                OR tweet_id = (SELECT head_tweet_id FROM threads WHERE threads.id = tweets.thread_id)
+            )
+            -- unseen only, skipped when no reader token is bound
+            AND ($4::TEXT IS NULL OR NOT EXISTS (
+                SELECT 1 FROM seen_tweets s
+                WHERE s.reader_token = $4 AND s.tweet_id = tweets.id AND s.seen_at >= $5
+            ))
+            -- entity filters: none of the requested (kind, value) pairs may be missing from the tweet
+            AND NOT EXISTS (
+                SELECT 1 FROM UNNEST($6::TEXT[], $7::TEXT[]) AS f(kind, value)
+                WHERE NOT EXISTS (
+                    SELECT 1 FROM tweet_entities te
+                    JOIN entities e ON e.id = te.entity_id
+                    WHERE te.tweet_id = tweets.id AND e.kind = f.kind AND e.value = f.value
+                )
+            )
+        ORDER BY {0} DESC, tweet_id DESC
+        LIMIT $8;
+        "#,
+        column,
+    );
+    let query = sqlx::query_as(&sql).bind(form.timeframe.to_datetime());
+
+    let query = match cursor {
+        Some(Cursor {
+            last_metric: CursorMetric::Time(last_time),
+            last_tweet_id,
+        }) => query.bind(*last_time).bind(last_tweet_id.clone()),
+        Some(Cursor {
+            last_metric: CursorMetric::Count(last_count),
+            last_tweet_id,
+        }) => query.bind(*last_count).bind(last_tweet_id.clone()),
+        Some(Cursor {
+            last_metric: CursorMetric::Score(_),
+            ..
+        }) => anyhow::bail!("trending cursor passed to a non-trending sort"),
+        // first page - any real tweet sorts below these
+        None => match form.sort_by {
+            SortBy::Time => query
+                .bind(Utc::now() + Duration::hours(1))
+                .bind(String::new()),
+            _ => query.bind(i64::MAX).bind(String::new()),
+        },
+    };
+    let entity_filter = EntityFilter::from_params(form);
+    let query = query
+        .bind(unseen.map(|u| u.reader_token.clone()))
+        .bind(unseen.map(|u| u.since))
+        .bind(entity_filter.kinds)
+        .bind(entity_filter.values)
+        .bind(TWEET_PAGE_SIZE);
+
+    let tweets = query.fetch_all(pool).await?;
+    Ok(tweets)
+}
+
+/// Feed cards only (no helpers, one card per thread) - for merging with other sources in /feed.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_top_tweets(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+    limit: i64,
 ) -> Result<Vec<Tweet>, sqlx::error::Error> {
-    let sql;
-    if let SortBy::Time = form.sort_by {
-        let mut last_metric = form.last_metric.clone();
-
-        // on the first call the frontend sends the largest possible integer.
-        // This doesn't work for time, so we swap it out for a unix timestamp 1 hour in the future.
-        if form.last_metric == "2036854775807" {
-            last_metric = (Utc::now() + Duration::hours(1)).to_rfc3339()
-        }
+    let res = sqlx::query_as!(
+        Tweet,
+        r#"
+        SELECT * FROM tweets
+        WHERE
+            tweet_class != 'helper'
+            AND tweet_created_at >= $1
+            AND (
+                thread_id IS NULL
+                OR tweet_id = (SELECT head_tweet_id FROM threads WHERE threads.id = tweets.thread_id)
+            )
+        ORDER BY popularity_count DESC NULLS LAST, tweet_id DESC
+        LIMIT $2;
+        "#,
+        since,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
 
-        sql = format!(
-            r#"
-            SELECT * 
-            FROM tweets
-            WHERE 
-                tweet_class != 'helper'
-                AND tweet_created_at >= '{0}'
-                AND tweet_created_at < '{1}'
-            ORDER BY tweet_created_at DESC
-            LIMIT 20;
-            "#,
-            form.timeframe.to_string(),
-            last_metric,
-        );
-    } else {
-        sql = format!(
-            r#"
-            SELECT * 
+/// None if there are no tweets in the timeframe
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_average_tweet_popularity(
+    pool: &PgPool,
+    since: DateTime<Utc>,
+) -> Result<Option<f64>, sqlx::error::Error> {
+    let res = sqlx::query!(
+        r#"
+        SELECT AVG(popularity_count)::FLOAT8 AS average
+        FROM tweets
+        WHERE
+            tweet_class != 'helper'
+            AND tweet_created_at >= $1;
+        "#,
+        since,
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(res.average)
+}
+
+/// Trending = engagement gained per hour since posting, time-decayed like Hacker News' gravity:
+///     score = popularity_count / (age_in_hours + 2) ^ TRENDING_GRAVITY
+///
+/// - age is measured from the start of the current hour (not now()), so that scores don't drift
+///   between page requests and the keyset cursor stays valid (at least until the hour ticks over)
+/// - cursor = (score of last tweet on previous page, its tweet id).
+///   tweet_id breaks ties, so that tweets with the same score don't get skipped
+/// - on the first page we start from an infinite score, which beats any real one
+#[tracing::instrument(skip(pool, form), level = "debug")]
+pub async fn fetch_next_page_of_trending_tweets(
+    pool: &PgPool,
+    form: &web::Query<TweetParams>,
+    cursor: Option<&Cursor>,
+    unseen: Option<&UnseenFilter>,
+) -> anyhow::Result<Vec<(Tweet, f64)>> {
+    let (last_score, last_tweet_id) = match cursor {
+        Some(Cursor {
+            last_metric: CursorMetric::Score(last_score),
+            last_tweet_id,
+        }) => (*last_score, last_tweet_id.clone()),
+        Some(_) => anyhow::bail!("non-trending cursor passed to the trending sort"),
+        None => (f64::INFINITY, String::new()),
+    };
+    let entity_filter = EntityFilter::from_params(form);
+
+    let rows = sqlx::query(
+        r#"
+        WITH scored_tweets AS (
+            SELECT
+                *,
+                COALESCE(popularity_count, 0) / POWER(
+                    GREATEST(EXTRACT(EPOCH FROM (date_trunc('hour', now()) - tweet_created_at))::float8 / 3600, 0) + 2,
+                    $1
+                ) AS trending_score
             FROM tweets
-            WHERE 
+            WHERE
                 tweet_class != 'helper'
-                AND tweet_created_at >= '{1}'
-                AND CAST({0} || LEFT(tweet_id, 10) AS BIGINT) < 
-                    CAST('{3}' || LEFT('{2}', 10) AS BIGINT)
-            ORDER BY 
-                CAST({0} || LEFT(tweet_id, 10) AS BIGINT) DESC 
-            LIMIT 20;
-            "#,
-            form.sort_by.to_string(),
-            form.timeframe.to_string(),
-            form.last_tweet_id,
-            form.last_metric,
-        );
+                AND tweet_created_at >= $2
+                -- a thread only shows up once, as its head
+                AND (
+                    thread_id IS NULL
+                    OR tweet_id = (SELECT head_tweet_id FROM threads WHERE threads.id = tweets.thread_id)
+                )
+                -- unseen only, skipped when no reader token is bound (same as in fetch_next_page_of_tweets)
+                AND ($5::TEXT IS NULL OR NOT EXISTS (
+                    SELECT 1 FROM seen_tweets s
+                    WHERE s.reader_token = $5 AND s.tweet_id = tweets.id AND s.seen_at >= $6
+                ))
+                -- entity filters (same as in fetch_next_page_of_tweets)
+                AND NOT EXISTS (
+                    SELECT 1 FROM UNNEST($7::TEXT[], $8::TEXT[]) AS f(kind, value)
+                    WHERE NOT EXISTS (
+                        SELECT 1 FROM tweet_entities te
+                        JOIN entities e ON e.id = te.entity_id
+                        WHERE te.tweet_id = tweets.id AND e.kind = f.kind AND e.value = f.value
+                    )
+                )
+        )
+
+        SELECT *
+        FROM scored_tweets
+        WHERE (trending_score, tweet_id) < ($3, $4)
+        ORDER BY trending_score DESC, tweet_id DESC
+        LIMIT $9;
+        "#,
+    )
+    .bind(TRENDING_GRAVITY)
+    .bind(form.timeframe.to_datetime())
+    .bind(last_score)
+    .bind(last_tweet_id)
+    .bind(unseen.map(|u| u.reader_token.clone()))
+    .bind(unseen.map(|u| u.since))
+    .bind(entity_filter.kinds)
+    .bind(entity_filter.values)
+    .bind(TWEET_PAGE_SIZE)
+    .fetch_all(pool)
+    .await?;
+
+    let mut tweets = vec![];
+    for row in rows.iter() {
+        let tweet = Tweet::from_row(row)?;
+        let trending_score: f64 = row.try_get("trending_score")?;
+        tweets.push((tweet, trending_score));
     }
-    let tweets = sqlx::query_as(&sql).fetch_all(pool).await?;
     Ok(tweets)
 }
@@ -1,12 +1,13 @@
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::Value;
 use sqlx::types::Uuid;
 use sqlx::PgPool;
 
+use crate::twitter::scrapers::responses::UserObject;
+
 // ----------------------------------------------------------------------------- structs/enums
 
-#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+#[derive(sqlx::FromRow, Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: Uuid,
     pub created_at: DateTime<Utc>,
@@ -19,6 +20,7 @@ pub struct User {
     pub following_count: Option<i64>,
     pub listed_count: Option<i64>,
     pub tweet_count: Option<i64>,
+    pub newest_tweet_id: Option<String>,
 }
 
 // ----------------------------------------------------------------------------- fn
@@ -37,6 +39,24 @@ pub async fn fetch_user(pool: &PgPool, user_id: &str) -> Result<User, sqlx::erro
     Ok(res)
 }
 
+/// returns None if we haven't stored the user yet (eg first time we pull their timeline)
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_user_if_exists(
+    pool: &PgPool,
+    user_id: &str,
+) -> Result<Option<User>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        User,
+        r#"
+        SELECT * FROM users WHERE twitter_user_id = $1
+        "#,
+        user_id,
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res)
+}
+
 #[tracing::instrument(skip(pool), level = "debug")]
 pub async fn fetch_user_by_uuid(pool: &PgPool, id: Uuid) -> Result<User, sqlx::error::Error> {
     let res = sqlx::query_as!(
@@ -51,8 +71,27 @@ pub async fn fetch_user_by_uuid(pool: &PgPool, id: Uuid) -> Result<User, sqlx::e
     Ok(res)
 }
 
+/// silently leaves out ids that don't exist
+#[tracing::instrument(skip(pool, ids), level = "debug")]
+pub async fn fetch_users_by_uuids(
+    pool: &PgPool,
+    ids: &[Uuid],
+) -> Result<Vec<User>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        User,
+        r#"
+        SELECT * FROM users WHERE id = ANY($1)
+        "#,
+        ids,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
 #[tracing::instrument(skip(pool, user), level = "debug")]
-pub async fn store_user(pool: &PgPool, user: &Value) -> Result<(), sqlx::error::Error> {
+pub async fn store_user(pool: &PgPool, user: &UserObject) -> Result<(), sqlx::error::Error> {
+    let metrics = user.public_metrics.as_ref();
     sqlx::query!(
         r#"
         INSERT INTO users
@@ -75,15 +114,35 @@ pub async fn store_user(pool: &PgPool, user: &Value) -> Result<(), sqlx::error::
         "#,
         Uuid::new_v4(),
         Utc::now(),
-        user["id"].as_str(),
-        user["name"].as_str(),
-        user["username"].as_str(),
-        user["url"].as_str(),
-        user["profile_image_url"].as_str(),
-        user["public_metrics"]["followers_count"].as_i64(),
-        user["public_metrics"]["following_count"].as_i64(),
-        user["public_metrics"]["listed_count"].as_i64(),
-        user["public_metrics"]["tweet_count"].as_i64(),
+        user.id,
+        user.name,
+        user.username,
+        user.url,
+        user.profile_image_url,
+        metrics.map(|m| m.followers_count),
+        metrics.map(|m| m.following_count),
+        metrics.map(|m| m.listed_count),
+        metrics.map(|m| m.tweet_count),
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn update_newest_tweet_id(
+    pool: &PgPool,
+    user_id: &str,
+    newest_tweet_id: &str,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE users
+        SET newest_tweet_id = $2
+        WHERE twitter_user_id = $1;
+        "#,
+        user_id,
+        newest_tweet_id,
     )
     .execute(pool)
     .await?;
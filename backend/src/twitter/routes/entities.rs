@@ -0,0 +1,48 @@
+#![allow(clippy::async_yields_async)]
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::twitter::model::entity::{fetch_trending_entities, EntityKind};
+use crate::twitter::routes::serve::Timeframe;
+use crate::utils::constants::TRENDING_ENTITIES_LIMIT;
+use crate::utils::errors::ApiError;
+use anyhow::Context;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct TrendingEntityParams {
+    pub timeframe: Timeframe,
+    // None = all kinds mixed together
+    pub kind: Option<EntityKind>,
+}
+
+// ----------------------------------------------------------------------------- fns
+
+/// Which cashtags / hashtags / accounts / links the timeframe's tweets talk about the most.
+/// Any of them can be fed straight back into /tweets as a filter, eg /tweets?cashtag=RAY.
+#[tracing::instrument(skip(pool))]
+#[get("/entities/trending")]
+pub async fn serve_trending_entities(
+    form: web::Query<TrendingEntityParams>,
+    pool: web::Data<Arc<PgPool>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let entities = fetch_trending_entities(
+        pool,
+        form.timeframe.to_datetime(),
+        form.kind.map(|k| k.to_string()),
+        TRENDING_ENTITIES_LIMIT,
+    )
+    .await
+    .context("failed to fetch trending entities")?;
+
+    let body = serde_json::to_string(&entities).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
@@ -1,2 +1,5 @@
+pub mod entities;
 pub mod pull;
+pub mod search;
 pub mod serve;
+pub mod syndication;
@@ -9,34 +9,56 @@ use sqlx::PgPool;
 use crate::config::Settings;
 use crate::twitter::core::jobs::{
     backfill_missing_media_and_helper_tweets, pull_timelines_for_followed_users,
+    refresh_metrics_for_recent_tweets,
 };
+use crate::twitter::scrapers::general::TwitterClient;
 use crate::utils::errors::ApiError;
 use anyhow::Context;
 
-#[tracing::instrument(skip(pool, config))]
+#[tracing::instrument(skip(pool, config, client))]
 #[get("/pull")]
 pub async fn pull(
     pool: web::Data<Arc<PgPool>>,
     config: web::Data<Arc<Settings>>,
+    client: web::Data<Arc<TwitterClient>>,
 ) -> Result<HttpResponse, ApiError> {
     let config = config.as_ref().deref();
     let pool = pool.as_ref().deref();
-    pull_timelines_for_followed_users(pool, config)
+    let client = client.as_ref().deref();
+    pull_timelines_for_followed_users(pool, config, client)
         .await
         .context("failed to pull timelines for followed users")?;
     Ok(HttpResponse::Ok().finish())
 }
 
-#[tracing::instrument(skip(pool, config))]
+#[tracing::instrument(skip(pool, config, client))]
 #[get("/backfill")]
 pub async fn backfill(
     pool: web::Data<Arc<PgPool>>,
     config: web::Data<Arc<Settings>>,
+    client: web::Data<Arc<TwitterClient>>,
 ) -> Result<HttpResponse, ApiError> {
     let config = config.as_ref().deref();
     let pool = pool.as_ref().deref();
-    backfill_missing_media_and_helper_tweets(pool, config)
+    let client = client.as_ref().deref();
+    backfill_missing_media_and_helper_tweets(pool, config, client)
         .await
         .context("failed to backfill media / helper tweets")?;
     Ok(HttpResponse::Ok().finish())
 }
+
+#[tracing::instrument(skip(pool, config, client))]
+#[get("/refresh")]
+pub async fn refresh(
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+    client: web::Data<Arc<TwitterClient>>,
+) -> Result<HttpResponse, ApiError> {
+    let config = config.as_ref().deref();
+    let pool = pool.as_ref().deref();
+    let client = client.as_ref().deref();
+    refresh_metrics_for_recent_tweets(pool, config, client)
+        .await
+        .context("failed to refresh metrics for recent tweets")?;
+    Ok(HttpResponse::Ok().finish())
+}
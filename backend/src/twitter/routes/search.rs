@@ -0,0 +1,82 @@
+#![allow(clippy::async_yields_async)]
+
+use std::ops::Deref;
+use std::sync::Arc;
+
+use actix_web::{get, web, HttpResponse};
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::twitter::model::search::fetch_next_page_of_search_results;
+use crate::twitter::routes::serve::{prep_full_tweets, Cursor, CursorMetric, Timeframe, TweetPage};
+use crate::utils::constants::TWEET_PAGE_SIZE;
+use crate::utils::errors::ApiError;
+use anyhow::Context;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct SearchParams {
+    pub q: String,
+    pub timeframe: Timeframe,
+    // opaque, as handed out in the previous page's next_cursor. None = first page
+    pub cursor: Option<String>,
+}
+
+// ----------------------------------------------------------------------------- fns
+
+/// Same response (and pagination) as /tweets - ranked by search score instead of a SortBy.
+/// See fetch_next_page_of_search_results for how the score works.
+#[tracing::instrument(skip(pool, config))]
+#[get("/search")]
+pub async fn serve_search(
+    form: web::Query<SearchParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let config = config.as_ref().deref();
+
+    let q = form.q.trim();
+    if q.is_empty() || q.chars().count() > 200 {
+        return Err(ApiError::BadRequest(
+            "q must be between 1 and 200 characters".into(),
+        ));
+    }
+    let cursor = match form.cursor {
+        Some(ref encoded) => Some(Cursor::decode_score(encoded)?),
+        None => None,
+    };
+
+    let results =
+        fetch_next_page_of_search_results(pool, q, form.timeframe.to_datetime(), cursor.as_ref())
+            .await
+            .context("failed to fetch next page of search results")?;
+
+    // a short page means there's nothing after it
+    let next_cursor = match results.last() {
+        Some((last, search_score)) if results.len() as i64 == TWEET_PAGE_SIZE => Some(
+            Cursor {
+                last_metric: CursorMetric::Score(*search_score),
+                last_tweet_id: last.tweet_id.clone(),
+            }
+            .encode()?,
+        ),
+        _ => None,
+    };
+
+    // the score only matters for the cursor - trending_score stays empty
+    let tweets = results.into_iter().map(|(t, _)| (t, None)).collect();
+    let full_tweets = prep_full_tweets(pool, config, tweets, config.app.max_thread_depth)
+        .await
+        .context("failed to prep full tweets")?;
+
+    let page = TweetPage {
+        tweets: full_tweets,
+        next_cursor,
+    };
+    let body = serde_json::to_string(&page).map_err(|e| anyhow::anyhow!("{}", e))?;
+    Ok(HttpResponse::Ok()
+        .content_type("application/json")
+        .body(body))
+}
@@ -1,18 +1,29 @@
 #![allow(clippy::async_yields_async)]
 
+use std::collections::{HashMap, HashSet};
 use std::fmt;
 use std::ops::Deref;
 use std::sync::Arc;
 
-use actix_web::{get, web, HttpResponse, Responder};
+use actix_web::cookie::{Cookie, SameSite};
+use actix_web::{get, web, HttpRequest, HttpResponse, Responder};
 use chrono::Duration;
 use serde::{Deserialize, Serialize};
-use sqlx::types::chrono::Utc;
+use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
 use sqlx::PgPool;
 
-use crate::twitter::model::media::{fetch_all_media_for_tweet, Media};
-use crate::twitter::model::tweet::{fetch_next_page_of_tweets, fetch_tweet, Tweet};
-use crate::twitter::model::user::{fetch_user_by_uuid, User};
+use crate::config::Settings;
+use crate::twitter::model::link_preview::{fetch_link_previews_for_tweets, LinkPreview};
+use crate::twitter::model::media::{fetch_all_media_for_tweets, Media};
+use crate::twitter::model::seen::{record_seen_tweets, UnseenFilter};
+use crate::twitter::model::thread::{fetch_threads, Thread};
+use crate::twitter::model::tweet::{
+    fetch_next_page_of_trending_tweets, fetch_next_page_of_tweets, fetch_thread_tweets,
+    fetch_tweets, Tweet,
+};
+use crate::twitter::model::user::{fetch_users_by_uuids, User};
+use crate::utils::constants::{READER_TOKEN_COOKIE, READER_TOKEN_HEADER, TWEET_PAGE_SIZE};
 use crate::utils::errors::ApiError;
 use anyhow::Context;
 
@@ -22,8 +33,30 @@ use anyhow::Context;
 pub struct TweetParams {
     pub sort_by: SortBy,
     pub timeframe: Timeframe,
+    // opaque, as handed out in the previous page's next_cursor. None = first page
+    pub cursor: Option<String>,
+    // only tweets this reader hasn't been served in the last seen_tweets_ttl_hours. None = false
+    pub unseen: Option<bool>,
+    // only tweets with this cashtag / hashtag / mention, eg cashtag=RAY. With or without the $ / # / @
+    pub cashtag: Option<String>,
+    pub hashtag: Option<String>,
+    pub mention: Option<String>,
+}
+
+/// Keyset pagination cursor = (sort metric, tweet_id) of the last tweet on the previous page.
+/// Goes over the wire as url-safe base64 json, so the frontend never needs to know what's inside.
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct Cursor {
+    pub last_metric: CursorMetric,
     pub last_tweet_id: String,
-    pub last_metric: String,
+}
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum CursorMetric {
+    Count(i64),
+    Time(DateTime<Utc>),
+    Score(f64),
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
@@ -34,6 +67,7 @@ pub enum SortBy {
     Likes,
     Replies,
     Time,
+    Trending,
 }
 
 #[derive(serde::Deserialize, serde::Serialize, Debug)]
@@ -52,52 +86,204 @@ pub struct FullTweet {
     pub tweet: Tweet,
     pub author: User,
     pub media: Option<Vec<Media>>,
+    // one per link in the tweet that we've managed to unfurl
+    pub link_previews: Vec<LinkPreview>,
     pub reply_to: Box<Option<FullTweet>>,
     pub quote_of: Box<Option<FullTweet>>,
+    // only present when sorting by trending - it's not a column, so we keep it around for the cursor
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trending_score: Option<f64>,
+    // present if the tweet is part of a self-thread - carries the thread's combined metrics
+    pub thread: Option<Thread>,
+}
+
+/// Everything needed to build FullTweets for a page, fetched up front so assembling needs no more queries.
+pub struct FullTweetParts {
+    // keyed by twitter's tweet_id, as that's what replied_to / quoted_tweet_id point to
+    pub ancestors: HashMap<String, Tweet>,
+    pub authors: HashMap<Uuid, User>,
+    pub media: HashMap<Uuid, Vec<Media>>,
+    pub link_previews: HashMap<Uuid, Vec<LinkPreview>>,
+    pub threads: HashMap<Uuid, Thread>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct TweetPage {
+    pub tweets: Vec<FullTweet>,
+    // None after a short page. A full page may still be followed by an empty one - cheaper than counting
+    pub next_cursor: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ThreadPage {
+    pub thread: Thread,
+    // oldest first
+    pub tweets: Vec<FullTweet>,
 }
 
 // ----------------------------------------------------------------------------- traits
 
-impl fmt::Display for SortBy {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+impl SortBy {
+    /// The only column names that ever make it into the sql - we can't bind identifiers, so we whitelist them.
+    /// None for trending - it's computed, not a column, and has its own query (fetch_next_page_of_trending_tweets).
+    /// Each of these has a (column, tweet_id) keyset index (migration 20210726093000). quote_count and
+    /// retweet_count lost their old indexes in that same migration on purpose: nothing sorts by them
+    /// ("retweets" is total_retweet_count), so an index would only slow down every metrics refresh.
+    pub fn column(&self) -> Option<&'static str> {
         match self {
-            SortBy::Popularity => write!(f, "popularity_count"),
-            SortBy::Retweets => write!(f, "total_retweet_count"),
-            SortBy::Likes => write!(f, "like_count"),
-            SortBy::Replies => write!(f, "reply_count"),
-            SortBy::Time => write!(f, "tweet_created_at"),
+            SortBy::Popularity => Some("popularity_count"),
+            SortBy::Retweets => Some("total_retweet_count"),
+            SortBy::Likes => Some("like_count"),
+            SortBy::Replies => Some("reply_count"),
+            SortBy::Time => Some("tweet_created_at"),
+            SortBy::Trending => None,
         }
     }
 }
 
 impl fmt::Display for Timeframe {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        let now = Utc::now();
+        write!(f, "{}", self.to_datetime().to_rfc3339())
+    }
+}
+
+impl Timeframe {
+    /// the cut off point - tweets older than this are outside of the timeframe
+    pub fn to_datetime(&self) -> DateTime<Utc> {
+        Utc::now() - self.to_duration()
+    }
+
+    pub fn to_duration(&self) -> Duration {
         match self {
-            Timeframe::Hour => {
-                let then = now - Duration::hours(1);
-                write!(f, "{}", then.to_rfc3339())
-            }
-            Timeframe::Four => {
-                let then = now - Duration::hours(4);
-                write!(f, "{}", then.to_rfc3339())
-            }
-            Timeframe::Day => {
-                let then = now - Duration::hours(24);
-                write!(f, "{}", then.to_rfc3339())
-            }
-            Timeframe::Twodays => {
-                let then = now - Duration::hours(48);
-                write!(f, "{}", then.to_rfc3339())
-            }
-            Timeframe::Week => {
-                let then = now - Duration::hours(24 * 7);
-                write!(f, "{}", then.to_rfc3339())
-            }
-            Timeframe::Month => {
-                let then = now - Duration::hours(24 * 30);
-                write!(f, "{}", then.to_rfc3339())
-            }
+            Timeframe::Hour => Duration::hours(1),
+            Timeframe::Four => Duration::hours(4),
+            Timeframe::Day => Duration::hours(24),
+            Timeframe::Twodays => Duration::hours(48),
+            Timeframe::Week => Duration::hours(24 * 7),
+            Timeframe::Month => Duration::hours(24 * 30),
+        }
+    }
+}
+
+impl Cursor {
+    pub fn encode(&self) -> anyhow::Result<String> {
+        let json = serde_json::to_vec(self)?;
+        Ok(base64::encode_config(json, base64::URL_SAFE_NO_PAD))
+    }
+
+    /// Rejects anything we didn't hand out ourselves, including a valid cursor for a different sort.
+    pub fn decode(encoded: &str, sort_by: &SortBy) -> Result<Cursor, ApiError> {
+        let cursor = Cursor::decode_any(encoded)?;
+        let matches_sort = match cursor.last_metric {
+            CursorMetric::Time(_) => matches!(sort_by, SortBy::Time),
+            CursorMetric::Score(_) => matches!(sort_by, SortBy::Trending),
+            CursorMetric::Count(_) => !matches!(sort_by, SortBy::Time | SortBy::Trending),
+        };
+        if !matches_sort {
+            return Err(Cursor::invalid());
+        }
+        Ok(cursor)
+    }
+
+    /// For rankings that aren't a SortBy (eg search) - only checks the cursor carries a score.
+    pub fn decode_score(encoded: &str) -> Result<Cursor, ApiError> {
+        let cursor = Cursor::decode_any(encoded)?;
+        match cursor.last_metric {
+            CursorMetric::Score(_) => Ok(cursor),
+            _ => Err(Cursor::invalid()),
+        }
+    }
+
+    fn decode_any(encoded: &str) -> Result<Cursor, ApiError> {
+        let json = base64::decode_config(encoded, base64::URL_SAFE_NO_PAD)
+            .map_err(|_| Cursor::invalid())?;
+        serde_json::from_slice(&json).map_err(|_| Cursor::invalid())
+    }
+
+    fn invalid() -> ApiError {
+        ApiError::BadRequest("invalid cursor".into())
+    }
+
+    /// Cursor pointing just past the given tweet, for the given sort.
+    pub fn after(tweet: &Tweet, trending_score: Option<f64>, sort_by: &SortBy) -> Cursor {
+        let last_metric = match sort_by {
+            SortBy::Popularity => CursorMetric::Count(tweet.popularity_count.unwrap_or(0)),
+            SortBy::Retweets => CursorMetric::Count(tweet.total_retweet_count.unwrap_or(0)),
+            SortBy::Likes => CursorMetric::Count(tweet.like_count.unwrap_or(0)),
+            SortBy::Replies => CursorMetric::Count(tweet.reply_count.unwrap_or(0)),
+            SortBy::Time => CursorMetric::Time(tweet.tweet_created_at),
+            SortBy::Trending => CursorMetric::Score(trending_score.unwrap_or(0.0)),
+        };
+        Cursor {
+            last_metric,
+            last_tweet_id: tweet.tweet_id.clone(),
+        }
+    }
+}
+
+impl FullTweet {
+    /// One line summary for places that can't show the metric icons - emails, chat messages.
+    pub fn describe_metrics(&self) -> String {
+        let t = &self.tweet;
+        format!(
+            "{} likes, {} retweets, {} replies",
+            t.like_count.unwrap_or(0),
+            t.total_retweet_count.unwrap_or(0),
+            t.reply_count.unwrap_or(0),
+        )
+    }
+
+    /// Our jpeg thumbnail once mirrored, twitter's display_url until then -
+    /// either is always an image (for videos / gifs it's the preview)
+    pub fn first_image(&self) -> Option<&String> {
+        self.media.iter().flatten().find_map(|m| m.preview_url())
+    }
+}
+
+impl FullTweetParts {
+    /// Nests the reply / quote chain above the tweet, at most `depth` levels up.
+    /// Ancestors we don't have (yet) are simply left out.
+    pub fn assemble(&self, tweet: Tweet, depth: usize) -> anyhow::Result<FullTweet> {
+        let author = self
+            .authors
+            .get(&tweet.user_id)
+            .cloned()
+            .with_context(|| format!("missing author for tweet {}", tweet.tweet_id))?;
+        let media = self.media.get(&tweet.id).cloned().unwrap_or_default();
+        let link_previews = self
+            .link_previews
+            .get(&tweet.id)
+            .cloned()
+            .unwrap_or_default();
+        let thread = tweet
+            .thread_id
+            .and_then(|id| self.threads.get(&id).cloned());
+        let reply_to = self.assemble_ancestor(&tweet.replied_to_tweet_id, depth)?;
+        let quote_of = self.assemble_ancestor(&tweet.quoted_tweet_id, depth)?;
+
+        Ok(FullTweet {
+            tweet,
+            author,
+            media: Some(media),
+            link_previews,
+            reply_to: Box::new(reply_to),
+            quote_of: Box::new(quote_of),
+            trending_score: None,
+            thread,
+        })
+    }
+
+    fn assemble_ancestor(
+        &self,
+        tweet_id: &Option<String>,
+        depth: usize,
+    ) -> anyhow::Result<Option<FullTweet>> {
+        if depth == 0 {
+            return Ok(None);
+        }
+        match tweet_id.as_ref().and_then(|id| self.ancestors.get(id)) {
+            Some(ancestor) => Ok(Some(self.assemble(ancestor.clone(), depth - 1)?)),
+            None => Ok(None),
         }
     }
 }
@@ -110,63 +296,275 @@ pub async fn health() -> impl Responder {
     HttpResponse::Ok().body("health ok!")
 }
 
-#[tracing::instrument(skip(pool))]
+/// Served tweets are only recorded as seen when the reader sent a token (X-Reader-Token header or
+/// cookie) or asked for unseen=true. Only the latter mints a new token for readers without one -
+/// sent back in the same header and set as a cookie.
+#[tracing::instrument(skip(req, pool, config))]
 #[get("/tweets")]
 pub async fn serve_tweets(
+    req: HttpRequest,
     form: web::Query<TweetParams>,
     pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
 ) -> Result<HttpResponse, ApiError> {
     let pool = pool.as_ref().deref();
-    let tweets = fetch_next_page_of_tweets(pool, &form)
+    let config = config.as_ref().deref();
+    let cursor = match form.cursor {
+        Some(ref encoded) => Some(Cursor::decode(encoded, &form.sort_by)?),
+        None => None,
+    };
+    let wants_unseen = form.unseen.unwrap_or(false);
+    // readers are only tracked once they've opted in by asking for unseen tweets -
+    // anyone else browsing (crawlers, the feeds, one-off visitors) doesn't get a token minted for them
+    let (reader_token, is_new_reader) = match reader_token(&req) {
+        Some(token) => (Some(token), false),
+        None if wants_unseen => (Some(Uuid::new_v4().to_simple().to_string()), true),
+        None => (None, false),
+    };
+    let unseen = match reader_token {
+        Some(ref token) if wants_unseen => Some(UnseenFilter {
+            reader_token: token.clone(),
+            since: Utc::now() - Duration::hours(config.app.seen_tweets_ttl_hours),
+        }),
+        _ => None,
+    };
+
+    let tweets = fetch_page(pool, &form, cursor.as_ref(), unseen.as_ref()).await?;
+
+    let next_cursor = match tweets.last() {
+        Some((last, trending_score)) if tweets.len() as i64 == TWEET_PAGE_SIZE => {
+            Some(Cursor::after(last, *trending_score, &form.sort_by).encode()?)
+        }
+        _ => None,
+    };
+
+    let full_tweets = prep_full_tweets(pool, config, tweets, config.app.max_thread_depth)
         .await
-        .context("failed to fetch next page of tweets")?;
+        .context("failed to prep full tweets")?;
 
-    let mut full_tweets: Vec<FullTweet> = vec![];
+    // only what's on the page itself - ancestors shown above a tweet don't count as seen.
+    // Failing to record shouldn't cost the reader their page, so only logging
+    if let Some(ref token) = reader_token {
+        let seen_ids = full_tweets.iter().map(|ft| ft.tweet.id).collect::<Vec<_>>();
+        if let Err(e) = record_seen_tweets(pool, token, &seen_ids, Utc::now()).await {
+            tracing::error!(">>>E: Failed to record seen tweets: {}", e);
+        }
+    }
 
-    for t in tweets.into_iter() {
-        let mut full_tweet = prep_full_tweet(pool, t)
-            .await
-            .context("failed to prep full tweet")?;
+    let page = TweetPage {
+        tweets: full_tweets,
+        next_cursor,
+    };
 
-        // tries to add a reply tweet, if present
-        if let Some(ref reply_tweet_id) = full_tweet.tweet.replied_to_tweet_id {
-            if let Ok(reply_tweet) = fetch_tweet(pool, reply_tweet_id).await {
-                let reply_full_tweet = prep_full_tweet(pool, reply_tweet)
-                    .await
-                    .context("failed to prep full tweet")?;
-                full_tweet.reply_to = Box::new(Some(reply_full_tweet));
-            }
+    let body = serde_json::to_string(&page).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let mut res = HttpResponse::Ok();
+    if let Some(token) = reader_token {
+        if is_new_reader {
+            res.cookie(
+                Cookie::build(READER_TOKEN_COOKIE, token.clone())
+                    .path("/")
+                    .http_only(true)
+                    .same_site(SameSite::Lax)
+                    .permanent()
+                    .finish(),
+            );
         }
+        res.insert_header((READER_TOKEN_HEADER, token));
+    }
+    Ok(res.content_type("application/json").body(body))
+}
+
+#[tracing::instrument(skip(pool, config))]
+#[get("/threads/{thread_id}")]
+pub async fn serve_thread(
+    thread_id: web::Path<Uuid>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    let pool = pool.as_ref().deref();
+    let config = config.as_ref().deref();
+    let thread_id = thread_id.into_inner();
 
-        // tried to add a quote tweet, if present
-        if let Some(ref quote_tweet_id) = full_tweet.tweet.quoted_tweet_id {
-            if let Ok(quote_tweet) = fetch_tweet(pool, quote_tweet_id).await {
-                let quote_full_tweet = prep_full_tweet(pool, quote_tweet)
-                    .await
-                    .context("failed to prep full tweet")?;
-                full_tweet.quote_of = Box::new(Some(quote_full_tweet));
+    let thread = fetch_threads(pool, &[thread_id])
+        .await
+        .context("failed to fetch thread")?
+        .pop();
+    let thread = match thread {
+        Some(thread) => thread,
+        None => return Ok(HttpResponse::NotFound().finish()),
+    };
+
+    let tweets = fetch_thread_tweets(pool, thread_id)
+        .await
+        .context("failed to fetch thread tweets")?;
+    let thread_tweet_ids = tweets
+        .iter()
+        .map(|t| t.tweet_id.clone())
+        .collect::<HashSet<String>>();
+    let mut full_tweets = prep_full_tweets(
+        pool,
+        config,
+        tweets.into_iter().map(|t| (t, None)).collect(),
+        config.app.max_thread_depth,
+    )
+    .await
+    .context("failed to prep full tweets")?;
+    // the previous tweet of the thread is right above anyway - only keep replies to someone else
+    for full_tweet in full_tweets.iter_mut() {
+        if let Some(ref reply_tweet_id) = full_tweet.tweet.replied_to_tweet_id {
+            if thread_tweet_ids.contains(reply_tweet_id) {
+                *full_tweet.reply_to = None;
             }
         }
-
-        full_tweets.push(full_tweet);
     }
 
-    let body = serde_json::to_string(&full_tweets).map_err(|e| anyhow::anyhow!("{}", e))?;
+    let page = ThreadPage {
+        thread,
+        tweets: full_tweets,
+    };
+
+    let body = serde_json::to_string(&page).map_err(|e| anyhow::anyhow!("{}", e))?;
     Ok(HttpResponse::Ok()
         .content_type("application/json")
         .body(body))
 }
 
-#[tracing::instrument(skip(pool, tweet), level = "debug")]
-pub async fn prep_full_tweet(pool: &PgPool, tweet: Tweet) -> Result<FullTweet, sqlx::error::Error> {
-    let author = fetch_user_by_uuid(&pool, tweet.user_id).await?;
-    let media = fetch_all_media_for_tweet(&pool, tweet.id).await?;
+/// One page of feed tweets for any sort - trending ones come with their score, the rest with None.
+#[tracing::instrument(skip(pool, form), level = "debug")]
+pub async fn fetch_page(
+    pool: &PgPool,
+    form: &web::Query<TweetParams>,
+    cursor: Option<&Cursor>,
+    unseen: Option<&UnseenFilter>,
+) -> anyhow::Result<Vec<(Tweet, Option<f64>)>> {
+    let tweets = match form.sort_by {
+        SortBy::Trending => fetch_next_page_of_trending_tweets(pool, form, cursor, unseen)
+            .await
+            .context("failed to fetch next page of trending tweets")?
+            .into_iter()
+            .map(|(t, score)| (t, Some(score)))
+            .collect(),
+        _ => fetch_next_page_of_tweets(pool, form, cursor, unseen)
+            .await
+            .context("failed to fetch next page of tweets")?
+            .into_iter()
+            .map(|t| (t, None))
+            .collect(),
+    };
+    Ok(tweets)
+}
+
+/// The first page for the given sort / timeframe, flat (no reply / quote nesting) - what goes out in
+/// outbound feeds and email digests.
+#[tracing::instrument(skip(pool, config), level = "debug")]
+pub async fn fetch_top_full_tweets(
+    pool: &PgPool,
+    config: &Settings,
+    params: TweetParams,
+) -> anyhow::Result<Vec<FullTweet>> {
+    let form = web::Query(params);
+    let tweets = fetch_page(pool, &form, None, None).await?;
+    let full_tweets = prep_full_tweets(pool, config, tweets, 0)
+        .await
+        .context("failed to prep full tweets")?;
+    Ok(full_tweets)
+}
+
+/// Turns a page of tweets into FullTweets with a fixed number of queries, no matter the page size:
+/// 1) the reply / quote chains above the page, one query per level (up to max_depth)
+/// 2) the authors of all of the above
+/// 3) the media of all of the above
+/// 4) their link previews
+/// 5) the threads any of the above belong to
+/// Everything is then stitched together in memory, keeping the page order.
+#[tracing::instrument(skip(pool, config, tweets), level = "debug")]
+pub async fn prep_full_tweets(
+    pool: &PgPool,
+    config: &Settings,
+    tweets: Vec<(Tweet, Option<f64>)>,
+    max_depth: usize,
+) -> anyhow::Result<Vec<FullTweet>> {
+    let mut ancestors: HashMap<String, Tweet> = HashMap::new();
+    let mut parent_ids = collect_parent_ids(tweets.iter().map(|(t, _)| t));
+    for _ in 0..max_depth {
+        parent_ids.retain(|id| !ancestors.contains_key(id));
+        if parent_ids.is_empty() {
+            break;
+        }
+        let parents = fetch_tweets(pool, &parent_ids).await?;
+        parent_ids = collect_parent_ids(parents.iter());
+        ancestors.extend(parents.into_iter().map(|t| (t.tweet_id.clone(), t)));
+    }
+
+    let all_tweets = || tweets.iter().map(|(t, _)| t).chain(ancestors.values());
+    let user_ids = all_tweets().map(|t| t.user_id).collect::<Vec<Uuid>>();
+    let tweet_ids = all_tweets().map(|t| t.id).collect::<Vec<Uuid>>();
+    let thread_ids = all_tweets()
+        .filter_map(|t| t.thread_id)
+        .collect::<Vec<Uuid>>();
+
+    let authors = fetch_users_by_uuids(pool, &user_ids)
+        .await?
+        .into_iter()
+        .map(|u| (u.id, u))
+        .collect::<HashMap<Uuid, User>>();
+    let mut media: HashMap<Uuid, Vec<Media>> = HashMap::new();
+    for mut m in fetch_all_media_for_tweets(pool, &tweet_ids).await? {
+        m.point_at_mirror(&config.app.api_url);
+        media.entry(m.tweet_id).or_default().push(m);
+    }
+    let mut link_previews: HashMap<Uuid, Vec<LinkPreview>> = HashMap::new();
+    for p in fetch_link_previews_for_tweets(pool, &tweet_ids).await? {
+        link_previews.entry(p.tweet_id).or_default().push(p.into());
+    }
+    let threads = fetch_threads(pool, &thread_ids)
+        .await?
+        .into_iter()
+        .map(|th| (th.id, th))
+        .collect::<HashMap<Uuid, Thread>>();
+
+    let parts = FullTweetParts {
+        ancestors,
+        authors,
+        media,
+        link_previews,
+        threads,
+    };
+    let mut full_tweets = vec![];
+    for (tweet, trending_score) in tweets.into_iter() {
+        let mut full_tweet = parts.assemble(tweet, max_depth)?;
+        full_tweet.trending_score = trending_score;
+        full_tweets.push(full_tweet);
+    }
+    Ok(full_tweets)
+}
+
+fn collect_parent_ids<'a>(tweets: impl Iterator<Item = &'a Tweet>) -> Vec<String> {
+    tweets
+        .flat_map(|t| t.replied_to_tweet_id.iter().chain(t.quoted_tweet_id.iter()))
+        .cloned()
+        .collect()
+}
 
-    Ok(FullTweet {
-        tweet,
-        author,
-        media: Some(media),
-        reply_to: Box::new(None::<FullTweet>),
-        quote_of: Box::new(None::<FullTweet>),
-    })
+/// The X-Reader-Token header wins over the cookie - it's what the frontend sends, as cookies don't
+/// survive the cross-origin setup we have when dev'ing. Anything that doesn't look like a token we
+/// handed out (or the frontend made up) is ignored, as if none was sent.
+fn reader_token(req: &HttpRequest) -> Option<String> {
+    let token = req
+        .headers()
+        .get(READER_TOKEN_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .or_else(|| {
+            req.cookie(READER_TOKEN_COOKIE)
+                .map(|c| c.value().to_string())
+        })?;
+    let is_valid = !token.is_empty()
+        && token.len() <= 64
+        && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+    if is_valid {
+        Some(token)
+    } else {
+        None
+    }
 }
@@ -0,0 +1,342 @@
+#![allow(clippy::async_yields_async)]
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::Arc;
+
+use actix_web::http::header;
+use actix_web::{get, web, HttpRequest, HttpResponse};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::twitter::routes::serve::{
+    fetch_top_full_tweets, FullTweet, SortBy, Timeframe, TweetParams,
+};
+use crate::utils::errors::ApiError;
+use crate::utils::general::escape_xml;
+
+// Top tweets as RSS 2.0 / Atom / JSON Feed, for feed readers and slack's rss integration.
+// Same sort_by / timeframe as /tweets, but always just the first page and no nesting.
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(serde::Deserialize, serde::Serialize, Debug)]
+pub struct SyndicationParams {
+    // both optional, so that a bare /feed.rss url works in any reader
+    pub sort_by: Option<SortBy>,
+    pub timeframe: Option<Timeframe>,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum SyndicationFormat {
+    Rss,
+    Atom,
+    Json,
+}
+
+/// Feed-level info shared by all formats.
+pub struct FeedMeta {
+    pub title: String,
+    pub site_url: String,
+    pub feed_url: String,
+    pub updated: DateTime<Utc>,
+}
+
+/// https://www.jsonfeed.org/version/1.1/
+#[derive(Debug, Serialize)]
+pub struct JsonFeed {
+    pub version: String,
+    pub title: String,
+    pub home_page_url: String,
+    pub feed_url: String,
+    pub items: Vec<JsonFeedItem>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFeedItem {
+    pub id: String,
+    pub url: String,
+    pub title: String,
+    pub content_text: String,
+    pub date_published: String,
+    pub authors: Vec<JsonFeedAuthor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct JsonFeedAuthor {
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avatar: Option<String>,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl SyndicationFormat {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            SyndicationFormat::Rss => "application/rss+xml; charset=utf-8",
+            SyndicationFormat::Atom => "application/atom+xml; charset=utf-8",
+            SyndicationFormat::Json => "application/feed+json; charset=utf-8",
+        }
+    }
+
+    pub fn path(&self) -> &'static str {
+        match self {
+            SyndicationFormat::Rss => "/feed.rss",
+            SyndicationFormat::Atom => "/feed.atom",
+            SyndicationFormat::Json => "/feed.json",
+        }
+    }
+
+    pub fn render(&self, meta: &FeedMeta, tweets: &[FullTweet]) -> anyhow::Result<String> {
+        match self {
+            SyndicationFormat::Rss => Ok(render_rss(meta, tweets)),
+            SyndicationFormat::Atom => Ok(render_atom(meta, tweets)),
+            SyndicationFormat::Json => render_json_feed(meta, tweets),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- fns
+
+#[tracing::instrument(skip(req, pool, config))]
+#[get("/feed.rss")]
+pub async fn serve_feed_rss(
+    req: HttpRequest,
+    form: web::Query<SyndicationParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    serve_syndicated(
+        &req,
+        form.into_inner(),
+        &pool,
+        &config,
+        SyndicationFormat::Rss,
+    )
+    .await
+}
+
+#[tracing::instrument(skip(req, pool, config))]
+#[get("/feed.atom")]
+pub async fn serve_feed_atom(
+    req: HttpRequest,
+    form: web::Query<SyndicationParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    serve_syndicated(
+        &req,
+        form.into_inner(),
+        &pool,
+        &config,
+        SyndicationFormat::Atom,
+    )
+    .await
+}
+
+#[tracing::instrument(skip(req, pool, config))]
+#[get("/feed.json")]
+pub async fn serve_feed_json(
+    req: HttpRequest,
+    form: web::Query<SyndicationParams>,
+    pool: web::Data<Arc<PgPool>>,
+    config: web::Data<Arc<Settings>>,
+) -> Result<HttpResponse, ApiError> {
+    serve_syndicated(
+        &req,
+        form.into_inner(),
+        &pool,
+        &config,
+        SyndicationFormat::Json,
+    )
+    .await
+}
+
+/// Feed readers poll a lot, so we support conditional requests:
+/// - ETag = hash of the rendered feed - changes whenever the set, order or content of tweets does
+/// - Last-Modified = the newest tweet in the feed - informational only, see is_not_modified
+/// If the client already has the current version, it gets an empty 304.
+#[tracing::instrument(skip(req, pool, config), level = "debug")]
+pub async fn serve_syndicated(
+    req: &HttpRequest,
+    params: SyndicationParams,
+    pool: &PgPool,
+    config: &Settings,
+    format: SyndicationFormat,
+) -> Result<HttpResponse, ApiError> {
+    let sort_by = params.sort_by.unwrap_or(SortBy::Popularity);
+    let timeframe = params.timeframe.unwrap_or(Timeframe::Day);
+    let title = format!("sol.wtf - top tweets by {:?} ({:?})", sort_by, timeframe).to_lowercase();
+    let full_tweets = fetch_top_full_tweets(
+        pool,
+        config,
+        TweetParams {
+            sort_by,
+            timeframe,
+            cursor: None,
+            unseen: None,
+            cashtag: None,
+            hashtag: None,
+            mention: None,
+        },
+    )
+    .await?;
+
+    let last_modified = full_tweets
+        .iter()
+        .map(|ft| ft.tweet.tweet_created_at)
+        .max()
+        .unwrap_or_else(Utc::now);
+    let meta = FeedMeta {
+        title,
+        site_url: config.app.site_url.clone(),
+        feed_url: format!("{}{}", config.app.api_url, format.path()),
+        updated: last_modified,
+    };
+    let body = format.render(&meta, &full_tweets)?;
+
+    let mut hasher = DefaultHasher::new();
+    body.hash(&mut hasher);
+    let etag = format!("\"{:x}\"", hasher.finish());
+    let last_modified = last_modified
+        .format("%a, %d %b %Y %H:%M:%S GMT")
+        .to_string();
+
+    if is_not_modified(req, &etag) {
+        return Ok(HttpResponse::NotModified()
+            .insert_header((header::ETAG, etag))
+            .insert_header((header::LAST_MODIFIED, last_modified))
+            .finish());
+    }
+    Ok(HttpResponse::Ok()
+        .content_type(format.content_type())
+        .insert_header((header::ETAG, etag))
+        .insert_header((header::LAST_MODIFIED, last_modified))
+        .body(body))
+}
+
+/// Only the ETag can tell whether the client's copy is current. Metrics keep changing (and with them
+/// the order) long after the newest tweet came in, so If-Modified-Since on its own would keep
+/// answering 304 for a stale feed - clients that only send that always get the full feed.
+/// Tags are compared weakly (RFC 7232), as proxies that compress the body mark them W/.
+pub fn is_not_modified(req: &HttpRequest, etag: &str) -> bool {
+    req.headers()
+        .get(header::IF_NONE_MATCH)
+        .and_then(|v| v.to_str().ok())
+        .map(|v| {
+            v.split(',')
+                .map(|tag| tag.trim())
+                .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+        })
+        .unwrap_or(false)
+}
+
+pub fn render_rss(meta: &FeedMeta, tweets: &[FullTweet]) -> String {
+    let mut items = String::new();
+    for ft in tweets.iter() {
+        let enclosure = match ft.first_image() {
+            Some(url) => format!(
+                r#"<enclosure url="{}" type="image/jpeg" length="0"/>"#,
+                escape_xml(url)
+            ),
+            None => String::new(),
+        };
+        items.push_str(&format!(
+            r#"<item><title>{title}</title><link>{url}</link><guid isPermaLink="true">{url}</guid><pubDate>{date}</pubDate><dc:creator>{author}</dc:creator><description>{text}</description>{enclosure}</item>"#,
+            title = escape_xml(&entry_title(ft)),
+            url = escape_xml(&ft.tweet.tweet_url),
+            date = ft.tweet.tweet_created_at.to_rfc2822(),
+            author = escape_xml(&author_name(ft)),
+            text = escape_xml(&ft.tweet.tweet_text),
+            enclosure = enclosure,
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><rss version="2.0" xmlns:atom="http://www.w3.org/2005/Atom" xmlns:dc="http://purl.org/dc/elements/1.1/"><channel><title>{title}</title><link>{site_url}</link><description>{title}</description><atom:link href="{feed_url}" rel="self" type="application/rss+xml"/><lastBuildDate>{updated}</lastBuildDate>{items}</channel></rss>"#,
+        title = escape_xml(&meta.title),
+        site_url = escape_xml(&meta.site_url),
+        feed_url = escape_xml(&meta.feed_url),
+        updated = meta.updated.to_rfc2822(),
+        items = items,
+    )
+}
+
+pub fn render_atom(meta: &FeedMeta, tweets: &[FullTweet]) -> String {
+    let mut entries = String::new();
+    for ft in tweets.iter() {
+        let enclosure = match ft.first_image() {
+            Some(url) => format!(
+                r#"<link rel="enclosure" href="{}" type="image/jpeg"/>"#,
+                escape_xml(url)
+            ),
+            None => String::new(),
+        };
+        entries.push_str(&format!(
+            r#"<entry><id>{url}</id><title>{title}</title><link rel="alternate" href="{url}"/>{enclosure}<published>{date}</published><updated>{date}</updated><author><name>{author}</name><uri>{author_url}</uri></author><content type="text">{text}</content></entry>"#,
+            url = escape_xml(&ft.tweet.tweet_url),
+            title = escape_xml(&entry_title(ft)),
+            enclosure = enclosure,
+            date = ft.tweet.tweet_created_at.to_rfc3339(),
+            author = escape_xml(&author_name(ft)),
+            author_url = escape_xml(&ft.author.profile_url),
+            text = escape_xml(&ft.tweet.tweet_text),
+        ));
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?><feed xmlns="http://www.w3.org/2005/Atom"><id>{feed_url}</id><title>{title}</title><link rel="alternate" href="{site_url}"/><link rel="self" href="{feed_url}"/><updated>{updated}</updated>{entries}</feed>"#,
+        feed_url = escape_xml(&meta.feed_url),
+        title = escape_xml(&meta.title),
+        site_url = escape_xml(&meta.site_url),
+        updated = meta.updated.to_rfc3339(),
+        entries = entries,
+    )
+}
+
+pub fn render_json_feed(meta: &FeedMeta, tweets: &[FullTweet]) -> anyhow::Result<String> {
+    let items = tweets
+        .iter()
+        .map(|ft| JsonFeedItem {
+            id: ft.tweet.tweet_url.clone(),
+            url: ft.tweet.tweet_url.clone(),
+            title: entry_title(ft),
+            content_text: ft.tweet.tweet_text.clone(),
+            date_published: ft.tweet.tweet_created_at.to_rfc3339(),
+            authors: vec![JsonFeedAuthor {
+                name: author_name(ft),
+                url: ft.author.profile_url.clone(),
+                avatar: ft.author.profile_image.clone(),
+            }],
+            image: ft.first_image().cloned(),
+        })
+        .collect();
+    let feed = JsonFeed {
+        version: String::from("https://jsonfeed.org/version/1.1"),
+        title: meta.title.clone(),
+        home_page_url: meta.site_url.clone(),
+        feed_url: meta.feed_url.clone(),
+        items,
+    };
+    Ok(serde_json::to_string(&feed)?)
+}
+
+/// Tweets have no title - "@handle: start of the text" reads well in a feed reader's list view.
+fn entry_title(ft: &FullTweet) -> String {
+    let text = ft.tweet.tweet_text.lines().next().unwrap_or_default();
+    let mut title = text.chars().take(80).collect::<String>();
+    if text.chars().count() > 80 {
+        title.push('…');
+    }
+    format!("@{}: {}", ft.author.twitter_handle, title)
+}
+
+fn author_name(ft: &FullTweet) -> String {
+    format!("{} (@{})", ft.author.twitter_name, ft.author.twitter_handle)
+}
@@ -7,12 +7,55 @@ use tokio::time;
 
 use crate::config::Environment;
 use crate::config::Settings;
+use crate::digest::core::jobs::send_digests_to_subscribers;
+use crate::digest::mailers::mailer::Mailer;
+use crate::discord::core::jobs::pull_messages_for_discord_channels;
+use crate::discord::scrapers::general::DiscordClient;
+use crate::mirror::core::jobs::mirror_recent_media;
+use crate::mirror::scrapers::general::MediaClient;
+use crate::mirror::stores::store::BlobStore;
+use crate::reddit::core::jobs::pull_top_posts_for_subreddits;
+use crate::reddit::scrapers::general::RedditClient;
+use crate::rss::core::jobs::pull_entries_for_rss_feeds;
+use crate::rss::scrapers::general::RssClient;
 use crate::twitter::core::jobs::{
-    backfill_missing_media_and_helper_tweets, pull_timelines_for_followed_users,
+    backfill_missing_link_previews, backfill_missing_media_and_helper_tweets,
+    prune_expired_seen_tweets, pull_timelines_for_followed_users,
+    refresh_metrics_for_recent_tweets,
 };
+use crate::twitter::scrapers::general::TwitterClient;
+use crate::twitter::scrapers::unfurl::LinkPreviewClient;
+use crate::webhooks::core::jobs::dispatch_new_top_tweets_to_webhooks;
+use crate::webhooks::senders::general::WebhookClient;
 
-#[tracing::instrument(skip(pool, config))]
-pub async fn schedule_tweet_refresh(pool: Arc<PgPool>, config: Arc<Settings>) {
+// one Arc per long-lived client
+#[allow(clippy::too_many_arguments)]
+#[tracing::instrument(skip(
+    pool,
+    config,
+    client,
+    reddit_client,
+    rss_client,
+    discord_client,
+    webhook_client,
+    link_preview_client,
+    media_client,
+    blob_store,
+    mailer
+))]
+pub async fn schedule_tweet_refresh(
+    pool: Arc<PgPool>,
+    config: Arc<Settings>,
+    client: Arc<TwitterClient>,
+    reddit_client: Arc<RedditClient>,
+    rss_client: Arc<RssClient>,
+    discord_client: Arc<DiscordClient>,
+    webhook_client: Arc<WebhookClient>,
+    link_preview_client: Arc<LinkPreviewClient>,
+    media_client: Arc<MediaClient>,
+    blob_store: Arc<dyn BlobStore>,
+    mailer: Arc<dyn Mailer>,
+) {
     let app_env: Environment = std::env::var("APP_ENVIRONMENT")
         .unwrap_or_else(|_| "dev".into())
         .try_into()
@@ -28,32 +71,142 @@ pub async fn schedule_tweet_refresh(pool: Arc<PgPool>, config: Arc<Settings>) {
         let mut interval = time::interval(Duration::from_secs(60 * config.app.refresh_freq));
         interval.tick().await;
 
-        // all 3 calls are happening inside the same spawn, so are between themselves synchronous (like they should be)
+        // all 12 calls are happening inside the same spawn, so are between themselves synchronous (like they should be)
         loop {
             tracing::info!(">>>I: Begin scheduled tweet refresh.");
 
             //intentionally upfront, otherwise on refreshes gets triggered and exhausts api
-            tracing::info!(">>>I: [0/2] Begin {} min delay", config.app.refresh_freq);
+            tracing::info!(">>>I: [0/11] Begin {} min delay", config.app.refresh_freq);
             interval.tick().await;
 
             // retry logic already inside
-            tracing::info!(">>>I: [1/2] Pull timelines");
-            pull_timelines_for_followed_users(pool.clone().as_ref(), config.clone().as_ref())
-                .await
-                .unwrap_or_else(|e| {
-                    tracing::error!(">>>E: Failed to pull timelines for users: {}", e);
-                });
+            tracing::info!(">>>I: [1/11] Pull timelines");
+            pull_timelines_for_followed_users(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to pull timelines for users: {}", e);
+            });
+
+            // retry logic already inside. Right after the pull, so that channels hear about new tweets asap
+            tracing::info!(">>>I: [2/11] Push new top tweets to webhooks");
+            dispatch_new_top_tweets_to_webhooks(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                webhook_client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to push new top tweets to webhooks: {}", e);
+            });
 
             // retry logic already inside
-            tracing::info!(">>>I: [2/2] Backfill media/helper tweets");
+            tracing::info!(">>>I: [3/11] Backfill media/helper tweets");
             backfill_missing_media_and_helper_tweets(
                 pool.clone().as_ref(),
                 config.clone().as_ref(),
+                client.clone().as_ref(),
             )
             .await
             .unwrap_or_else(|e| {
                 tracing::error!(">>>E: Failed to backfill media/helper tweets: {}", e);
             });
+
+            // no retries within a run - transiently failed links come back in a later one. Doesn't touch the twitter budget
+            tracing::info!(">>>I: [4/11] Backfill link previews");
+            backfill_missing_link_previews(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                link_preview_client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to backfill link previews: {}", e);
+            });
+
+            // retry logic already inside. Right after the backfill, so that freshly backfilled media gets mirrored too
+            tracing::info!(">>>I: [5/11] Mirror media");
+            mirror_recent_media(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                media_client.clone().as_ref(),
+                blob_store.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to mirror media: {}", e);
+            });
+
+            // retry logic already inside. Goes last - least important, shares the lookup budget with backfill
+            tracing::info!(">>>I: [6/11] Refresh metrics for recent tweets");
+            refresh_metrics_for_recent_tweets(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to refresh metrics for recent tweets: {}", e);
+            });
+
+            // retry logic already inside. Separate api, so doesn't compete with the above for budget
+            tracing::info!(">>>I: [7/11] Pull top reddit posts");
+            pull_top_posts_for_subreddits(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                reddit_client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to pull top reddit posts: {}", e);
+            });
+
+            // retry logic already inside
+            tracing::info!(">>>I: [8/11] Pull rss feeds");
+            pull_entries_for_rss_feeds(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                rss_client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to pull rss feeds: {}", e);
+            });
+
+            // retry logic already inside
+            tracing::info!(">>>I: [9/11] Pull discord announcements");
+            pull_messages_for_discord_channels(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                discord_client.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to pull discord announcements: {}", e);
+            });
+
+            // retry logic already inside. Goes last, so that digests include everything pulled above
+            tracing::info!(">>>I: [10/11] Send email digests");
+            send_digests_to_subscribers(
+                pool.clone().as_ref(),
+                config.clone().as_ref(),
+                mailer.clone().as_ref(),
+            )
+            .await
+            .unwrap_or_else(|e| {
+                tracing::error!(">>>E: Failed to send email digests: {}", e);
+            });
+
+            // housekeeping, no api calls
+            tracing::info!(">>>I: [11/11] Prune expired seen tweets");
+            prune_expired_seen_tweets(pool.clone().as_ref(), config.clone().as_ref())
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!(">>>E: Failed to prune expired seen tweets: {}", e);
+                });
         }
     });
 }
@@ -1,29 +1,57 @@
 use crate::config::Settings;
-use chrono::{DateTime, NaiveDateTime, Utc};
-use reqwest::Response;
-use serde_json::Value;
+use crate::utils::constants::{
+    RATE_LIMIT_FOLLOWING, RATE_LIMIT_SINGLE_TWEET, RATE_LIMIT_TWEET_LOOKUP,
+    RATE_LIMIT_USER_TIMELINE, RATE_LIMIT_WINDOW_MINS,
+};
+use anyhow::Context;
+use chrono::{DateTime, Duration, NaiveDateTime, Utc};
+use reqwest::{Response, StatusCode};
+use serde::de::DeserializeOwned;
+use std::collections::HashMap;
 use std::fmt;
+use std::sync::Mutex;
 
 // ----------------------------------------------------------------------------- structs/enums
 
 #[allow(non_snake_case)]
 #[derive(Debug, serde::Serialize)]
 pub struct Params {
+    pub ids: Option<String>,
     pub expansions: Option<String>,
     pub tweet___fields: Option<String>,
     pub user___fields: Option<String>,
     pub media___fields: Option<String>,
     pub max_results: Option<u32>,
     pub pagination_token: Option<String>,
+    pub since_id: Option<String>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct RateLimits {
     pub limit_left: u32,
     pub limit_total: u32,
     pub reset_time: DateTime<Utc>,
 }
 
+/// Twitter rate limits each endpoint family separately, so we track a bucket per family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Endpoint {
+    UserTimeline, // /users/:id/tweets
+    SingleTweet,  // /tweets/:id
+    TweetLookup,  // /tweets?ids=
+    Following,    // /users/:id/following
+}
+
+/// Long-lived client - keeps a single connection pool and the latest known rate limits for each endpoint.
+/// Meant to be created once on startup and shared (Arc) between the scheduler and the routes.
+pub struct TwitterClient {
+    client: reqwest::Client,
+    bearer_token: String,
+    rate_limits: Mutex<HashMap<Endpoint, RateLimits>>,
+}
+
+// ----------------------------------------------------------------------------- traits
+
 impl fmt::Display for RateLimits {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -36,37 +64,174 @@ impl fmt::Display for RateLimits {
     }
 }
 
-// ------------------------------------------------------------------------------ fn
+impl RateLimits {
+    /// What we assume the limits to be before twitter tells us otherwise (= a fresh window).
+    pub fn assumed(endpoint: Endpoint) -> Self {
+        let limit_total = endpoint.window_limit();
+        RateLimits {
+            limit_left: limit_total,
+            limit_total,
+            reset_time: Utc::now() + Duration::minutes(RATE_LIMIT_WINDOW_MINS),
+        }
+    }
+}
+
+impl Endpoint {
+    pub fn window_limit(&self) -> u32 {
+        match self {
+            Endpoint::UserTimeline => RATE_LIMIT_USER_TIMELINE,
+            Endpoint::SingleTweet => RATE_LIMIT_SINGLE_TWEET,
+            Endpoint::TweetLookup => RATE_LIMIT_TWEET_LOOKUP,
+            Endpoint::Following => RATE_LIMIT_FOLLOWING,
+        }
+    }
+}
+
+impl TwitterClient {
+    pub fn new(config: &Settings) -> Self {
+        TwitterClient {
+            client: reqwest::Client::new(),
+            bearer_token: config.twitter.bearer_token.clone(),
+            rate_limits: Mutex::new(HashMap::new()),
+        }
+    }
 
-#[tracing::instrument(skip(config), level = "debug")]
-pub async fn v2_api_get(
-    config: &Settings,
-    mut url: String,
-    params: Option<&Params>,
-) -> anyhow::Result<(Value, RateLimits)> {
-    let client = reqwest::Client::new();
-    let bearer_token = &config.twitter.bearer_token;
-
-    if let Some(params) = params {
-        let endpoint_params_raw = serde_url_params::to_string(params)?;
-        //needed coz twitter has weird field namings with dots, and you can't have dots in struct fields
-        let endpoint_params = endpoint_params_raw.replace("___", ".");
-        url = format!("{}?{}", url, endpoint_params);
+    /// How many calls we can still make to this endpoint in the current window.
+    pub fn remaining(&self, endpoint: Endpoint) -> u32 {
+        self.known_limits(endpoint).limit_left
     }
 
-    let res = client
-        .get(url)
-        .header("Authorization", format!("Bearer {}", bearer_token))
-        .send()
-        .await?;
-
-    tracing::info!(">>>I: GET call status: {}", &res.status());
-    let rate_limits = handle_rate_limits(&res)?; //  ^ the trait `From<Box<dyn StdError>>` is not implemented for `reqwest::Error`
-    let body: Value = res.json().await?;
-    // tracing::info!(">>>I: GET call returned body:\n\n{:#?}", &body);
-    Ok((body, rate_limits))
+    /// Our own count for the current window - a fresh window if we haven't got one going.
+    pub fn known_limits(&self, endpoint: Endpoint) -> RateLimits {
+        let rate_limits = self.rate_limits.lock().expect("rate limits lock poisoned");
+        match rate_limits.get(&endpoint) {
+            Some(limits) if limits.reset_time > Utc::now() => limits.clone(),
+            _ => RateLimits::assumed(endpoint),
+        }
+    }
+
+    /// Token bucket: takes one token for the endpoint, or pauses the caller until the window resets.
+    /// Tokens are taken before the call goes out, so that concurrent callers don't all see the same budget.
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn wait_for_budget(&self, endpoint: Endpoint) {
+        loop {
+            let wait = {
+                let mut rate_limits = self.rate_limits.lock().expect("rate limits lock poisoned");
+                let now = Utc::now();
+                let limits = rate_limits
+                    .entry(endpoint)
+                    .or_insert_with(|| RateLimits::assumed(endpoint));
+                // window is over - refill the bucket
+                if limits.reset_time <= now {
+                    *limits = RateLimits::assumed(endpoint);
+                }
+                if limits.limit_left > 0 {
+                    limits.limit_left -= 1;
+                    return;
+                }
+                limits.reset_time - now
+            }; //lock dropped here - never hold it across the sleep
+
+            tracing::info!(
+                ">>>I: Out of budget for {:?}, pausing for {}s",
+                endpoint,
+                wait.num_seconds()
+            );
+            // +1s since twitter's reset time only has second precision
+            let wait = wait.to_std().unwrap_or_default() + std::time::Duration::from_secs(1);
+            tokio::time::sleep(wait).await;
+        }
+    }
+
+    /// Twitter's headers are the source of truth - but responses can arrive out of order,
+    /// so within the same window we never let a stale header give us back tokens we already spent.
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub fn update_rate_limits(&self, endpoint: Endpoint, new_limits: &RateLimits) {
+        let mut rate_limits = self.rate_limits.lock().expect("rate limits lock poisoned");
+        match rate_limits.get_mut(&endpoint) {
+            Some(limits) if limits.reset_time == new_limits.reset_time => {
+                limits.limit_left = limits.limit_left.min(new_limits.limit_left);
+                limits.limit_total = new_limits.limit_total;
+            }
+            Some(limits) if limits.reset_time > new_limits.reset_time => {
+                // header from a previous window - ignore
+            }
+            _ => {
+                rate_limits.insert(endpoint, new_limits.clone());
+            }
+        }
+    }
+
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn v2_api_get<T: DeserializeOwned>(
+        &self,
+        endpoint: Endpoint,
+        mut url: String,
+        params: Option<&Params>,
+    ) -> anyhow::Result<(T, RateLimits)> {
+        if let Some(params) = params {
+            let endpoint_params_raw = serde_url_params::to_string(params)?;
+            //needed coz twitter has weird field namings with dots, and you can't have dots in struct fields
+            let endpoint_params = endpoint_params_raw.replace("___", ".");
+            url = format!("{}?{}", url, endpoint_params);
+        }
+
+        self.wait_for_budget(endpoint).await;
+        let res = self
+            .client
+            .get(url)
+            .header("Authorization", format!("Bearer {}", self.bearer_token))
+            .send()
+            .await?;
+
+        let status = res.status();
+        tracing::info!(">>>I: GET call status: {}", &status);
+        // errors (eg a 503 from twitter's edge) don't always carry the headers - missing ones just mean
+        // we learn nothing new, and keep counting on our own bucket
+        let rate_limits = match handle_rate_limits(&res) {
+            Ok(rate_limits) => {
+                self.update_rate_limits(endpoint, &rate_limits);
+                rate_limits
+            }
+            Err(e) => {
+                tracing::info!(
+                    ">>>I: No usable rate limit headers on a {} ({}), keeping our own count",
+                    status,
+                    e
+                );
+                self.known_limits(endpoint)
+            }
+        };
+        if status == StatusCode::TOO_MANY_REQUESTS {
+            // empty the bucket (headers or not), so the next attempt (eg a retry) waits for the reset
+            self.update_rate_limits(
+                endpoint,
+                &RateLimits {
+                    limit_left: 0,
+                    ..rate_limits.clone()
+                },
+            );
+            return Err(anyhow::anyhow!(
+                "rate limited on {:?}. {}",
+                endpoint,
+                rate_limits
+            ));
+        }
+        let res = res.error_for_status()?;
+
+        let raw_body = res.text().await?;
+        // tracing::info!(">>>I: GET call returned body:\n\n{}", &raw_body);
+        // going through serde_json directly (instead of res.json()) gives us the exact field / line that failed
+        let body: T = serde_json::from_str(&raw_body).context(format!(
+            "failed to deserialize response into {}",
+            std::any::type_name::<T>()
+        ))?;
+        Ok((body, rate_limits))
+    }
 }
 
+// ------------------------------------------------------------------------------ fn
+
 #[tracing::instrument(level = "debug")]
 pub fn handle_rate_limits(res: &Response) -> anyhow::Result<RateLimits> {
     let limit_left = res
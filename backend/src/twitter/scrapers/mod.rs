@@ -1,2 +1,4 @@
 pub mod general;
+pub mod responses;
 pub mod specific;
+pub mod unfurl;
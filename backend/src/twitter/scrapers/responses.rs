@@ -0,0 +1,224 @@
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+// Typed versions of the payloads returned by Twitter's v2 api.
+// Only the fields we actually request (see expansions / *.fields in specific.rs) are modelled.
+// Anything we rely on is a required field - so if Twitter changes the shape of the payload,
+// we fail at deserialization with a precise error, instead of silently writing NULLs into the db.
+
+// ----------------------------------------------------------------------------- responses
+
+/// GET /2/users/:id/tweets
+#[derive(Debug, Clone, Deserialize)]
+pub struct TimelineResponse {
+    // not present at all if the user has no tweets in the requested window
+    #[serde(default)]
+    pub data: Vec<TweetObject>,
+    #[serde(default)]
+    pub includes: Includes,
+    pub meta: Meta,
+}
+
+/// GET /2/tweets/:id
+#[derive(Debug, Clone, Deserialize)]
+pub struct SingleTweetResponse {
+    pub data: TweetObject,
+    #[serde(default)]
+    pub includes: Includes,
+}
+
+/// GET /2/tweets?ids=
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweetLookupResponse {
+    // deleted / protected tweets are left out of data and show up under "errors" instead
+    #[serde(default)]
+    pub data: Vec<TweetObject>,
+    #[serde(default)]
+    pub includes: Includes,
+}
+
+/// GET /2/users/:id/following
+#[derive(Debug, Clone, Deserialize)]
+pub struct FollowingResponse {
+    #[serde(default)]
+    pub data: Vec<UserObject>,
+    pub meta: Meta,
+}
+
+// ----------------------------------------------------------------------------- objects
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweetObject {
+    pub id: String,
+    pub text: String,
+    pub author_id: String,
+    pub created_at: DateTime<Utc>,
+    // id of the tweet that started the conversation - equal to id for a conversation's root
+    pub conversation_id: String,
+    pub in_reply_to_user_id: Option<String>,
+    pub public_metrics: TweetPublicMetrics,
+    #[serde(default)]
+    pub referenced_tweets: Vec<ReferencedTweet>,
+    pub attachments: Option<Attachments>,
+    // not present at all if the tweet has none
+    pub entities: Option<Entities>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TweetPublicMetrics {
+    pub retweet_count: i64,
+    pub reply_count: i64,
+    pub like_count: i64,
+    pub quote_count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ReferencedTweet {
+    #[serde(rename = "type")]
+    pub ref_type: ReferenceType,
+    pub id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ReferenceType {
+    Retweeted,
+    RepliedTo,
+    Quoted,
+    // don't want a new reference type on twitter's side to break the whole timeline
+    #[serde(other)]
+    Unknown,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attachments {
+    #[serde(default)]
+    pub media_keys: Vec<String>,
+}
+
+/// Things twitter has already parsed out of the text. Each kind is missing if the tweet has none of it.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Entities {
+    #[serde(default)]
+    pub cashtags: Vec<TagEntity>,
+    #[serde(default)]
+    pub hashtags: Vec<TagEntity>,
+    #[serde(default)]
+    pub mentions: Vec<MentionEntity>,
+    #[serde(default)]
+    pub urls: Vec<UrlEntity>,
+}
+
+/// tag comes without the leading $ / #
+#[derive(Debug, Clone, Deserialize)]
+pub struct TagEntity {
+    pub tag: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MentionEntity {
+    pub username: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UrlEntity {
+    pub url: String, // the t.co link, as it appears in the text
+    pub expanded_url: Option<String>,
+}
+
+/// The following endpoint only returns id/name/username, hence the rest are optional.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserObject {
+    pub id: String,
+    pub name: String,
+    pub username: String,
+    pub url: Option<String>,
+    pub profile_image_url: Option<String>,
+    pub public_metrics: Option<UserPublicMetrics>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserPublicMetrics {
+    pub followers_count: i64,
+    pub following_count: i64,
+    pub tweet_count: i64,
+    pub listed_count: i64,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct MediaObject {
+    pub media_key: String,
+    #[serde(rename = "type")]
+    pub media_type: String,
+    pub url: Option<String>,
+    pub preview_image_url: Option<String>,
+    pub alt_text: Option<String>,
+    pub width: Option<i32>,
+    pub height: Option<i32>,
+    pub duration_ms: Option<i32>,
+    // videos and gifs only
+    #[serde(default)]
+    pub variants: Vec<VariantObject>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct VariantObject {
+    pub content_type: String,
+    pub bit_rate: Option<i32>,
+    pub url: String,
+}
+
+/// Expanded objects for the ENTIRE returned batch of tweets (not per tweet).
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct Includes {
+    #[serde(default)]
+    pub users: Vec<UserObject>,
+    #[serde(default)]
+    pub tweets: Vec<TweetObject>,
+    #[serde(default)]
+    pub media: Vec<MediaObject>,
+}
+
+impl Includes {
+    /// Includes are returned for the whole batch - this picks out the part relevant to a single tweet:
+    /// its media, the tweets it references and the authors of both.
+    pub fn for_tweet(&self, tweet: &TweetObject) -> Includes {
+        let media_keys = tweet
+            .attachments
+            .as_ref()
+            .map(|a| a.media_keys.as_slice())
+            .unwrap_or_default();
+        let tweets = self
+            .tweets
+            .iter()
+            .filter(|t| tweet.referenced_tweets.iter().any(|rt| rt.id == t.id))
+            .cloned()
+            .collect::<Vec<TweetObject>>();
+        let users = self
+            .users
+            .iter()
+            .filter(|u| u.id == tweet.author_id || tweets.iter().any(|t| t.author_id == u.id))
+            .cloned()
+            .collect::<Vec<UserObject>>();
+        let media = self
+            .media
+            .iter()
+            .filter(|m| media_keys.contains(&m.media_key))
+            .cloned()
+            .collect::<Vec<MediaObject>>();
+
+        Includes {
+            users,
+            tweets,
+            media,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Meta {
+    pub result_count: u32,
+    pub next_token: Option<String>,
+    pub newest_id: Option<String>,
+    pub oldest_id: Option<String>,
+}
@@ -1,84 +1,136 @@
 use crate::config::Settings;
-use crate::twitter::scrapers::general::{v2_api_get, Params, RateLimits};
-use serde_json::Value;
+use crate::twitter::scrapers::general::{Endpoint, Params, RateLimits, TwitterClient};
+use crate::twitter::scrapers::responses::{
+    FollowingResponse, SingleTweetResponse, TimelineResponse, TweetLookupResponse, UserObject,
+};
 
-#[tracing::instrument(skip(config))]
+/// Without since_id (= first time we see the user) we only pull the last refresh_tweets_per_user tweets.
+/// With since_id we pull everything newer than it, a full page at a time.
+#[tracing::instrument(skip(client, config))]
 pub async fn get_user_timeline(
+    client: &TwitterClient,
     config: &Settings,
     user_id: &str,
-) -> anyhow::Result<(Value, RateLimits)> {
+    since_id: Option<String>,
+    pagination_token: Option<String>,
+) -> anyhow::Result<(TimelineResponse, RateLimits)> {
     let url = format!("https://api.twitter.com/2/users/{}/tweets", user_id);
+    let max_results = match since_id {
+        Some(_) => 100,
+        None => config.app.refresh_tweets_per_user,
+    };
     let params = Params {
+        ids: None,
         expansions: Some(String::from("author_id,referenced_tweets.id,referenced_tweets.id.author_id,in_reply_to_user_id,attachments.media_keys")),
         tweet___fields: Some(String::from(
-            "created_at,in_reply_to_user_id,public_metrics,referenced_tweets",
+            "created_at,conversation_id,in_reply_to_user_id,public_metrics,referenced_tweets,entities",
         )),
         user___fields: Some(String::from("name,username,profile_image_url,url,public_metrics")),
-        media___fields: Some(String::from("preview_image_url,url")),
-        max_results: Some(config.app.refresh_tweets_per_user),
-        pagination_token: None,
+        media___fields: Some(String::from(
+            "preview_image_url,url,variants,alt_text,width,height,duration_ms",
+        )),
+        max_results: Some(max_results),
+        pagination_token,
+        since_id,
     };
-    v2_api_get(&config, url, Some(&params)).await
+    client
+        .v2_api_get(Endpoint::UserTimeline, url, Some(&params))
+        .await
 }
 
-#[tracing::instrument(skip(config))]
+#[tracing::instrument(skip(client))]
 pub async fn get_single_tweet(
-    config: &Settings,
+    client: &TwitterClient,
     tweet_id: &str,
-) -> anyhow::Result<(Value, RateLimits)> {
+) -> anyhow::Result<(SingleTweetResponse, RateLimits)> {
     let url = format!("https://api.twitter.com/2/tweets/{}", tweet_id);
     let params = Params {
+        ids: None,
+        expansions: Some(String::from("author_id,referenced_tweets.id,referenced_tweets.id.author_id,in_reply_to_user_id,attachments.media_keys")),
+        tweet___fields: Some(String::from(
+            "created_at,conversation_id,in_reply_to_user_id,public_metrics,referenced_tweets,entities",
+        )),
+        user___fields: Some(String::from("name,username,profile_image_url,url,public_metrics")),
+        media___fields: Some(String::from(
+            "preview_image_url,url,variants,alt_text,width,height,duration_ms",
+        )),
+        max_results: None,
+        pagination_token: None,
+        since_id: None,
+    };
+    client
+        .v2_api_get(Endpoint::SingleTweet, url, Some(&params))
+        .await
+}
+
+/// Up to 100 tweets in a single call - see TWEET_LOOKUP_BATCH_SIZE.
+#[tracing::instrument(skip(client))]
+pub async fn get_tweets_batch(
+    client: &TwitterClient,
+    tweet_ids: &[String],
+) -> anyhow::Result<(TweetLookupResponse, RateLimits)> {
+    let url = String::from("https://api.twitter.com/2/tweets");
+    let params = Params {
+        ids: Some(tweet_ids.join(",")),
         expansions: Some(String::from("author_id,referenced_tweets.id,referenced_tweets.id.author_id,in_reply_to_user_id,attachments.media_keys")),
         tweet___fields: Some(String::from(
-            "created_at,in_reply_to_user_id,public_metrics,referenced_tweets",
+            "created_at,conversation_id,in_reply_to_user_id,public_metrics,referenced_tweets,entities",
         )),
         user___fields: Some(String::from("name,username,profile_image_url,url,public_metrics")),
-        media___fields: Some(String::from("preview_image_url,url")),
+        media___fields: Some(String::from(
+            "preview_image_url,url,variants,alt_text,width,height,duration_ms",
+        )),
         max_results: None,
         pagination_token: None,
+        since_id: None,
     };
-    v2_api_get(&config, url, Some(&params)).await
+    client
+        .v2_api_get(Endpoint::TweetLookup, url, Some(&params))
+        .await
 }
 
-#[tracing::instrument(skip(config))]
+#[tracing::instrument(skip(client, config))]
 pub async fn fetch_followed_users(
+    client: &TwitterClient,
     config: &Settings,
     pagination_token: Option<String>,
-) -> anyhow::Result<(Value, RateLimits)> {
+) -> anyhow::Result<(FollowingResponse, RateLimits)> {
     let soldotwtf = &config.app.followers_for_account;
     let url = format!("https://api.twitter.com/2/users/{}/following", soldotwtf);
     let params = Params {
+        ids: None,
         expansions: None,
         tweet___fields: None,
         user___fields: None,
         media___fields: None,
         max_results: Some(1000),
         pagination_token,
+        since_id: None,
     };
-    v2_api_get(&config, url, Some(&params)).await
+    client
+        .v2_api_get(Endpoint::Following, url, Some(&params))
+        .await
 }
 
-#[tracing::instrument(skip(config), level = "debug")]
+#[tracing::instrument(skip(client, config), level = "debug")]
 pub async fn fetch_all_followed_users(
+    client: &TwitterClient,
     config: &Settings,
-) -> anyhow::Result<(Vec<Value>, RateLimits)> {
-    let mut users: Vec<Value> = vec![];
+) -> anyhow::Result<(Vec<UserObject>, RateLimits)> {
+    let mut users: Vec<UserObject> = vec![];
     let mut rate_limits;
     let mut page_token: Option<String> = None;
 
     loop {
         let (mut new_users, new_rate_limits) =
-            fetch_followed_users(&config, page_token.clone()).await?;
-        let mut new_users_vec = new_users["data"]
-            .as_array_mut()
-            .ok_or(anyhow::anyhow!("failed to convert vec to array"))?;
+            fetch_followed_users(client, &config, page_token.clone()).await?;
 
         // taken from https://stackoverflow.com/questions/40792801/best-way-to-concatenate-vectors-in-rust#40795247
-        users.append(&mut new_users_vec);
+        users.append(&mut new_users.data);
         rate_limits = new_rate_limits;
 
-        match new_users["meta"]["next_token"].as_str() {
-            Some(next_token) => page_token = Some(next_token.into()),
+        match new_users.meta.next_token {
+            Some(next_token) => page_token = Some(next_token),
             None => break,
         }
     }
@@ -0,0 +1,343 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::client::connect::dns::Name;
+use reqwest::dns::{Addrs, Resolve, Resolving};
+use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use reqwest::Url;
+
+use crate::config::Settings;
+use crate::utils::general::unescape_html;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the api clients.
+/// Every limit here is there because the urls come from random tweets - we don't trust the other end.
+pub struct LinkPreviewClient {
+    client: reqwest::Client,
+    max_bytes: usize,
+    allow_private_hosts: bool,
+}
+
+/// The bits of a page's OpenGraph (or twitter card / plain html) metadata that make up a preview.
+#[derive(Debug, Default)]
+pub struct OpenGraph {
+    pub title: Option<String>,
+    pub description: Option<String>,
+    pub image_url: Option<String>,
+    pub site_name: Option<String>,
+}
+
+/// A fetched page - `final_url` is where the redirects ended up.
+#[derive(Debug)]
+pub struct UnfurledLink {
+    pub final_url: String,
+    pub og: OpenGraph,
+}
+
+/// Resolves hostnames like the system resolver would, but refuses to hand reqwest anything non-public.
+/// As reqwest connects to exactly the addresses returned here (for the original url and for every redirect),
+/// there's no window between checking an address and connecting to it for a dns rebind to slip through.
+struct PublicOnlyResolver;
+
+// ----------------------------------------------------------------------------- traits
+
+impl Resolve for PublicOnlyResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            // the port is ignored - reqwest swaps in the url's own
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            // one bad address is enough to refuse - a public one next to it is how rebinding tricks work
+            if addrs.is_empty() || addrs.iter().any(|a| !is_public_ip(a.ip())) {
+                return Err(format!("{} resolves to a non-public address", host).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+impl LinkPreviewClient {
+    pub fn new(config: &Settings) -> Self {
+        let max_redirects = config.link_previews.max_redirects;
+        let allow_private_hosts = config.link_previews.allow_private_hosts;
+        let mut builder = reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.link_previews.timeout_secs))
+            // every hop gets the same check as the original url - a redirect could point anywhere
+            .redirect(Policy::custom(move |attempt| {
+                if attempt.previous().len() >= max_redirects {
+                    attempt.error("too many redirects")
+                } else if !allow_private_hosts && !is_public_http_url(attempt.url()) {
+                    attempt.error("redirect to a non-public url")
+                } else {
+                    attempt.follow()
+                }
+            }))
+            .user_agent("sol.wtf link preview bot");
+        // tests point the client at a fixture server on localhost - nothing else should ever turn this on
+        if !allow_private_hosts {
+            builder = builder.dns_resolver(Arc::new(PublicOnlyResolver));
+        }
+        LinkPreviewClient {
+            client: builder
+                .build()
+                .expect("failed to build link preview http client"),
+            max_bytes: config.link_previews.max_bytes,
+            allow_private_hosts,
+        }
+    }
+
+    /// Reads at most `max_bytes` of the page - the meta tags live in <head>, so we stop as soon as that's over,
+    /// and ignore anything that came in after it.
+    #[tracing::instrument(skip(self), level = "debug")]
+    pub async fn unfurl(&self, url: &str) -> anyhow::Result<UnfurledLink> {
+        let parsed = Url::parse(url)?;
+        if !self.allow_private_hosts && !is_public_http_url(&parsed) {
+            return Err(anyhow::anyhow!("refusing to unfurl non-public url {}", url));
+        }
+
+        let mut res = self.client.get(parsed).send().await?;
+        tracing::info!(">>>I: GET call status: {}", &res.status());
+        res.error_for_status_ref()?;
+        let is_html = res
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|ct| ct.to_str().ok())
+            .map(|ct| ct.contains("text/html"))
+            .unwrap_or(false);
+        if !is_html {
+            return Err(anyhow::anyhow!("{} is not an html page", url));
+        }
+
+        let final_url = res.url().clone();
+        let mut body: Vec<u8> = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            body.extend_from_slice(&chunk);
+            if body.len() >= self.max_bytes || contains_end_of_head(&body) {
+                break;
+            }
+        }
+        body.truncate(self.max_bytes);
+        if let Some(end_of_head) = find_end_of_head(&body) {
+            body.truncate(end_of_head);
+        }
+
+        let html = String::from_utf8_lossy(&body);
+        Ok(UnfurledLink {
+            og: parse_open_graph(&html, &final_url),
+            final_url: final_url.to_string(),
+        })
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// og: tags first, then twitter card tags, then plain <title> / description.
+/// Relative image urls are resolved against the page they came from.
+pub fn parse_open_graph(html: &str, page_url: &Url) -> OpenGraph {
+    let meta = collect_meta_tags(html);
+    let pick = |keys: &[&str]| {
+        keys.iter()
+            .filter_map(|k| meta.get(*k))
+            .map(|v| v.trim())
+            .find(|v| !v.is_empty())
+            .map(|v| v.to_string())
+    };
+
+    OpenGraph {
+        title: pick(&["og:title", "twitter:title"])
+            .or_else(|| find_title_tag(html))
+            .map(|t| truncate_chars(&t, 300)),
+        description: pick(&["og:description", "twitter:description", "description"])
+            .map(|d| truncate_chars(&d, 1000)),
+        image_url: pick(&["og:image", "og:image:url", "twitter:image"])
+            .and_then(|i| page_url.join(&i).ok())
+            .filter(|i| i.scheme() == "http" || i.scheme() == "https")
+            .map(|i| i.to_string()),
+        site_name: pick(&["og:site_name"]).map(|s| truncate_chars(&s, 100)),
+    }
+}
+
+/// property (or name) -> content, for every <meta> tag. The first occurrence wins, same as for crawlers.
+fn collect_meta_tags(html: &str) -> HashMap<String, String> {
+    // ascii lowercasing keeps byte offsets the same, so we can search one and slice the other
+    let lower = html.to_ascii_lowercase();
+    let mut tags = HashMap::new();
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find("<meta") {
+        let start = pos + start + "<meta".len();
+        let end = match lower[start..].find('>') {
+            Some(end) => start + end,
+            None => break,
+        };
+        let attrs = parse_attributes(&html[start..end]);
+        let key = attrs.get("property").or_else(|| attrs.get("name"));
+        if let (Some(key), Some(content)) = (key, attrs.get("content")) {
+            tags.entry(key.to_ascii_lowercase())
+                .or_insert_with(|| unescape_html(content));
+        }
+        pos = end;
+    }
+    tags
+}
+
+/// Just enough of an html attribute parser for meta tags: name="value", name='value' and name=value.
+fn parse_attributes(tag: &str) -> HashMap<String, String> {
+    let mut attrs = HashMap::new();
+    let mut chars = tag.char_indices().peekable();
+    loop {
+        // skip to the start of a name
+        while let Some(&(_, c)) = chars.peek() {
+            if c.is_ascii_alphabetic() {
+                break;
+            }
+            chars.next();
+        }
+        let name_start = match chars.peek() {
+            Some(&(i, _)) => i,
+            None => break,
+        };
+        let mut name_end = tag.len();
+        while let Some(&(i, c)) = chars.peek() {
+            if !(c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == ':') {
+                name_end = i;
+                break;
+            }
+            chars.next();
+        }
+        let name = tag[name_start..name_end].to_ascii_lowercase();
+
+        while let Some(&(_, c)) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+        if chars.peek().map(|&(_, c)| c) != Some('=') {
+            // valueless attribute
+            continue;
+        }
+        chars.next();
+        while let Some(&(_, c)) = chars.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            chars.next();
+        }
+
+        let value = match chars.peek() {
+            Some(&(i, quote)) if quote == '"' || quote == '\'' => {
+                chars.next();
+                let mut end = tag.len();
+                for (j, c) in chars.by_ref() {
+                    if c == quote {
+                        end = j;
+                        break;
+                    }
+                }
+                &tag[i + 1..end]
+            }
+            Some(&(i, _)) => {
+                let mut end = tag.len();
+                while let Some(&(j, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '/' {
+                        end = j;
+                        break;
+                    }
+                    chars.next();
+                }
+                &tag[i..end]
+            }
+            None => "",
+        };
+        attrs.entry(name).or_insert_with(|| value.to_string());
+    }
+    attrs
+}
+
+fn find_title_tag(html: &str) -> Option<String> {
+    let lower = html.to_ascii_lowercase();
+    let start = lower.find("<title")?;
+    let start = start + lower[start..].find('>')? + 1;
+    let end = start + lower[start..].find("</title")?;
+    let title = unescape_html(html[start..end].trim());
+    if title.is_empty() {
+        None
+    } else {
+        Some(title)
+    }
+}
+
+fn contains_end_of_head(body: &[u8]) -> bool {
+    find_end_of_head(body).is_some()
+}
+
+fn find_end_of_head(body: &[u8]) -> Option<usize> {
+    body.windows(b"</head".len())
+        .position(|w| w.eq_ignore_ascii_case(b"</head"))
+}
+
+fn truncate_chars(s: &str, max: usize) -> String {
+    match s.char_indices().nth(max) {
+        Some((i, _)) => format!("{}…", &s[..i]),
+        None => s.to_string(),
+    }
+}
+
+/// Keeps the server from being used to poke at itself or the private network.
+/// Ip literals are checked here, as they never go through dns - hostnames are vetted by PublicOnlyResolver.
+fn is_public_http_url(url: &Url) -> bool {
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return false;
+    }
+    match url.host_str() {
+        None => false,
+        Some("localhost") => false,
+        Some(host) => match host
+            .trim_matches(|c| c == '[' || c == ']')
+            .parse::<IpAddr>()
+        {
+            Ok(ip) => is_public_ip(ip),
+            Err(_) => true,
+        },
+    }
+}
+
+fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => is_public_ipv6(ip),
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, _, _] = ip.octets();
+    !(ip.is_private() // 10/8, 172.16/12, 192.168/16
+        || ip.is_loopback()
+        || ip.is_link_local() // 169.254/16, incl. cloud metadata at 169.254.169.254
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0 // "this network"
+        || (a == 100 && (64..128).contains(&b)) // carrier-grade nat, 100.64/10
+        || a >= 240) // reserved
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    // ipv4-mapped / -compatible addresses reach the ipv4 network, so they get the ipv4 rules
+    if let Some(v4) = ip.to_ipv4() {
+        return is_public_ipv4(v4);
+    }
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        || (first & 0xfe00) == 0xfc00 // unique local, fc00::/7
+        || (first & 0xffc0) == 0xfe80) // link-local, fe80::/10
+}
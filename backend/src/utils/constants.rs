@@ -2,3 +2,52 @@ pub const RETRY_BASE: u64 = 5;
 pub const RETRY_FACTOR: u64 = 1000;
 pub const RETRY_COUNT_IMPORTANT: usize = 2;
 pub const RETRY_COUNT_NORMAL: usize = 1;
+
+// twitter's default rate limits per 15min window - only used until we get real numbers back in headers
+pub const RATE_LIMIT_WINDOW_MINS: i64 = 15;
+pub const RATE_LIMIT_USER_TIMELINE: u32 = 1500;
+pub const RATE_LIMIT_SINGLE_TWEET: u32 = 900;
+pub const RATE_LIMIT_TWEET_LOOKUP: u32 = 900;
+pub const RATE_LIMIT_FOLLOWING: u32 = 15;
+
+// max ids twitter accepts in a single /2/tweets?ids= lookup
+pub const TWEET_LOOKUP_BATCH_SIZE: usize = 100;
+
+// how fast trending scores decay with age - same default as hacker news
+pub const TRENDING_GRAVITY: f64 = 1.8;
+
+// how much popularity counts in search ranking, vs pure text relevance. 0 = relevance only
+pub const SEARCH_POPULARITY_WEIGHT: f64 = 1.0;
+
+// how many entries /entities/trending returns
+pub const TRENDING_ENTITIES_LIMIT: i64 = 20;
+
+// tweets per page of /tweets (and so also per syndicated feed / digest, which are its first page)
+pub const TWEET_PAGE_SIZE: i64 = 20;
+
+// items per page of the merged, multi-source /feed
+pub const FEED_PAGE_SIZE: usize = 20;
+// deepest (0-based) page of /feed we serve - every source has to hand over all the pages before it too
+pub const FEED_MAX_PAGE: u32 = 9;
+
+// how many of a channel's latest messages we (re)read each run - 100 is the most discord returns in one call
+pub const DISCORD_MESSAGES_PER_CHANNEL: u32 = 50;
+
+// how many runs we give a failing webhook delivery before giving up on that tweet
+pub const WEBHOOK_MAX_ATTEMPTS: i32 = 3;
+
+// where /tweets looks for the anonymous reader token behind "unseen only"
+pub const READER_TOKEN_HEADER: &str = "x-reader-token";
+pub const READER_TOKEN_COOKIE: &str = "reader_token";
+
+// images bigger than this (on either side) don't get thumbnailed - decoding them would eat too much memory
+pub const MAX_IMAGE_DIMENSION: u32 = 8192;
+pub const THUMBNAIL_JPEG_QUALITY: u8 = 80;
+
+// mirrored media never changes under a given key, so browsers / cdns can keep it for good (1 year)
+pub const MEDIA_CACHE_MAX_AGE_SECS: u32 = 31_536_000;
+
+// a link preview that failed for a transient reason (timeout, 5xx, 429) is tried again this much later,
+// up to LINK_PREVIEW_MAX_ATTEMPTS times in total
+pub const LINK_PREVIEW_RETRY_HOURS: i64 = 6;
+pub const LINK_PREVIEW_MAX_ATTEMPTS: i32 = 3;
@@ -1,3 +1,4 @@
+use actix_web::http::StatusCode;
 use actix_web::ResponseError;
 
 // preserve sqlx error where possible, otherwise show anyhow error
@@ -7,10 +8,20 @@ pub enum ApiError {
     SqlxError(#[from] sqlx::error::Error),
     #[error(transparent)] // this implements Display
     UnexpectedError(#[from] anyhow::Error),
+    // for anything the client sent us that we can't work with (eg a garbled cursor)
+    #[error("bad request: {0}")]
+    BadRequest(String),
 }
 
 // it says Display not implemented, but actually it is because we're deriving Display from thiserror
-impl ResponseError for ApiError {}
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
 
 // ----------------------------------------------------------------------------- saved for my ref - manual display/debug impl
 
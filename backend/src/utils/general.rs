@@ -19,3 +19,135 @@
 pub fn type_name_of<T>(_: T) -> &'static str {
     std::any::type_name::<T>()
 }
+
+/// Good enough for both xml (outbound feeds) and html (emails) - we only ever escape text and attribute values.
+pub fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// The named entities that actually show up in titles / descriptions, plus any numeric one (eg &#8217; or &#x27;).
+/// Single pass, so "&amp;lt;" comes out as "&lt;", not "<". Anything unrecognized is left as it was.
+pub fn unescape_html(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    let mut rest = s;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest
+            .find(';')
+            .filter(|&end| end <= 10)
+            .and_then(|end| decode_entity(&rest[1..end]).map(|c| (c, end)));
+        match decoded {
+            Some((c, end)) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+fn decode_entity(name: &str) -> Option<char> {
+    match name {
+        "amp" => Some('&'),
+        "lt" => Some('<'),
+        "gt" => Some('>'),
+        "quot" => Some('"'),
+        "apos" => Some('\''),
+        "nbsp" => Some(' '),
+        _ => {
+            let code =
+                if let Some(hex) = name.strip_prefix("#x").or_else(|| name.strip_prefix("#X")) {
+                    u32::from_str_radix(hex, 16).ok()
+                } else if let Some(dec) = name.strip_prefix('#') {
+                    dec.parse().ok()
+                } else {
+                    None
+                }?;
+            std::char::from_u32(code)
+        }
+    }
+}
+
+/// Html (eg an rss summary) down to plain text: tags dropped, along with whatever's inside <script> / <style>,
+/// block-level tags turned into line breaks, entities decoded and runs of whitespace collapsed.
+/// A "<" that doesn't open a tag (eg "a < b") is kept.
+pub fn html_to_text(html: &str) -> String {
+    // ascii lowercasing keeps byte offsets the same, so we can search one and slice the other
+    let lower = html.to_ascii_lowercase();
+    let mut text = String::with_capacity(html.len());
+    let mut pos = 0;
+    while let Some(start) = lower[pos..].find('<').map(|i| pos + i) {
+        push_html_text(&mut text, &html[pos..start]);
+        let opens_tag = lower[start + 1..]
+            .starts_with(|c: char| c.is_ascii_alphabetic() || c == '/' || c == '!');
+        if !opens_tag {
+            text.push('<');
+            pos = start + 1;
+            continue;
+        }
+        let end = match lower[start..].find('>') {
+            Some(i) => start + i + 1,
+            None => {
+                // an unclosed tag at the very end - drop it
+                pos = html.len();
+                break;
+            }
+        };
+        let tag = &lower[start + 1..end - 1];
+        let is_closing = tag.starts_with('/');
+        let name = tag
+            .trim_start_matches('/')
+            .split(|c: char| c.is_whitespace() || c == '/')
+            .next()
+            .unwrap_or_default();
+        pos = end;
+        match name {
+            "script" | "style" if !is_closing => {
+                let closing = format!("</{}", name);
+                pos = lower[end..]
+                    .find(&closing)
+                    .map(|i| end + i)
+                    .unwrap_or_else(|| html.len());
+            }
+            "br" | "p" | "div" | "li" | "tr" | "blockquote" | "pre" | "h1" | "h2" | "h3" | "h4"
+            | "h5" | "h6" | "figure" | "figcaption" => text.push('\n'),
+            _ => {}
+        }
+    }
+    push_html_text(&mut text, &html[pos..]);
+
+    unescape_html(&text)
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Line breaks in html source are just whitespace - only tags (and &#10;) make new lines.
+fn push_html_text(text: &mut String, raw: &str) {
+    text.extend(raw.chars().map(|c| if c.is_whitespace() { ' ' } else { c }));
+}
+
+/// Whether a failed http call is worth trying again later - timeouts, dropped connections, 429s and 5xxs.
+/// Anything else (4xx, a page we can't use, a non-public host) will fail the same way next time.
+pub fn is_transient_http_error(e: &anyhow::Error) -> bool {
+    e.chain()
+        .filter_map(|cause| cause.downcast_ref::<reqwest::Error>())
+        .any(|e| match e.status() {
+            Some(status) => {
+                status == reqwest::StatusCode::TOO_MANY_REQUESTS || status.is_server_error()
+            }
+            None => e.is_timeout() || e.is_connect() || e.is_body(),
+        })
+}
@@ -0,0 +1,68 @@
+use chrono::{Duration, Utc};
+use sqlx::PgPool;
+
+use crate::config::Settings;
+use crate::twitter::routes::serve::prep_full_tweets;
+use crate::utils::constants::WEBHOOK_MAX_ATTEMPTS;
+use crate::webhooks::core::processors::deliver_tweet;
+use crate::webhooks::model::delivery::fetch_undelivered_tweets;
+use crate::webhooks::model::webhook::fetch_active_webhooks;
+use crate::webhooks::senders::general::WebhookClient;
+
+/// Runs right after timelines are pulled, so that channels hear about a tweet as soon as we have it.
+/// Tweets that cross a webhook's threshold later (as their metrics grow) get picked up by a later run,
+/// for as long as they're inside the window.
+#[tracing::instrument(skip(pool, config, client))]
+pub async fn dispatch_new_top_tweets_to_webhooks(
+    pool: &PgPool,
+    config: &Settings,
+    client: &WebhookClient,
+) -> anyhow::Result<()> {
+    let webhooks = fetch_active_webhooks(pool).await?;
+    let since = Utc::now() - Duration::hours(config.webhooks.window_hours);
+
+    let mut delivered = 0;
+    for webhook in webhooks.iter() {
+        let tweets = fetch_undelivered_tweets(
+            pool,
+            webhook.id,
+            webhook.min_popularity,
+            since,
+            WEBHOOK_MAX_ATTEMPTS,
+            config.webhooks.max_deliveries_per_run,
+        )
+        .await?;
+        if tweets.is_empty() {
+            continue;
+        }
+        // flat - a chat message has no room for reply / quote chains
+        let full_tweets = prep_full_tweets(
+            pool,
+            config,
+            tweets.into_iter().map(|t| (t, None)).collect(),
+            0,
+        )
+        .await?;
+
+        for ft in full_tweets.iter() {
+            //fallible, but one tweet failing shouldn't stop the rest (only logging, retries are inside)
+            match deliver_tweet(pool, client, webhook, ft).await {
+                Ok(true) => delivered += 1,
+                Ok(false) => {}
+                Err(e) => tracing::error!(
+                    ">>>E: Failed to deliver tweet {} to webhook {}: {}",
+                    ft.tweet.tweet_id,
+                    webhook.name,
+                    e
+                ),
+            }
+        }
+    }
+
+    tracing::info!(
+        ">>>I: total webhook deliveries: {} across {} webhooks",
+        delivered,
+        webhooks.len(),
+    );
+    Ok(())
+}
@@ -0,0 +1,80 @@
+use sqlx::PgPool;
+use tokio_retry::strategy::ExponentialBackoff;
+use tokio_retry::Retry;
+
+use crate::twitter::routes::serve::FullTweet;
+use crate::utils::constants::{RETRY_BASE, RETRY_COUNT_IMPORTANT, RETRY_FACTOR};
+use crate::webhooks::model::delivery::{claim_delivery, record_delivery_outcome, DeliveryStatus};
+use crate::webhooks::model::webhook::Webhook;
+use crate::webhooks::senders::general::WebhookClient;
+use crate::webhooks::senders::payloads::WebhookPayload;
+use anyhow::Context;
+
+/// Claims the delivery first, so that the same tweet can never go out twice - even if two runs overlap.
+/// Whatever happens after the claim ends up in the delivery log.
+/// Returns whether the tweet was delivered.
+#[tracing::instrument(skip(pool, client, webhook, ft), fields(webhook_id = %webhook.id, tweet_id = %ft.tweet.id))]
+pub async fn deliver_tweet(
+    pool: &PgPool,
+    client: &WebhookClient,
+    webhook: &Webhook,
+    ft: &FullTweet,
+) -> anyhow::Result<bool> {
+    let delivery_id = match claim_delivery(pool, webhook.id, ft.tweet.id)
+        .await
+        .context("failed to claim delivery")?
+    {
+        Some(delivery_id) => delivery_id,
+        None => return Ok(false),
+    };
+
+    let payload = WebhookPayload::for_webhook(webhook, ft);
+    let res = match payload {
+        // post, retrying 2 times (5s and 25s) - it's what the feature is for, so counts as important
+        Ok(payload) => {
+            let retry_strategy = ExponentialBackoff::from_millis(RETRY_BASE)
+                .factor(RETRY_FACTOR)
+                .take(RETRY_COUNT_IMPORTANT);
+            Retry::spawn(retry_strategy, || async {
+                client.post(&webhook.url, &payload).await
+            })
+            .await
+        }
+        Err(e) => Err(e),
+    };
+
+    match res {
+        Ok(status) => {
+            record_delivery_outcome(
+                pool,
+                delivery_id,
+                DeliveryStatus::Delivered,
+                Some(status as i32),
+                None,
+            )
+            .await
+            .context("failed to record delivery")?;
+            Ok(true)
+        }
+        Err(e) => {
+            // keep the http status if we got that far
+            let status = e
+                .downcast_ref::<reqwest::Error>()
+                .and_then(|re| re.status())
+                .map(|s| s.as_u16() as i32);
+            record_delivery_outcome(
+                pool,
+                delivery_id,
+                DeliveryStatus::Failed,
+                status,
+                Some(e.to_string()),
+            )
+            .await
+            .context("failed to record failed delivery")?;
+            Err(e.context(format!(
+                "failed to deliver after {} retries",
+                RETRY_COUNT_IMPORTANT
+            )))
+        }
+    }
+}
@@ -0,0 +1,134 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::twitter::model::tweet::Tweet;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DeliveryStatus::Pending => write!(f, "pending"),
+            DeliveryStatus::Delivered => write!(f, "delivered"),
+            DeliveryStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+/// Tweets this webhook should hear about, most popular first:
+/// - above its threshold and newer than `since`
+/// - only the head of a self-thread, same as in the feed
+/// - never delivered before. A failed delivery gets tried again, up to `max_attempts` runs.
+///   A pending one might have gone out before a crash, so we'd rather miss it than post it twice.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_undelivered_tweets(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    min_popularity: i64,
+    since: DateTime<Utc>,
+    max_attempts: i32,
+    limit: i64,
+) -> Result<Vec<Tweet>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Tweet,
+        r#"
+        SELECT * FROM tweets
+        WHERE
+            tweet_class != 'helper'
+            AND tweet_created_at >= $2
+            AND popularity_count >= $3
+            AND (
+                thread_id IS NULL
+                OR tweet_id = (SELECT head_tweet_id FROM threads WHERE threads.id = tweets.thread_id)
+            )
+            AND NOT EXISTS (
+                SELECT 1 FROM webhook_deliveries d
+                WHERE d.webhook_id = $1
+                    AND d.tweet_id = tweets.id
+                    AND (d.status != 'failed' OR d.attempts >= $4)
+            )
+        ORDER BY popularity_count DESC NULLS LAST, tweet_id DESC
+        LIMIT $5;
+        "#,
+        webhook_id,
+        since,
+        min_popularity,
+        max_attempts,
+        limit,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
+
+/// Marks the delivery as pending before anything is sent. Returns None if it's not ours to send -
+/// ie it's already been delivered, or is pending from a run that didn't finish.
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn claim_delivery(
+    pool: &PgPool,
+    webhook_id: Uuid,
+    tweet_id: Uuid,
+) -> Result<Option<Uuid>, sqlx::error::Error> {
+    let res = sqlx::query!(
+        r#"
+        INSERT INTO webhook_deliveries
+            (id, created_at, webhook_id, tweet_id, status, attempts, last_attempt_at)
+        VALUES ($1, $2, $3, $4, $5, 1, $2)
+
+        ON CONFLICT (webhook_id, tweet_id)
+        DO UPDATE SET
+            status = $5,
+            attempts = webhook_deliveries.attempts + 1,
+            last_attempt_at = $2
+        WHERE webhook_deliveries.status = $6
+        RETURNING id
+        "#,
+        Uuid::new_v4(),
+        Utc::now(),
+        webhook_id,
+        tweet_id,
+        DeliveryStatus::Pending.to_string(),
+        DeliveryStatus::Failed.to_string(),
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(res.map(|r| r.id))
+}
+
+#[tracing::instrument(skip(pool, error), level = "debug")]
+pub async fn record_delivery_outcome(
+    pool: &PgPool,
+    delivery_id: Uuid,
+    status: DeliveryStatus,
+    response_status: Option<i32>,
+    error: Option<String>,
+) -> Result<(), sqlx::error::Error> {
+    sqlx::query!(
+        r#"
+        UPDATE webhook_deliveries
+        SET status = $2, response_status = $3, error = $4
+        WHERE id = $1
+        "#,
+        delivery_id,
+        status.to_string(),
+        response_status,
+        error,
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(sqlx::FromRow, Debug, Serialize, Deserialize)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub created_at: DateTime<Utc>,
+    pub name: String,
+    pub target: String,
+    pub url: String,
+    pub chat_id: Option<String>,
+    pub min_popularity: i64,
+    pub active: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WebhookTarget {
+    Slack,
+    Discord,
+    Telegram,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl fmt::Display for WebhookTarget {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WebhookTarget::Slack => write!(f, "slack"),
+            WebhookTarget::Discord => write!(f, "discord"),
+            WebhookTarget::Telegram => write!(f, "telegram"),
+        }
+    }
+}
+
+impl TryFrom<String> for WebhookTarget {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match &value[..] {
+            "slack" => Ok(Self::Slack),
+            "discord" => Ok(Self::Discord),
+            "telegram" => Ok(Self::Telegram),
+            _ => Err(format!("{} is an unsupported WebhookTarget.", value)),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- fn
+
+#[tracing::instrument(skip(pool), level = "debug")]
+pub async fn fetch_active_webhooks(pool: &PgPool) -> Result<Vec<Webhook>, sqlx::error::Error> {
+    let res = sqlx::query_as!(
+        Webhook,
+        r#"
+        SELECT * FROM webhooks WHERE active = TRUE ORDER BY created_at
+        "#,
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(res)
+}
@@ -0,0 +1,41 @@
+use std::time::Duration;
+
+use serde::Serialize;
+
+// ----------------------------------------------------------------------------- structs/enums
+
+/// Long-lived client - meant to be created once on startup and shared (Arc), same as the api clients.
+/// The same client posts to every target - all the differences live in the payloads.
+pub struct WebhookClient {
+    client: reqwest::Client,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl WebhookClient {
+    pub fn new() -> Self {
+        WebhookClient {
+            client: reqwest::Client::builder()
+                // a hanging chat api shouldn't hold up the whole scheduler
+                .timeout(Duration::from_secs(10))
+                .build()
+                .expect("failed to build webhook http client"),
+        }
+    }
+
+    /// Returns the response's http status. Anything other than a 2xx is an error, so that retries kick in
+    /// (eg discord and telegram both answer 429 when we post too fast).
+    #[tracing::instrument(skip(self, url, payload), level = "debug")]
+    pub async fn post<T: Serialize + ?Sized>(&self, url: &str, payload: &T) -> anyhow::Result<u16> {
+        let res = self.client.post(url).json(payload).send().await?;
+        tracing::info!(">>>I: POST webhook status: {}", &res.status());
+        let res = res.error_for_status()?;
+        Ok(res.status().as_u16())
+    }
+}
+
+impl Default for WebhookClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
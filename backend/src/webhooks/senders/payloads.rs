@@ -0,0 +1,165 @@
+use std::convert::TryFrom;
+
+use serde::Serialize;
+
+use crate::twitter::routes::serve::FullTweet;
+use crate::utils::general::escape_xml;
+use crate::webhooks::model::webhook::{Webhook, WebhookTarget};
+
+// Typed versions of what each chat app's incoming webhook / bot api accepts.
+// Only the fields we actually use are modelled.
+
+// ----------------------------------------------------------------------------- structs/enums
+
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum WebhookPayload {
+    Slack(SlackMessage),
+    Discord(DiscordMessage),
+    Telegram(TelegramMessage),
+}
+
+/// https://api.slack.com/messaging/webhooks
+#[derive(Debug, Serialize)]
+pub struct SlackMessage {
+    pub text: String, // mrkdwn
+    pub unfurl_links: bool,
+}
+
+/// https://discord.com/developers/docs/resources/webhook#execute-webhook
+#[derive(Debug, Serialize)]
+pub struct DiscordMessage {
+    pub username: String,
+    pub embeds: Vec<DiscordEmbed>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordEmbed {
+    pub title: String,
+    pub url: String,
+    pub description: String,
+    pub timestamp: String,
+    pub color: u32,
+    pub author: DiscordEmbedAuthor,
+    pub footer: DiscordEmbedFooter,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<DiscordEmbedImage>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordEmbedAuthor {
+    pub name: String,
+    pub url: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon_url: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordEmbedFooter {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DiscordEmbedImage {
+    pub url: String,
+}
+
+/// https://core.telegram.org/bots/api#sendmessage
+#[derive(Debug, Serialize)]
+pub struct TelegramMessage {
+    pub chat_id: String,
+    pub text: String, // html
+    pub parse_mode: String,
+}
+
+// ----------------------------------------------------------------------------- traits
+
+impl WebhookPayload {
+    pub fn for_webhook(webhook: &Webhook, ft: &FullTweet) -> anyhow::Result<Self> {
+        let target = WebhookTarget::try_from(webhook.target.clone()).map_err(anyhow::Error::msg)?;
+        let payload = match target {
+            WebhookTarget::Slack => WebhookPayload::Slack(SlackMessage::from_full_tweet(ft)),
+            WebhookTarget::Discord => WebhookPayload::Discord(DiscordMessage::from_full_tweet(ft)),
+            WebhookTarget::Telegram => {
+                let chat_id = webhook.chat_id.clone().ok_or_else(|| {
+                    anyhow::anyhow!("telegram webhook {} has no chat_id", webhook.id)
+                })?;
+                WebhookPayload::Telegram(TelegramMessage::from_full_tweet(ft, chat_id))
+            }
+        };
+        Ok(payload)
+    }
+}
+
+impl SlackMessage {
+    pub fn from_full_tweet(ft: &FullTweet) -> Self {
+        // slack only wants &, < and > escaped - and quotes the text line by line
+        let escape = |s: &str| {
+            s.replace('&', "&amp;")
+                .replace('<', "&lt;")
+                .replace('>', "&gt;")
+        };
+        let quoted = ft
+            .tweet
+            .tweet_text
+            .lines()
+            .map(|line| format!(">{}", escape(line)))
+            .collect::<Vec<_>>()
+            .join("\n");
+        SlackMessage {
+            text: format!(
+                "*{}* (@{}) · {}\n{}\n<{}|view on twitter>",
+                escape(&ft.author.twitter_name),
+                ft.author.twitter_handle,
+                ft.describe_metrics(),
+                quoted,
+                ft.tweet.tweet_url,
+            ),
+            // the quote above already has the text, the unfurl would just repeat it
+            unfurl_links: false,
+        }
+    }
+}
+
+impl DiscordMessage {
+    pub fn from_full_tweet(ft: &FullTweet) -> Self {
+        DiscordMessage {
+            username: String::from("sol.wtf"),
+            embeds: vec![DiscordEmbed {
+                title: format!("@{}", ft.author.twitter_handle),
+                url: ft.tweet.tweet_url.clone(),
+                description: ft.tweet.tweet_text.clone(),
+                timestamp: ft.tweet.tweet_created_at.to_rfc3339(),
+                color: 0x1DA1F2, // twitter blue
+                author: DiscordEmbedAuthor {
+                    name: ft.author.twitter_name.clone(),
+                    url: ft.author.profile_url.clone(),
+                    icon_url: ft.author.profile_image.clone(),
+                },
+                footer: DiscordEmbedFooter {
+                    text: ft.describe_metrics(),
+                },
+                image: ft
+                    .first_image()
+                    .map(|url| DiscordEmbedImage { url: url.clone() }),
+            }],
+        }
+    }
+}
+
+impl TelegramMessage {
+    pub fn from_full_tweet(ft: &FullTweet, chat_id: String) -> Self {
+        TelegramMessage {
+            chat_id,
+            text: format!(
+                "<b>{}</b> (@{}) · {}\n\n{}\n\n<a href=\"{}\">view on twitter</a>",
+                escape_xml(&ft.author.twitter_name),
+                escape_xml(&ft.author.twitter_handle),
+                ft.describe_metrics(),
+                escape_xml(&ft.tweet.tweet_text),
+                escape_xml(&ft.tweet.tweet_url),
+            ),
+            parse_mode: String::from("HTML"),
+        }
+    }
+}
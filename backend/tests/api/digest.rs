@@ -0,0 +1,140 @@
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+
+use backend::config::Settings;
+use backend::digest::core::jobs::send_digests_to_subscribers;
+use backend::digest::core::processors::send_confirmation;
+use backend::digest::mailers::mailer::Mailer;
+use backend::digest::mailers::smtp::SmtpMailer;
+use backend::digest::model::subscriber::{confirm_subscriber, store_subscriber};
+use chrono::Utc;
+
+use crate::helpers::{insert_tweet, insert_user, spawn_db, test_config};
+
+/// Just enough of an smtp server for lettre to hand it mail - the same job mailhog does locally.
+/// Every message's DATA section comes out of the returned channel.
+fn spawn_smtp_sink() -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to bind smtp sink");
+    let port = listener.local_addr().unwrap().port();
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            let tx = tx.clone();
+            // lettre pools connections, so more than one can be open at a time
+            thread::spawn(move || serve_smtp(stream.unwrap(), tx));
+        }
+    });
+    (port, rx)
+}
+
+fn serve_smtp(mut stream: TcpStream, tx: Sender<String>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    stream.write_all(b"220 sink\r\n").unwrap();
+    let mut line = String::new();
+    loop {
+        line.clear();
+        if reader.read_line(&mut line).unwrap_or(0) == 0 {
+            return;
+        }
+        let command = line.trim_end().to_uppercase();
+        let reply: &[u8] = if command.starts_with("DATA") {
+            stream.write_all(b"354 go ahead\r\n").unwrap();
+            let mut data = vec![];
+            loop {
+                line.clear();
+                reader.read_line(&mut line).unwrap();
+                if line.trim_end() == "." {
+                    break;
+                }
+                data.push(line.trim_end().to_string());
+            }
+            tx.send(data.join("\r\n")).unwrap();
+            b"250 queued\r\n"
+        } else if command.starts_with("QUIT") {
+            stream.write_all(b"221 bye\r\n").unwrap();
+            return;
+        } else {
+            // EHLO, MAIL, RCPT, RSET, NOOP - accept everything
+            b"250 ok\r\n"
+        };
+        stream.write_all(reply).unwrap();
+    }
+}
+
+/// test_config, pointed at the sink.
+fn sink_config(port: u16) -> Settings {
+    let mut config = test_config();
+    config.email.smtp_host = "127.0.0.1".into();
+    config.email.smtp_port = port;
+    config.email.smtp_username = String::new();
+    config.email.smtp_tls = false;
+    config
+}
+
+/// Undoes quoted-printable's soft line breaks, so long urls can be searched for in one piece.
+fn unfold(message: &str) -> String {
+    message.replace("=\r\n", "")
+}
+
+#[actix_rt::test]
+async fn confirmation_email_reaches_the_smtp_server() {
+    let pool = spawn_db().await;
+    let (port, rx) = spawn_smtp_sink();
+    let config = sink_config(port);
+    let mailer = SmtpMailer::new(&config).unwrap();
+
+    let subscriber = store_subscriber(&pool, "reader@example.com", "popularity", "day")
+        .await
+        .unwrap();
+    send_confirmation(&config, &mailer as &dyn Mailer, &subscriber)
+        .await
+        .unwrap();
+
+    let message = unfold(&rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(message.contains("To: reader@example.com"));
+    assert!(message.contains("Subject: Confirm your sol.wtf digest"));
+    assert!(message.contains(&subscriber.confirm_token));
+    // both the html and the plain text part
+    assert!(message.contains("text/plain"));
+    assert!(message.contains("text/html"));
+}
+
+#[actix_rt::test]
+async fn digests_go_out_once_per_timeframe() {
+    let pool = spawn_db().await;
+    let (port, rx) = spawn_smtp_sink();
+    let config = sink_config(port);
+    let mailer = SmtpMailer::new(&config).unwrap();
+
+    let user_id = insert_user(&pool, "alice").await;
+    insert_tweet(&pool, user_id, "100", 5, Utc::now()).await;
+    let subscriber = store_subscriber(&pool, "reader@example.com", "popularity", "day")
+        .await
+        .unwrap();
+
+    // unconfirmed addresses get nothing
+    send_digests_to_subscribers(&pool, &config, &mailer)
+        .await
+        .unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+
+    confirm_subscriber(&pool, &subscriber.confirm_token)
+        .await
+        .unwrap();
+    send_digests_to_subscribers(&pool, &config, &mailer)
+        .await
+        .unwrap();
+    let message = unfold(&rx.recv_timeout(Duration::from_secs(5)).unwrap());
+    assert!(message.contains("To: reader@example.com"));
+    assert!(message.contains("tweet 100"));
+    assert!(message.contains(&subscriber.unsubscribe_token));
+
+    // already sent today
+    send_digests_to_subscribers(&pool, &config, &mailer)
+        .await
+        .unwrap();
+    assert!(rx.recv_timeout(Duration::from_millis(500)).is_err());
+}
@@ -0,0 +1,142 @@
+use std::sync::Mutex;
+
+use async_trait::async_trait;
+use backend::config::DiscordChannel;
+use backend::discord::core::processors::process_discord_channel;
+use backend::discord::scrapers::gateway::DiscordGateway;
+use backend::discord::scrapers::responses::MessageObject;
+use backend::feed::model::post::fetch_top_posts;
+use chrono::{TimeZone, Utc};
+use serde_json::json;
+
+use crate::helpers::spawn_db;
+
+/// Hands out canned messages instead of calling discord, and remembers which channels it was asked about.
+struct MockGateway {
+    messages: Vec<MessageObject>,
+    requested: Mutex<Vec<String>>,
+}
+
+#[async_trait]
+impl DiscordGateway for MockGateway {
+    async fn get_recent_messages(&self, channel_id: &str) -> anyhow::Result<Vec<MessageObject>> {
+        self.requested.lock().unwrap().push(channel_id.to_string());
+        Ok(self.messages.clone())
+    }
+}
+
+impl MockGateway {
+    fn new(messages: serde_json::Value) -> Self {
+        MockGateway {
+            messages: serde_json::from_value(messages).expect("failed to deserialize messages"),
+            requested: Mutex::new(vec![]),
+        }
+    }
+}
+
+fn channel() -> DiscordChannel {
+    DiscordChannel {
+        guild_id: "428295358100013066".into(),
+        channel_id: "428304839097712640".into(),
+        name: "announcements".into(),
+    }
+}
+
+/// Shaped like GET /channels/:id/messages - newest first, reactions only there if someone reacted.
+fn messages(first_reactions: i64) -> serde_json::Value {
+    json!([
+        {
+            "id": "861000000000000003",
+            "channel_id": "428304839097712640",
+            "content": "",
+            "timestamp": "2021-07-03T10:00:00.000000+00:00",
+            "author": {"id": "1", "username": "Solana"},
+            "type": 6,
+            "pinned": false
+        },
+        {
+            "id": "861000000000000002",
+            "channel_id": "428304839097712640",
+            "content": "Mainnet beta v1.7.4 is out\nUpgrade your validators",
+            "timestamp": "2021-07-02T10:00:00.000000+00:00",
+            "author": {"id": "1", "username": "Solana"},
+            "reactions": [
+                {"count": first_reactions, "me": false, "emoji": {"id": null, "name": "🔥"}},
+                {"count": 5, "me": false, "emoji": {"id": "8", "name": "solana"}}
+            ],
+            "attachments": [{"id": "9", "url": "https://cdn.discordapp.com/attachments/notes.png"}]
+        },
+        {
+            "id": "861000000000000001",
+            "channel_id": "428304839097712640",
+            "content": "   \n  ",
+            "timestamp": "2021-07-01T10:00:00.000000+00:00",
+            "author": {"id": "1", "username": "Solana"}
+        },
+        {
+            "id": "861000000000000000",
+            "channel_id": "428304839097712640",
+            "content": "Hackathon winners announced",
+            "timestamp": "2021-07-01T09:00:00.000000+00:00",
+            "author": {"id": "2", "username": "Solana Foundation"}
+        }
+    ])
+}
+
+#[actix_rt::test]
+async fn empty_messages_are_skipped_and_reactions_summed() {
+    let pool = spawn_db().await;
+    let gateway = MockGateway::new(messages(10));
+
+    let stored = process_discord_channel(&pool, &gateway, &channel())
+        .await
+        .unwrap();
+
+    // the empty (pin notice) and whitespace-only messages don't count
+    assert_eq!(stored, 2);
+    assert_eq!(
+        *gateway.requested.lock().unwrap(),
+        vec!["428304839097712640".to_string()]
+    );
+
+    let posts = fetch_top_posts(&pool, Utc.timestamp(0, 0), 10)
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+
+    let announcement = &posts[0];
+    assert_eq!(announcement.source, "discord");
+    assert_eq!(announcement.channel, "announcements");
+    assert_eq!(announcement.title, "Mainnet beta v1.7.4 is out");
+    assert_eq!(announcement.score, Some(15));
+    assert_eq!(
+        announcement.post_url,
+        "https://discord.com/channels/428295358100013066/428304839097712640/861000000000000002"
+    );
+    assert_eq!(
+        announcement.link_url.as_deref(),
+        Some("https://cdn.discordapp.com/attachments/notes.png")
+    );
+
+    // no reactions at all = 0, not NULL
+    assert_eq!(posts[1].score, Some(0));
+    assert_eq!(posts[1].link_url, None);
+}
+
+#[actix_rt::test]
+async fn rereading_a_channel_refreshes_reactions() {
+    let pool = spawn_db().await;
+
+    process_discord_channel(&pool, &MockGateway::new(messages(10)), &channel())
+        .await
+        .unwrap();
+    process_discord_channel(&pool, &MockGateway::new(messages(40)), &channel())
+        .await
+        .unwrap();
+
+    let posts = fetch_top_posts(&pool, Utc.timestamp(0, 0), 10)
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+    assert_eq!(posts[0].score, Some(45));
+}
@@ -0,0 +1,39 @@
+use std::sync::Arc;
+
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, read_body, read_body_json, TestRequest};
+use actix_web::{web, App};
+use backend::feed::routes::serve::serve_feed;
+use backend::utils::constants::FEED_MAX_PAGE;
+
+use crate::helpers::{spawn_db, test_config};
+
+#[actix_rt::test]
+async fn pages_past_the_max_are_a_bad_request() {
+    let pool = spawn_db().await;
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(pool.clone())))
+            .app_data(web::Data::new(Arc::new(test_config())))
+            .service(serve_feed),
+    )
+    .await;
+
+    let uri = |page: u32| format!("/feed?timeframe=day&page={}", page);
+    let res = call_service(
+        &app,
+        TestRequest::get().uri(&uri(FEED_MAX_PAGE)).to_request(),
+    )
+    .await;
+    assert_eq!(res.status(), StatusCode::OK);
+    let page: serde_json::Value = read_body_json(res).await;
+    assert!(page["next_page"].is_null());
+
+    // would otherwise have every source hand over billions of rows
+    for page in [FEED_MAX_PAGE + 1, u32::MAX].iter() {
+        let res = call_service(&app, TestRequest::get().uri(&uri(*page)).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let body = read_body(res).await;
+        assert!(String::from_utf8_lossy(&body).contains("page must be at most"));
+    }
+}
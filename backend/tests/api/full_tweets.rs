@@ -0,0 +1,138 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::time::{Duration, Instant};
+
+use backend::twitter::model::media::fetch_all_media_for_tweet;
+use backend::twitter::model::tweet::{fetch_tweet, fetch_tweets, Tweet};
+use backend::twitter::model::user::fetch_user_by_uuid;
+use backend::twitter::routes::serve::{prep_full_tweets, FullTweet};
+use chrono::Utc;
+use sqlx::PgPool;
+
+use crate::helpers::{
+    count_queries, insert_media, insert_tweet, insert_user, set_replied_to, spawn_db, test_config,
+};
+
+/// `n` tweets, each with a photo, replying to a parent that in turn replies to a grandparent -
+/// so assembling them walks two levels of ancestors. Returns the ids of the bottom tweets.
+async fn insert_reply_chains(pool: &PgPool, n: usize) -> Vec<String> {
+    let mut page_ids = vec![];
+    for i in 0..n {
+        let user_id = insert_user(pool, &format!("user{}", i)).await;
+        let grandparent = format!("{}00", i + 1);
+        let parent = format!("{}01", i + 1);
+        let tweet = format!("{}02", i + 1);
+        insert_tweet(pool, user_id, &grandparent, 1, Utc::now()).await;
+        insert_tweet(pool, user_id, &parent, 1, Utc::now()).await;
+        let id = insert_tweet(pool, user_id, &tweet, 1, Utc::now()).await;
+        insert_media(pool, id, &format!("media{}", i)).await;
+        set_replied_to(pool, &parent, &grandparent).await;
+        set_replied_to(pool, &tweet, &parent).await;
+        page_ids.push(tweet);
+    }
+    page_ids
+}
+
+#[actix_rt::test]
+async fn prep_full_tweets_runs_a_fixed_number_of_queries() {
+    let pool = spawn_db().await;
+    let config = test_config();
+    let page_ids = insert_reply_chains(&pool, 20).await;
+
+    let mut counts = vec![];
+    for page_size in [1, 5, 20].iter() {
+        let tweets = fetch_tweets(&pool, &page_ids[..*page_size])
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|t| (t, None))
+            .collect::<Vec<_>>();
+        let (count, full_tweets) = count_queries(prep_full_tweets(&pool, &config, tweets, 5)).await;
+        let full_tweets = full_tweets.unwrap();
+
+        assert_eq!(full_tweets.len(), *page_size);
+        for ft in full_tweets.iter() {
+            let parent = ft.reply_to.as_ref().as_ref().expect("parent not nested");
+            assert!(parent.reply_to.is_some(), "grandparent not nested");
+            assert_eq!(ft.media.as_ref().map(|m| m.len()), Some(1));
+        }
+        counts.push(count);
+    }
+
+    // 2 levels of ancestors + authors + media + link previews + threads, whatever the page size
+    assert_eq!(counts, vec![6, 6, 6]);
+}
+
+/// How pages used to be assembled: author, media and each parent fetched one tweet at a time.
+/// Only kept around as the baseline for the benchmark below.
+fn prep_full_tweet_one_by_one(
+    pool: &PgPool,
+    tweet: Tweet,
+    depth: usize,
+) -> Pin<Box<dyn Future<Output = FullTweet> + '_>> {
+    Box::pin(async move {
+        let author = fetch_user_by_uuid(pool, tweet.user_id).await.unwrap();
+        let media = fetch_all_media_for_tweet(pool, tweet.id).await.unwrap();
+        let mut parents = vec![];
+        for parent_id in [&tweet.replied_to_tweet_id, &tweet.quoted_tweet_id].iter() {
+            let parent = match parent_id {
+                Some(parent_id) if depth > 0 => match fetch_tweet(pool, parent_id).await {
+                    Ok(parent) => Some(prep_full_tweet_one_by_one(pool, parent, depth - 1).await),
+                    Err(_) => None,
+                },
+                _ => None,
+            };
+            parents.push(parent);
+        }
+        let quote_of = parents.pop().unwrap();
+        let reply_to = parents.pop().unwrap();
+        FullTweet {
+            tweet,
+            author,
+            media: Some(media),
+            link_previews: vec![],
+            reply_to: Box::new(reply_to),
+            quote_of: Box::new(quote_of),
+            trending_score: None,
+            thread: None,
+        }
+    })
+}
+
+/// Not a pass / fail test - run it with `cargo test --test api -- --ignored --nocapture` to see the numbers.
+#[actix_rt::test]
+#[ignore]
+async fn bench_prep_full_tweets_against_per_tweet_queries() {
+    const RUNS: u32 = 20;
+    let pool = spawn_db().await;
+    let config = test_config();
+    let page_ids = insert_reply_chains(&pool, 20).await;
+    let page = || async { fetch_tweets(&pool, &page_ids).await.unwrap() };
+
+    // both paths get the same, already warmed up, pool
+    let mut one_by_one = Duration::default();
+    let mut batched = Duration::default();
+    for _ in 0..RUNS {
+        let tweets = page().await;
+        let start = Instant::now();
+        let mut full_tweets = vec![];
+        for tweet in tweets.into_iter() {
+            full_tweets.push(prep_full_tweet_one_by_one(&pool, tweet, 5).await);
+        }
+        one_by_one += start.elapsed();
+        assert_eq!(full_tweets.len(), 20);
+
+        let tweets = page().await.into_iter().map(|t| (t, None)).collect();
+        let start = Instant::now();
+        let full_tweets = prep_full_tweets(&pool, &config, tweets, 5).await.unwrap();
+        batched += start.elapsed();
+        assert_eq!(full_tweets.len(), 20);
+    }
+
+    println!(
+        "page of 20 tweets, 2 levels of ancestors, avg over {} runs: one by one {:?}, batched {:?}",
+        RUNS,
+        one_by_one / RUNS,
+        batched / RUNS,
+    );
+}
@@ -0,0 +1,200 @@
+use backend::config::Settings;
+use backend::twitter::core::processors::process_link_preview;
+use backend::twitter::scrapers::unfurl::{parse_open_graph, LinkPreviewClient};
+use chrono::{DateTime, Utc};
+use reqwest::Url;
+use sqlx::PgPool;
+
+use crate::helpers::{spawn_db, spawn_http_fixture, test_config, FixtureResponse};
+
+/// test_config, but allowed to reach the fixture server on localhost.
+fn fixture_config() -> Settings {
+    let mut config = test_config();
+    config.link_previews.allow_private_hosts = true;
+    config
+}
+
+fn page(head: &str) -> String {
+    format!(
+        "<!doctype html><html><head>{}</head><body><p>hi</p></body></html>",
+        head
+    )
+}
+
+// ----------------------------------------------------------------------------- parse_open_graph
+
+#[test]
+fn og_tags_win_over_twitter_cards_and_plain_html() {
+    let url = Url::parse("https://example.com/posts/1").unwrap();
+
+    let og = parse_open_graph(
+        &page(
+            r#"<title>Plain</title>
+            <meta name="twitter:title" content="Card">
+            <meta property="og:title" content="Open &amp; Graph">
+            <meta name="description" content="plain description">
+            <meta property="og:site_name" content='Example'>"#,
+        ),
+        &url,
+    );
+    assert_eq!(og.title.as_deref(), Some("Open & Graph"));
+    assert_eq!(og.description.as_deref(), Some("plain description"));
+    assert_eq!(og.site_name.as_deref(), Some("Example"));
+
+    let card = parse_open_graph(
+        &page(r#"<title>Plain</title><meta name="twitter:title" content="Card">"#),
+        &url,
+    );
+    assert_eq!(card.title.as_deref(), Some("Card"));
+
+    let plain = parse_open_graph(&page("<title> Plain &#8211; title </title>"), &url);
+    assert_eq!(plain.title.as_deref(), Some("Plain – title"));
+    assert_eq!(plain.image_url, None);
+}
+
+#[test]
+fn relative_images_are_resolved_against_the_page() {
+    let url = Url::parse("https://example.com/posts/1").unwrap();
+    let og = parse_open_graph(
+        &page(r#"<meta property="og:image" content="/img/cover.png">"#),
+        &url,
+    );
+    assert_eq!(
+        og.image_url.as_deref(),
+        Some("https://example.com/img/cover.png")
+    );
+
+    // not something a browser would load
+    let og = parse_open_graph(
+        &page(r#"<meta property="og:image" content="javascript:alert(1)">"#),
+        &url,
+    );
+    assert_eq!(og.image_url, None);
+}
+
+// ----------------------------------------------------------------------------- unfurl
+
+#[actix_rt::test]
+async fn redirects_are_followed_to_the_final_page() {
+    let base = spawn_http_fixture(|path| match path {
+        "/short" => FixtureResponse::redirect("/article"),
+        "/article" => FixtureResponse::html(&page(
+            r#"<meta property="og:title" content="The article"><meta property="og:image" content="cover.jpg">"#,
+        )),
+        _ => FixtureResponse::status(404),
+    });
+    let client = LinkPreviewClient::new(&fixture_config());
+
+    let unfurled = client.unfurl(&format!("{}/short", base)).await.unwrap();
+    assert_eq!(unfurled.final_url, format!("{}/article", base));
+    assert_eq!(unfurled.og.title.as_deref(), Some("The article"));
+    assert_eq!(unfurled.og.image_url, Some(format!("{}/cover.jpg", base)));
+}
+
+#[actix_rt::test]
+async fn too_many_redirects_are_refused() {
+    let base = spawn_http_fixture(|path| {
+        let hop = path.trim_start_matches("/hop/").parse::<u32>().unwrap_or(0);
+        FixtureResponse::redirect(&format!("/hop/{}", hop + 1))
+    });
+    let config = fixture_config();
+    let client = LinkPreviewClient::new(&config);
+
+    let err = client.unfurl(&format!("{}/hop/0", base)).await.unwrap_err();
+    assert!(
+        format!("{:?}", err).contains("too many redirects"),
+        "{:?}",
+        err
+    );
+}
+
+fn cut_off_pages(path: &str) -> FixtureResponse {
+    match path {
+        // the tag after </head> is never looked at
+        "/head" => FixtureResponse::html(
+            r#"<html><head><title>Head</title></head><body><meta property="og:title" content="Body"></body></html>"#,
+        ),
+        // a never ending <head> - the tag past max_bytes is never read
+        "/huge" => FixtureResponse::html(&format!(
+            r#"<html><head><title>Huge</title>{}<meta property="og:title" content="Too far"></head></html>"#,
+            " ".repeat(4096)
+        )),
+        _ => FixtureResponse::status(404),
+    }
+}
+
+#[actix_rt::test]
+async fn body_is_cut_off_at_max_bytes_and_end_of_head() {
+    let base = spawn_http_fixture(cut_off_pages);
+    let mut config = fixture_config();
+    config.link_previews.max_bytes = 1024;
+    let client = LinkPreviewClient::new(&config);
+
+    let head = client.unfurl(&format!("{}/head", base)).await.unwrap();
+    assert_eq!(head.og.title.as_deref(), Some("Head"));
+    let huge = client.unfurl(&format!("{}/huge", base)).await.unwrap();
+    assert_eq!(huge.og.title.as_deref(), Some("Huge"));
+}
+
+#[actix_rt::test]
+async fn non_html_and_private_hosts_are_refused() {
+    let base = spawn_http_fixture(|_| FixtureResponse {
+        status: 200,
+        headers: vec![("Content-Type", "application/pdf".into())],
+        body: b"%PDF-1.4".to_vec(),
+    });
+
+    let client = LinkPreviewClient::new(&fixture_config());
+    let err = client
+        .unfurl(&format!("{}/doc.pdf", base))
+        .await
+        .unwrap_err();
+    assert!(err.to_string().contains("not an html page"), "{}", err);
+
+    // what production runs with - localhost is off limits
+    let client = LinkPreviewClient::new(&test_config());
+    assert!(client.unfurl(&format!("{}/doc.pdf", base)).await.is_err());
+    assert!(client.unfurl("http://localhost/").await.is_err());
+    assert!(client
+        .unfurl("http://169.254.169.254/latest")
+        .await
+        .is_err());
+    assert!(client.unfurl("file:///etc/passwd").await.is_err());
+}
+
+// ----------------------------------------------------------------------------- process_link_preview
+
+async fn stored_failure(pool: &PgPool, url: &str) -> (i32, Option<DateTime<Utc>>) {
+    sqlx::query_as("SELECT attempts, retry_after FROM link_previews WHERE url = $1")
+        .bind(url)
+        .fetch_one(pool)
+        .await
+        .expect("no link preview stored")
+}
+
+#[actix_rt::test]
+async fn only_transient_failures_are_retried() {
+    let pool = spawn_db().await;
+    let base = spawn_http_fixture(|path| match path {
+        "/down" => FixtureResponse::status(503),
+        _ => FixtureResponse::status(404),
+    });
+    let client = LinkPreviewClient::new(&fixture_config());
+
+    let down = format!("{}/down", base);
+    process_link_preview(&pool, &client, &down).await.unwrap();
+    let (attempts, retry_after) = stored_failure(&pool, &down).await;
+    assert_eq!(attempts, 1);
+    assert!(retry_after.unwrap() > Utc::now());
+
+    // until it runs out of attempts
+    process_link_preview(&pool, &client, &down).await.unwrap();
+    process_link_preview(&pool, &client, &down).await.unwrap();
+    let (attempts, retry_after) = stored_failure(&pool, &down).await;
+    assert_eq!(attempts, 3);
+    assert_eq!(retry_after, None);
+
+    let gone = format!("{}/gone", base);
+    process_link_preview(&pool, &client, &gone).await.unwrap();
+    assert_eq!(stored_failure(&pool, &gone).await, (1, None));
+}
@@ -0,0 +1,14 @@
+mod digest;
+mod discord;
+mod feed;
+mod full_tweets;
+mod helpers;
+mod link_previews;
+mod mirror;
+mod pending;
+mod reddit;
+mod rss;
+mod search;
+mod syndication;
+mod tweets;
+mod webhooks;
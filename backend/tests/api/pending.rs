@@ -0,0 +1,40 @@
+use backend::twitter::model::pending::{fetch_pending_work, replace_pending_work, JobKind};
+
+use crate::helpers::spawn_db;
+
+fn ids(ids: &[&str]) -> Vec<String> {
+    ids.iter().map(|id| id.to_string()).collect()
+}
+
+#[actix_rt::test]
+async fn deferring_again_keeps_the_place_in_the_queue() {
+    let pool = spawn_db().await;
+    let kind = JobKind::CoreTweet;
+
+    replace_pending_work(&pool, &kind, &ids(&["1", "2"]))
+        .await
+        .unwrap();
+    let first = fetch_pending_work(&pool, &kind).await.unwrap();
+
+    // 1 got done, 2 was deferred again, 3 is new
+    replace_pending_work(&pool, &kind, &ids(&["3", "2"]))
+        .await
+        .unwrap();
+    let second = fetch_pending_work(&pool, &kind).await.unwrap();
+
+    let order = second
+        .iter()
+        .map(|w| w.object_id.as_str())
+        .collect::<Vec<_>>();
+    assert_eq!(order, vec!["2", "3"]);
+    assert_eq!(second[0].created_at, first[1].created_at);
+    assert_eq!(second[0].id, first[1].id);
+
+    // other job kinds are left alone
+    replace_pending_work(&pool, &JobKind::HelperTweet, &[])
+        .await
+        .unwrap();
+    assert_eq!(fetch_pending_work(&pool, &kind).await.unwrap().len(), 2);
+    replace_pending_work(&pool, &kind, &[]).await.unwrap();
+    assert!(fetch_pending_work(&pool, &kind).await.unwrap().is_empty());
+}
@@ -0,0 +1,77 @@
+use backend::feed::model::post::fetch_top_posts;
+use backend::reddit::core::processors::posts_worth_storing;
+use backend::reddit::model::post::store_reddit_post;
+use backend::reddit::scrapers::responses::ListingResponse;
+use chrono::{TimeZone, Utc};
+
+use crate::helpers::spawn_db;
+
+/// r/solana's top.json, cut down to 5 posts: a pinned mod thread, a link post, a text post, an nsfw one,
+/// and one of a kind other than t3 (post) - top listings don't mix kinds in practice, but we check anyway.
+fn listing() -> ListingResponse {
+    serde_json::from_str(include_str!("../fixtures/reddit_top.json"))
+        .expect("failed to deserialize reddit_top.json")
+}
+
+#[test]
+fn listing_deserializes() {
+    let listing = listing();
+    assert_eq!(listing.data.children.len(), 5);
+    assert_eq!(listing.data.after.as_deref(), Some("t3_o9e5ee"));
+
+    let link_post = &listing.data.children[1].data;
+    assert_eq!(link_post.name, "t3_o9b2bb");
+    assert_eq!(link_post.score, 845);
+    assert_eq!(link_post.num_comments, 97);
+    assert!(!link_post.is_self);
+    assert!(link_post.selftext.is_empty());
+}
+
+#[test]
+fn only_regular_posts_are_worth_storing() {
+    let listing = listing();
+    let kept = posts_worth_storing(&listing)
+        .map(|p| p.name.as_str())
+        .collect::<Vec<_>>();
+    // stickied, over_18 and non-t3 are all gone
+    assert_eq!(kept, vec!["t3_o9b2bb", "t3_o9c3cc"]);
+}
+
+#[actix_rt::test]
+async fn kept_posts_are_stored_as_link_and_text_posts() {
+    let pool = spawn_db().await;
+    let listing = listing();
+    for post in posts_worth_storing(&listing) {
+        store_reddit_post(&pool, post).await.unwrap();
+    }
+    // storing again only refreshes the metrics
+    for post in posts_worth_storing(&listing) {
+        store_reddit_post(&pool, post).await.unwrap();
+    }
+
+    let posts = fetch_top_posts(&pool, Utc.timestamp(0, 0), 10)
+        .await
+        .unwrap();
+    assert_eq!(posts.len(), 2);
+
+    let link_post = &posts[0];
+    assert_eq!(link_post.source, "reddit");
+    assert_eq!(link_post.channel, "solana");
+    assert_eq!(
+        link_post.link_url.as_deref(),
+        Some("https://twitter.com/ProjectSerum/status/1410000000000000000")
+    );
+    assert_eq!(link_post.body, None);
+    assert_eq!(link_post.posted_at, Utc.timestamp(1_625_140_000, 0));
+
+    let text_post = &posts[1];
+    assert_eq!(text_post.link_url, None);
+    assert!(text_post
+        .body
+        .as_deref()
+        .unwrap()
+        .starts_with("I've got a program"));
+    assert!(text_post
+        .post_url
+        .starts_with("https://www.reddit.com/r/solana/comments/o9c3cc/"));
+}
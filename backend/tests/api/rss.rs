@@ -0,0 +1,138 @@
+use backend::feed::model::post::{fetch_top_posts, Post};
+use backend::rss::core::processors::store_feed_entries;
+use backend::utils::general::html_to_text;
+use chrono::{DateTime, TimeZone, Utc};
+use sqlx::PgPool;
+
+use crate::helpers::spawn_db;
+
+fn cutoff() -> DateTime<Utc> {
+    Utc.ymd(2021, 6, 1).and_hms(0, 0, 0)
+}
+
+async fn store_fixture(pool: &PgPool, xml: &str) -> usize {
+    store_fixture_from(pool, xml, "https://example.com/feed").await
+}
+
+async fn store_fixture_from(pool: &PgPool, xml: &str, feed_url: &str) -> usize {
+    let feed = feed_rs::parser::parse(xml.as_bytes()).expect("failed to parse fixture");
+    store_feed_entries(pool, &feed, feed_url, cutoff()).await
+}
+
+async fn stored_posts(pool: &PgPool) -> Vec<Post> {
+    fetch_top_posts(pool, Utc.timestamp(0, 0), 100)
+        .await
+        .unwrap()
+}
+
+// ----------------------------------------------------------------------------- rss
+
+#[actix_rt::test]
+async fn rss_entries_are_deduped_on_guid_and_cut_off_by_date() {
+    let pool = spawn_db().await;
+    let xml = include_str!("../fixtures/rss_feed.xml");
+
+    // 4 items: one re-published under the same guid, one without a date, one older than the cutoff
+    assert_eq!(store_fixture(&pool, xml).await, 1);
+    // polling the same feed again adds nothing
+    assert_eq!(store_fixture(&pool, xml).await, 0);
+
+    let posts = stored_posts(&pool).await;
+    assert_eq!(posts.len(), 1);
+    let post = &posts[0];
+    assert_eq!(post.source, "rss");
+    assert_eq!(
+        post.source_id,
+        "https://example.com/feed#https://medium.com/p/hackathon-1"
+    );
+    assert_eq!(post.channel, "Solana Labs - Medium");
+    assert_eq!(post.title, "Announcing Solana Season Hackathon");
+    assert_eq!(post.author.as_deref(), Some("Solana Labs"));
+    assert_eq!(post.posted_at, Utc.ymd(2021, 7, 1).and_hms(15, 0, 0));
+    assert_eq!(post.score, None);
+    // the summary's html doesn't make it into the feed
+    assert_eq!(
+        post.body.as_deref(),
+        Some("Builders & founders:\n$5M in prizes…")
+    );
+}
+
+#[actix_rt::test]
+async fn the_same_guid_in_another_feed_is_another_entry() {
+    let pool = spawn_db().await;
+    let xml = include_str!("../fixtures/rss_feed.xml");
+
+    assert_eq!(store_fixture(&pool, xml).await, 1);
+    // eg a mirror, or a publisher numbering its posts from 1 like everyone else
+    assert_eq!(
+        store_fixture_from(&pool, xml, "https://example.org/rss").await,
+        1
+    );
+    assert_eq!(stored_posts(&pool).await.len(), 2);
+}
+
+// ----------------------------------------------------------------------------- atom
+
+#[actix_rt::test]
+async fn atom_entries_fall_back_to_updated_and_content() {
+    let pool = spawn_db().await;
+    let xml = include_str!("../fixtures/atom_feed.xml");
+
+    assert_eq!(store_fixture(&pool, xml).await, 2);
+    assert_eq!(store_fixture(&pool, xml).await, 0);
+
+    // unscored, so newest first
+    let posts = stored_posts(&pool).await;
+    assert_eq!(posts.len(), 2);
+
+    let only_updated = &posts[0];
+    assert_eq!(
+        only_updated.source_id,
+        "https://example.com/feed#tag:solana.substack.com,2021:post-22"
+    );
+    assert_eq!(
+        only_updated.posted_at,
+        Utc.ymd(2021, 7, 3).and_hms(10, 0, 0)
+    );
+    assert_eq!(
+        only_updated.body.as_deref(),
+        Some("Only updated\nNo published date, so updated counts")
+    );
+
+    let published = &posts[1];
+    assert_eq!(published.channel, "Solana Newsletter");
+    assert_eq!(
+        published.post_url,
+        "https://solana.substack.com/p/weekly-21"
+    );
+    assert_eq!(published.posted_at, Utc.ymd(2021, 7, 2).and_hms(8, 0, 0));
+    assert_eq!(
+        published.body.as_deref(),
+        Some("Serum & Raydium news,\nplus a link")
+    );
+}
+
+// ----------------------------------------------------------------------------- html_to_text
+
+#[test]
+fn html_is_flattened_to_text() {
+    assert_eq!(
+        html_to_text("<p>one</p><p>two <i>and</i>\n   a half</p>"),
+        "one\ntwo and a half"
+    );
+    assert_eq!(
+        html_to_text("<style>p { color: red }</style>a<script>alert(1)</script>b"),
+        "ab"
+    );
+    // escaped markup is text, and stays so
+    assert_eq!(html_to_text("&lt;b&gt; isn't bold"), "<b> isn't bold");
+    assert_eq!(html_to_text("1 < 2 &amp;&amp; 3 > 2"), "1 < 2 && 3 > 2");
+    assert_eq!(
+        html_to_text("&#8220;quoted&#x201D; &unknown; &"),
+        "“quoted” &unknown; &"
+    );
+    assert_eq!(
+        html_to_text("<!-- comment -->text<br/>more<img src=x"),
+        "text\nmore"
+    );
+}
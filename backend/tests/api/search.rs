@@ -0,0 +1,52 @@
+use std::sync::Arc;
+
+use actix_web::test::{call_service, init_service, read_body_json, TestRequest};
+use actix_web::{web, App};
+use backend::twitter::model::search::store_tweet_search_document;
+use backend::twitter::routes::search::serve_search;
+use backend::utils::constants::TWEET_PAGE_SIZE;
+use chrono::Utc;
+use reqwest::Url;
+
+use crate::helpers::{insert_tweet, insert_user, spawn_db, test_config};
+
+#[actix_rt::test]
+async fn search_pages_are_page_sized_and_the_last_one_has_no_cursor() {
+    let pool = spawn_db().await;
+    let user_id = insert_user(&pool, "alice").await;
+    let total = TWEET_PAGE_SIZE + 5;
+    for i in 0..total {
+        let tweet_id = (100 + i).to_string();
+        insert_tweet(&pool, user_id, &tweet_id, i, Utc::now()).await;
+        store_tweet_search_document(&pool, &tweet_id).await.unwrap();
+    }
+    let app = init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(pool.clone())))
+            .app_data(web::Data::new(Arc::new(test_config())))
+            .service(serve_search),
+    )
+    .await;
+
+    let mut cursor: Option<String> = None;
+    let mut page_sizes = vec![];
+    loop {
+        let mut params = vec![("q", "tweet".to_string()), ("timeframe", "day".into())];
+        if let Some(ref cursor) = cursor {
+            params.push(("cursor", cursor.clone()));
+        }
+        let url = Url::parse_with_params("http://localhost/search", &params).unwrap();
+        let req = TestRequest::get()
+            .uri(&format!("/search?{}", url.query().unwrap()))
+            .to_request();
+        let page: serde_json::Value = read_body_json(call_service(&app, req).await).await;
+
+        page_sizes.push(page["tweets"].as_array().unwrap().len() as i64);
+        cursor = page["next_cursor"].as_str().map(String::from);
+        if cursor.is_none() {
+            break;
+        }
+    }
+    // no trailing empty page
+    assert_eq!(page_sizes, vec![TWEET_PAGE_SIZE, 5]);
+}
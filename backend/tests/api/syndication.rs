@@ -0,0 +1,32 @@
+use actix_web::http::header;
+use actix_web::test::TestRequest;
+use backend::twitter::routes::syndication::is_not_modified;
+
+const ETAG: &str = "\"5eb63bbbe01eeed0\"";
+
+#[test]
+fn only_a_matching_etag_is_not_modified() {
+    let matching = [
+        ETAG.to_string(),
+        format!("W/{}", ETAG),
+        format!("\"other\", {}", ETAG),
+        "*".to_string(),
+    ];
+    for if_none_match in matching.iter() {
+        let req = TestRequest::default()
+            .insert_header((header::IF_NONE_MATCH, if_none_match.as_str()))
+            .to_http_request();
+        assert!(is_not_modified(&req, ETAG), "missed {}", if_none_match);
+    }
+
+    let req = TestRequest::default()
+        .insert_header((header::IF_NONE_MATCH, "W/\"other\""))
+        .to_http_request();
+    assert!(!is_not_modified(&req, ETAG));
+
+    // metrics may have changed since, so a date alone never gets a 304
+    let req = TestRequest::default()
+        .insert_header((header::IF_MODIFIED_SINCE, "Fri, 01 Jan 2100 00:00:00 GMT"))
+        .to_http_request();
+    assert!(!is_not_modified(&req, ETAG));
+}
@@ -0,0 +1,368 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::http::StatusCode;
+use actix_web::test::{call_service, init_service, read_body, read_body_json, TestRequest};
+use actix_web::{web, App};
+use backend::twitter::model::tweet::fetch_next_page_of_tweets;
+use backend::twitter::routes::serve::{
+    serve_tweets, Cursor, CursorMetric, SortBy, Timeframe, TweetParams,
+};
+use backend::utils::constants::READER_TOKEN_HEADER;
+use backend::utils::errors::ApiError;
+use chrono::{Duration, Utc};
+use reqwest::Url;
+use sqlx::PgPool;
+
+use crate::helpers::{count_rows, insert_tweet, insert_user, spawn_db, test_config};
+
+fn params(sort_by: SortBy) -> web::Query<TweetParams> {
+    web::Query(TweetParams {
+        sort_by,
+        timeframe: Timeframe::Day,
+        cursor: None,
+        unseen: None,
+        cashtag: None,
+        hashtag: None,
+        mention: None,
+    })
+}
+
+/// Just the /tweets route, on top of the test db.
+async fn tweets_app(
+    pool: &PgPool,
+) -> impl Service<actix_http::Request, Response = ServiceResponse, Error = actix_web::Error> {
+    init_service(
+        App::new()
+            .app_data(web::Data::new(Arc::new(pool.clone())))
+            .app_data(web::Data::new(Arc::new(test_config())))
+            .service(serve_tweets),
+    )
+    .await
+}
+
+/// /tweets?sort_by=..&timeframe=day&.., with every value url-encoded.
+fn tweets_uri(sort_by: &str, extra: &[(&str, &str)]) -> String {
+    let mut params = vec![("sort_by", sort_by), ("timeframe", "day")];
+    params.extend_from_slice(extra);
+    let url = Url::parse_with_params("http://localhost/tweets", &params).unwrap();
+    format!("/tweets?{}", url.query().unwrap())
+}
+
+fn encode(metric: CursorMetric, last_tweet_id: &str) -> String {
+    Cursor {
+        last_metric: metric,
+        last_tweet_id: last_tweet_id.into(),
+    }
+    .encode()
+    .unwrap()
+}
+
+// ----------------------------------------------------------------------------- sort_by
+
+#[test]
+fn sort_by_only_takes_whitelisted_values() {
+    let hostile = [
+        "popularity_count",
+        "trending_score",
+        "popularity%20DESC%3B%20DROP%20TABLE%20tweets%3B--",
+        "like_count,%20(SELECT%201)",
+        "",
+    ];
+    for sort_by in hostile.iter() {
+        let query = format!("sort_by={}&timeframe=day", sort_by);
+        assert!(
+            web::Query::<TweetParams>::from_query(&query).is_err(),
+            "accepted sort_by={}",
+            sort_by
+        );
+    }
+
+    let valid = [
+        "popularity",
+        "retweets",
+        "likes",
+        "replies",
+        "time",
+        "trending",
+    ];
+    for sort_by in valid.iter() {
+        let query = format!("sort_by={}&timeframe=day", sort_by);
+        assert!(web::Query::<TweetParams>::from_query(&query).is_ok());
+    }
+}
+
+#[test]
+fn trending_has_no_column() {
+    assert!(SortBy::Trending.column().is_none());
+    assert_eq!(SortBy::Popularity.column(), Some("popularity_count"));
+}
+
+// ----------------------------------------------------------------------------- cursor
+
+#[test]
+fn garbage_cursors_are_a_bad_request() {
+    let hostile: [String; 6] = [
+        String::new(),
+        "not base64 at all!".into(),
+        "'; DROP TABLE tweets; --".into(),
+        base64::encode_config("not json", base64::URL_SAFE_NO_PAD),
+        base64::encode_config(
+            r#"{"last_metric":{"count":"1 OR 1=1"}}"#,
+            base64::URL_SAFE_NO_PAD,
+        ),
+        base64::encode_config(
+            r#"{"last_metric":{"column":1},"last_tweet_id":"1"}"#,
+            base64::URL_SAFE_NO_PAD,
+        ),
+    ];
+    for cursor in hostile.iter() {
+        assert!(
+            matches!(
+                Cursor::decode(cursor, &SortBy::Popularity),
+                Err(ApiError::BadRequest(_))
+            ),
+            "accepted cursor {}",
+            cursor
+        );
+    }
+}
+
+#[test]
+fn cursors_only_work_for_the_sort_they_came_from() {
+    let count = encode(CursorMetric::Count(10), "1");
+    let time = encode(CursorMetric::Time(Utc::now()), "1");
+    let score = encode(CursorMetric::Score(1.5), "1");
+
+    assert!(Cursor::decode(&count, &SortBy::Likes).is_ok());
+    assert!(Cursor::decode(&count, &SortBy::Time).is_err());
+    assert!(Cursor::decode(&count, &SortBy::Trending).is_err());
+    assert!(Cursor::decode(&time, &SortBy::Time).is_ok());
+    assert!(Cursor::decode(&time, &SortBy::Popularity).is_err());
+    assert!(Cursor::decode(&score, &SortBy::Trending).is_ok());
+    assert!(Cursor::decode(&score, &SortBy::Replies).is_err());
+}
+
+#[test]
+fn score_cursors_survive_the_round_trip_bit_for_bit() {
+    // the score is compared against the same expression in postgres - 1 ulp off would skip or repeat a tweet
+    let mut bits: u64 = 0x9E37_79B9_7F4A_7C15;
+    for _ in 0..100_000 {
+        // xorshift - a reproducible spread over every f64 there is
+        bits ^= bits << 13;
+        bits ^= bits >> 7;
+        bits ^= bits << 17;
+        let score = f64::from_bits(bits);
+        if !score.is_finite() {
+            continue;
+        }
+        let cursor = Cursor::decode_score(&encode(CursorMetric::Score(score), "1")).unwrap();
+        match cursor.last_metric {
+            CursorMetric::Score(decoded) => {
+                assert_eq!(decoded.to_bits(), score.to_bits(), "{:e}", score)
+            }
+            other => panic!("expected a score, got {:?}", other),
+        }
+    }
+}
+
+// ----------------------------------------------------------------------------- fetch_next_page_of_tweets
+
+#[actix_rt::test]
+async fn hostile_cursor_values_are_only_ever_bound() {
+    let pool = spawn_db().await;
+    let user_id = insert_user(&pool, "alice").await;
+    for i in 0..3 {
+        insert_tweet(&pool, user_id, &format!("10{}", i), i, Utc::now()).await;
+    }
+
+    let encoded = encode(CursorMetric::Count(i64::MAX), "'; DROP TABLE tweets; --");
+    let cursor = Cursor::decode(&encoded, &SortBy::Popularity).unwrap();
+    let tweets = fetch_next_page_of_tweets(&pool, &params(SortBy::Popularity), Some(&cursor), None)
+        .await
+        .unwrap();
+
+    // (i64::MAX, anything) sits above every real row, so the whole table comes back - and stays there
+    assert_eq!(tweets.len(), 3);
+    assert_eq!(count_rows(&pool, "tweets").await, 3);
+}
+
+#[actix_rt::test]
+async fn extreme_cursor_values_dont_error() {
+    let pool = spawn_db().await;
+    let user_id = insert_user(&pool, "alice").await;
+    insert_tweet(&pool, user_id, "100", 5, Utc::now()).await;
+
+    let bottom = Cursor {
+        last_metric: CursorMetric::Count(i64::MIN),
+        last_tweet_id: String::new(),
+    };
+    let tweets = fetch_next_page_of_tweets(&pool, &params(SortBy::Likes), Some(&bottom), None)
+        .await
+        .unwrap();
+    assert!(tweets.is_empty());
+
+    let far_future = Cursor {
+        last_metric: CursorMetric::Time(Utc::now() + Duration::days(365 * 1000)),
+        last_tweet_id: String::new(),
+    };
+    let tweets = fetch_next_page_of_tweets(&pool, &params(SortBy::Time), Some(&far_future), None)
+        .await
+        .unwrap();
+    assert_eq!(tweets.len(), 1);
+}
+
+#[actix_rt::test]
+async fn mismatched_cursor_or_trending_sort_is_refused() {
+    let pool = spawn_db().await;
+
+    let score = Cursor {
+        last_metric: CursorMetric::Score(1.0),
+        last_tweet_id: "1".into(),
+    };
+    assert!(
+        fetch_next_page_of_tweets(&pool, &params(SortBy::Popularity), Some(&score), None)
+            .await
+            .is_err()
+    );
+    // trending has its own query - there's no column to put into this one
+    assert!(
+        fetch_next_page_of_tweets(&pool, &params(SortBy::Trending), None, None)
+            .await
+            .is_err()
+    );
+}
+
+#[actix_rt::test]
+async fn paging_through_ties_skips_and_repeats_nothing() {
+    let pool = spawn_db().await;
+    let user_id = insert_user(&pool, "alice").await;
+    let now = Utc::now();
+    for i in 0..25 {
+        // every tweet has the same popularity - only tweet_id can tell them apart
+        insert_tweet(&pool, user_id, &format!("{}", 1000 + i), 7, now).await;
+    }
+
+    let form = params(SortBy::Popularity);
+    let first = fetch_next_page_of_tweets(&pool, &form, None, None)
+        .await
+        .unwrap();
+    assert_eq!(first.len(), 20);
+
+    let cursor = Cursor::after(first.last().unwrap(), None, &SortBy::Popularity);
+    let second = fetch_next_page_of_tweets(&pool, &form, Some(&cursor), None)
+        .await
+        .unwrap();
+    assert_eq!(second.len(), 5);
+
+    let seen = first
+        .iter()
+        .chain(second.iter())
+        .map(|t| t.tweet_id.clone())
+        .collect::<HashSet<String>>();
+    assert_eq!(seen.len(), 25);
+}
+
+// ----------------------------------------------------------------------------- GET /tweets
+
+#[actix_rt::test]
+async fn garbage_query_values_get_a_400() {
+    let pool = spawn_db().await;
+    let app = tweets_app(&pool).await;
+
+    let uris = [
+        tweets_uri("popularity DESC; DROP TABLE tweets;--", &[]),
+        tweets_uri("popularity", &[("timeframe", "forever")]),
+        tweets_uri("popularity", &[("cursor", "not base64 at all!")]),
+        tweets_uri("popularity", &[("cursor", "'; DROP TABLE tweets; --")]),
+        tweets_uri(
+            "popularity",
+            &[("cursor", &encode(CursorMetric::Time(Utc::now()), "1"))],
+        ),
+        tweets_uri(
+            "trending",
+            &[("cursor", &encode(CursorMetric::Count(1), "1"))],
+        ),
+    ];
+    for uri in uris.iter() {
+        let res = call_service(&app, TestRequest::get().uri(uri).to_request()).await;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST, "{}", uri);
+    }
+
+    // cursor errors say what was wrong, not how the server fell over
+    let uri = tweets_uri("popularity", &[("cursor", "'; DROP TABLE tweets; --")]);
+    let res = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+    let body = read_body(res).await;
+    assert!(String::from_utf8_lossy(&body).starts_with("bad request:"));
+    assert_eq!(count_rows(&pool, "tweets").await, 0);
+}
+
+#[actix_rt::test]
+async fn hostile_cursor_over_http_is_only_ever_bound() {
+    let pool = spawn_db().await;
+    let user_id = insert_user(&pool, "alice").await;
+    for i in 0..3 {
+        insert_tweet(&pool, user_id, &format!("10{}", i), i, Utc::now()).await;
+    }
+    let app = tweets_app(&pool).await;
+
+    let cursor = encode(CursorMetric::Count(i64::MAX), "'; DROP TABLE tweets; --");
+    let uri = tweets_uri("popularity", &[("cursor", &cursor)]);
+    let res = call_service(&app, TestRequest::get().uri(&uri).to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+
+    let page: serde_json::Value = read_body_json(res).await;
+    assert_eq!(page["tweets"].as_array().unwrap().len(), 3);
+    // a short page is the last one
+    assert!(page["next_cursor"].is_null());
+    assert_eq!(count_rows(&pool, "tweets").await, 3);
+}
+
+#[actix_rt::test]
+async fn only_readers_with_a_token_or_asking_for_unseen_are_tracked() {
+    let pool = spawn_db().await;
+    let user_id = insert_user(&pool, "alice").await;
+    insert_tweet(&pool, user_id, "100", 1, Utc::now()).await;
+    let app = tweets_app(&pool).await;
+
+    // anonymous browsing - nothing minted, nothing recorded
+    let req = TestRequest::get().uri(&tweets_uri("popularity", &[]));
+    let res = call_service(&app, req.to_request()).await;
+    assert_eq!(res.status(), StatusCode::OK);
+    assert!(res.headers().get(READER_TOKEN_HEADER).is_none());
+    assert_eq!(count_rows(&pool, "seen_tweets").await, 0);
+
+    // a token that isn't one counts as none
+    let req = TestRequest::get()
+        .uri(&tweets_uri("popularity", &[]))
+        .insert_header((READER_TOKEN_HEADER, "'; DROP TABLE seen_tweets; --"));
+    let res = call_service(&app, req.to_request()).await;
+    assert!(res.headers().get(READER_TOKEN_HEADER).is_none());
+    assert_eq!(count_rows(&pool, "seen_tweets").await, 0);
+
+    // asking for unseen mints a token, and the page gets recorded against it
+    let req = TestRequest::get().uri(&tweets_uri("popularity", &[("unseen", "true")]));
+    let res = call_service(&app, req.to_request()).await;
+    let token = res
+        .headers()
+        .get(READER_TOKEN_HEADER)
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(res.headers().get("set-cookie").is_some());
+    assert_eq!(count_rows(&pool, "seen_tweets").await, 1);
+
+    // so the same reader asking again gets nothing new
+    let req = TestRequest::get()
+        .uri(&tweets_uri("popularity", &[("unseen", "true")]))
+        .insert_header((READER_TOKEN_HEADER, token.as_str()));
+    let res = call_service(&app, req.to_request()).await;
+    assert_eq!(
+        res.headers().get(READER_TOKEN_HEADER).unwrap(),
+        token.as_str()
+    );
+    let page: serde_json::Value = read_body_json(res).await;
+    assert!(page["tweets"].as_array().unwrap().is_empty());
+}
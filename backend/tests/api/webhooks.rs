@@ -0,0 +1,235 @@
+use backend::twitter::routes::serve::{prep_full_tweets, FullTweet};
+use backend::utils::constants::WEBHOOK_MAX_ATTEMPTS;
+use backend::webhooks::model::delivery::{
+    claim_delivery, fetch_undelivered_tweets, record_delivery_outcome, DeliveryStatus,
+};
+use backend::webhooks::model::webhook::Webhook;
+use backend::webhooks::senders::payloads::WebhookPayload;
+use chrono::{Duration, Utc};
+use sqlx::types::Uuid;
+use sqlx::PgPool;
+
+use crate::helpers::{insert_tweet, insert_user, spawn_db, test_config};
+
+fn webhook_for(target: &str, chat_id: Option<&str>) -> Webhook {
+    Webhook {
+        id: Uuid::new_v4(),
+        created_at: Utc::now(),
+        name: format!("team {}", target),
+        target: target.into(),
+        url: "https://example.com/hook".into(),
+        chat_id: chat_id.map(String::from),
+        min_popularity: 5,
+        active: true,
+    }
+}
+
+async fn undelivered_tweet_ids(pool: &PgPool, webhook: &Webhook) -> Vec<String> {
+    fetch_undelivered_tweets(
+        pool,
+        webhook.id,
+        webhook.min_popularity,
+        Utc::now() - Duration::hours(1),
+        WEBHOOK_MAX_ATTEMPTS,
+        10,
+    )
+    .await
+    .unwrap()
+    .into_iter()
+    .map(|t| t.tweet_id)
+    .collect()
+}
+
+async fn fail(pool: &PgPool, delivery_id: Uuid) {
+    record_delivery_outcome(
+        pool,
+        delivery_id,
+        DeliveryStatus::Failed,
+        Some(500),
+        Some("boom".into()),
+    )
+    .await
+    .unwrap();
+}
+
+// ----------------------------------------------------------------------------- deliveries
+
+#[actix_rt::test]
+async fn a_delivery_is_only_ever_claimed_once_unless_it_failed() {
+    let pool = spawn_db().await;
+    let webhook = webhook_for("slack", None);
+    let user_id = insert_user(&pool, "alice").await;
+    let tweet_id = insert_tweet(&pool, user_id, "100", 10, Utc::now()).await;
+
+    let delivery_id = claim_delivery(&pool, webhook.id, tweet_id)
+        .await
+        .unwrap()
+        .expect("first claim should win");
+    // pending - another run (or one that crashed mid-send) has it
+    assert_eq!(
+        claim_delivery(&pool, webhook.id, tweet_id).await.unwrap(),
+        None
+    );
+
+    // failed ones are up for grabs again, under the same delivery
+    fail(&pool, delivery_id).await;
+    assert_eq!(
+        claim_delivery(&pool, webhook.id, tweet_id).await.unwrap(),
+        Some(delivery_id)
+    );
+
+    record_delivery_outcome(
+        &pool,
+        delivery_id,
+        DeliveryStatus::Delivered,
+        Some(200),
+        None,
+    )
+    .await
+    .unwrap();
+    assert_eq!(
+        claim_delivery(&pool, webhook.id, tweet_id).await.unwrap(),
+        None
+    );
+
+    // deliveries are per webhook
+    let other = webhook_for("discord", None);
+    assert!(claim_delivery(&pool, other.id, tweet_id)
+        .await
+        .unwrap()
+        .is_some());
+}
+
+#[actix_rt::test]
+async fn failed_deliveries_are_retried_until_max_attempts() {
+    let pool = spawn_db().await;
+    let webhook = webhook_for("slack", None);
+    let user_id = insert_user(&pool, "alice").await;
+    let tweet_id = insert_tweet(&pool, user_id, "100", 10, Utc::now()).await;
+    // under the webhook's threshold
+    insert_tweet(&pool, user_id, "101", 1, Utc::now()).await;
+
+    assert_eq!(undelivered_tweet_ids(&pool, &webhook).await, vec!["100"]);
+
+    for attempt in 1..=WEBHOOK_MAX_ATTEMPTS {
+        let delivery_id = claim_delivery(&pool, webhook.id, tweet_id)
+            .await
+            .unwrap()
+            .unwrap();
+        // not while it's pending
+        assert!(undelivered_tweet_ids(&pool, &webhook).await.is_empty());
+
+        fail(&pool, delivery_id).await;
+        let expected: Vec<String> = if attempt < WEBHOOK_MAX_ATTEMPTS {
+            vec!["100".into()]
+        } else {
+            vec![]
+        };
+        assert_eq!(
+            undelivered_tweet_ids(&pool, &webhook).await,
+            expected,
+            "after attempt {}",
+            attempt
+        );
+    }
+}
+
+// ----------------------------------------------------------------------------- payloads
+
+/// A tweet with everything in it that chat markup could trip over.
+async fn tricky_full_tweet(pool: &PgPool) -> FullTweet {
+    let user_id = insert_user(pool, "alice").await;
+    insert_tweet(pool, user_id, "100", 10, Utc::now()).await;
+    sqlx::query("UPDATE users SET twitter_name = 'A&B <team>'")
+        .execute(pool)
+        .await
+        .unwrap();
+    sqlx::query(
+        r#"
+        UPDATE tweets SET tweet_text = '<b>fish & "chips"</b>' || chr(10) || 'it''s <i>not</i> bold'
+        "#,
+    )
+    .execute(pool)
+    .await
+    .unwrap();
+
+    let tweets = fetch_undelivered_tweets(
+        pool,
+        Uuid::new_v4(),
+        0,
+        Utc::now() - Duration::hours(1),
+        WEBHOOK_MAX_ATTEMPTS,
+        10,
+    )
+    .await
+    .unwrap();
+    prep_full_tweets(
+        pool,
+        &test_config(),
+        tweets.into_iter().map(|t| (t, None)).collect(),
+        0,
+    )
+    .await
+    .unwrap()
+    .pop()
+    .unwrap()
+}
+
+#[actix_rt::test]
+async fn payloads_are_escaped_for_each_target() {
+    let pool = spawn_db().await;
+    let ft = tricky_full_tweet(&pool).await;
+
+    match WebhookPayload::for_webhook(&webhook_for("slack", None), &ft).unwrap() {
+        WebhookPayload::Slack(message) => {
+            // mrkdwn only cares about &, < and >
+            assert!(message.text.starts_with("*A&amp;B &lt;team&gt;* (@alice)"));
+            assert!(message.text.contains(
+                ">&lt;b&gt;fish &amp; \"chips\"&lt;/b&gt;\n>it's &lt;i&gt;not&lt;/i&gt; bold"
+            ));
+            assert!(message
+                .text
+                .ends_with("<https://twitter.com/i/status/100|view on twitter>"));
+            assert!(!message.unfurl_links);
+        }
+        other => panic!("expected a slack message, got {:?}", other),
+    }
+
+    match WebhookPayload::for_webhook(&webhook_for("telegram", Some("-1001234")), &ft).unwrap() {
+        WebhookPayload::Telegram(message) => {
+            assert_eq!(message.chat_id, "-1001234");
+            assert_eq!(message.parse_mode, "HTML");
+            assert!(message
+                .text
+                .starts_with("<b>A&amp;B &lt;team&gt;</b> (@alice)"));
+            assert!(message.text.contains(
+                "&lt;b&gt;fish &amp; &quot;chips&quot;&lt;/b&gt;\nit&#39;s &lt;i&gt;not&lt;/i&gt; bold"
+            ));
+        }
+        other => panic!("expected a telegram message, got {:?}", other),
+    }
+
+    // embeds aren't markup - the text goes in as it is
+    match WebhookPayload::for_webhook(&webhook_for("discord", None), &ft).unwrap() {
+        WebhookPayload::Discord(message) => {
+            assert_eq!(message.embeds[0].author.name, "A&B <team>");
+            assert_eq!(
+                message.embeds[0].description,
+                "<b>fish & \"chips\"</b>\nit's <i>not</i> bold"
+            );
+        }
+        other => panic!("expected a discord message, got {:?}", other),
+    }
+}
+
+#[actix_rt::test]
+async fn misconfigured_webhooks_get_no_payload() {
+    let pool = spawn_db().await;
+    let ft = tricky_full_tweet(&pool).await;
+
+    let err = WebhookPayload::for_webhook(&webhook_for("telegram", None), &ft).unwrap_err();
+    assert!(err.to_string().contains("has no chat_id"), "{}", err);
+
+    let err = WebhookPayload::for_webhook(&webhook_for("teams", None), &ft).unwrap_err();
+    assert!(err.to_string().contains("unsupported"), "{}", err);
+}
@@ -25,6 +25,16 @@ export const fetchSecure = async function (path, options) {
   return data
 }
 
+// same as fetchSecure, for when the response headers matter too
+export const fetchSecureWithHeaders = async function (path, options) {
+  const {data, headers} = await axios({
+    method: 'get',
+    url: `${host}/${path}`,
+    ...options,
+  })
+  return {data, headers}
+}
+
 export const postSecure = async function (path, payload,options) {
   const {data} = await axios({
     method: 'post',